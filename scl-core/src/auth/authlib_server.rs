@@ -0,0 +1,416 @@
+//! authlib-injector 外置登录服务器的管理模块
+//!
+//! 可以通过服务器地址或者拖放得到的 `authlib-injector:yggdrasil-server:<url>` 链接添加服务器，
+//! 并随时刷新服务器的元数据（名称、主页、皮肤域名白名单、功能选项和签名公钥等）。
+//!
+//! 详情参考[启动器技术规范](https://github.com/yushijinhun/authlib-injector/wiki/%E5%90%AF%E5%8A%A8%E5%99%A8%E6%8A%80%E6%9C%AF%E8%A7%84%E8%8C%83)
+
+use std::collections::BTreeMap;
+
+use base64::prelude::*;
+use serde_json::Value;
+
+use super::{error::AuthError, store::AccountStorage, structs::AuthMethod};
+use crate::prelude::*;
+
+/// 拖放外置登录服务器时使用的链接前缀
+pub const DRAG_AND_DROP_PREFIX: &str = "authlib-injector:yggdrasil-server:";
+
+/// 外置登录服务器元数据中的功能选项前缀
+const FEATURE_PREFIX: &str = "feature.";
+
+/// 将用户输入的服务器地址或拖放链接转换成服务器地址
+///
+/// 支持以下格式：
+/// - `authlib-injector:yggdrasil-server:` 开头的拖放链接，其后为经过 URL 编码的服务器地址
+/// - 完整的服务器地址，例如 `https://example.com/api/yggdrasil/`
+/// - 省略了协议的服务器地址，例如 `example.com/api/yggdrasil/`，会补全为 `https://`
+pub fn parse_server_uri(input: &str) -> DynResult<String> {
+    let input = input.trim();
+    let input = if let Some(encoded) = input.strip_prefix(DRAG_AND_DROP_PREFIX) {
+        urlencoding::decode(encoded)
+            .map_err(|_| anyhow::anyhow!("无法解码拖放的服务器地址：{}", encoded))?
+            .trim()
+            .to_string()
+    } else {
+        input.to_owned()
+    };
+    if input.is_empty() {
+        anyhow::bail!("服务器地址为空");
+    }
+    let input = if input.starts_with("http://") || input.starts_with("https://") {
+        input
+    } else {
+        format!("https://{input}")
+    };
+    let url = url::Url::parse(&input)
+        .map_err(|e| anyhow::anyhow!("服务器地址格式不正确：{} {:?}", input, e))?;
+    Ok(url.to_string())
+}
+
+/// 根据 API 地址指示（ALI）取得服务器的实际 API 地址，返回的地址总会以 `/` 结尾
+pub async fn resolve_api_location(server_url: &str) -> DynResult<String> {
+    let resp = crate::http::get(server_url)
+        .await
+        .map_err(|_| anyhow::anyhow!("无法请求 Authlib API 服务器：{}", server_url))?;
+    let api_location = if let Some(h) = resp.header("X-Authlib-Injector-API-Location") {
+        url::Url::parse(server_url)?.join(h.last().as_str())?
+    } else {
+        url::Url::parse(server_url)?
+    };
+    let api_location = api_location.to_string();
+    if api_location.ends_with('/') {
+        Ok(api_location)
+    } else {
+        Ok(format!("{api_location}/"))
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct RawServerLinks {
+    homepage: String,
+    register: String,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+#[serde(rename_all = "camelCase")]
+struct RawServerInfo {
+    server_name: String,
+    implementation_name: String,
+    implementation_version: String,
+    links: RawServerLinks,
+    #[serde(flatten)]
+    extra: BTreeMap<String, Value>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+#[serde(rename_all = "camelCase")]
+struct RawServerMeta {
+    meta: RawServerInfo,
+    skin_domains: Vec<String>,
+    signature_publickey: String,
+}
+
+/// 外置登录服务器的元数据
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AuthlibServerMeta {
+    /// 服务器名称，服务器没有提供时为 API 地址的域名
+    pub server_name: String,
+    /// 服务器主页，服务器没有提供时为 API 地址的源
+    pub homepage: String,
+    /// 服务器的注册页面，可能为空
+    pub register: String,
+    /// 服务端实现的名称，可能为空
+    pub implementation_name: String,
+    /// 服务端实现的版本，可能为空
+    pub implementation_version: String,
+    /// 材质域名白名单
+    pub skin_domains: Vec<String>,
+    /// 用于验证材质签名的公钥，PEM 格式
+    pub signature_publickey: String,
+    /// 服务器声明的功能选项，键名不含 `feature.` 前缀，例如 `non_email_login`
+    pub features: BTreeMap<String, bool>,
+}
+
+impl AuthlibServerMeta {
+    fn parse(api_location: &str, data: &[u8]) -> DynResult<Self> {
+        let raw: RawServerMeta = serde_json::from_slice(data)
+            .map_err(|e| anyhow::anyhow!("无法解析 Authlib 服务器元数据：{:?}", e))?;
+        let api_location_url = url::Url::parse(api_location)?;
+        let server_name = if raw.meta.server_name.is_empty() {
+            api_location_url
+                .host()
+                .ok_or_else(|| anyhow::anyhow!("无法取得 Authlib 服务器接口的 Host 部分"))?
+                .to_string()
+        } else {
+            raw.meta.server_name
+        };
+        let homepage = if raw.meta.links.homepage.is_empty() {
+            api_location_url.origin().ascii_serialization()
+        } else {
+            raw.meta.links.homepage
+        };
+        let features = raw
+            .meta
+            .extra
+            .into_iter()
+            .filter_map(|(key, value)| {
+                let key = key.strip_prefix(FEATURE_PREFIX)?;
+                Some((key.to_owned(), value.as_bool()?))
+            })
+            .collect();
+        Ok(Self {
+            server_name,
+            homepage,
+            register: raw.meta.links.register,
+            implementation_name: raw.meta.implementation_name,
+            implementation_version: raw.meta.implementation_version,
+            skin_domains: raw.skin_domains,
+            signature_publickey: raw.signature_publickey,
+            features,
+        })
+    }
+
+    /// 服务器是否启用了某个功能选项，传入的名称不含 `feature.` 前缀
+    pub fn has_feature(&self, name: &str) -> bool {
+        self.features.get(name).copied().unwrap_or_default()
+    }
+}
+
+/// 一个外置登录服务器
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuthlibServer {
+    /// 服务器的 API 地址，以 `/` 结尾
+    pub api_location: String,
+    /// 解析后的服务器元数据
+    pub meta: AuthlibServerMeta,
+    /// 经过 Base64 编码的原始元数据，用于启动游戏时预先提供给 authlib-injector
+    pub raw_meta: String,
+    /// 最后一次成功获取元数据的 UNIX 时间戳（秒）
+    pub updated_at: u64,
+}
+
+impl AuthlibServer {
+    /// 通过服务器地址或拖放链接获取服务器信息
+    ///
+    /// 支持的格式参考 [`parse_server_uri`]
+    pub async fn fetch(input: &str) -> DynResult<Self> {
+        let server_url = parse_server_uri(input)?;
+        let api_location = resolve_api_location(&server_url).await?;
+        Self::fetch_api_location(api_location).await
+    }
+
+    /// 直接通过 API 地址获取服务器信息，不会进行 API 地址指示的解析
+    pub async fn fetch_api_location(api_location: impl Into<String>) -> DynResult<Self> {
+        let mut api_location = api_location.into();
+        if !api_location.ends_with('/') {
+            api_location.push('/');
+        }
+        let mut res = crate::http::get(&api_location)
+            .await
+            .map_err(AuthError::network)?;
+        if !res.status().is_success() {
+            return Err(AuthError::network(format!(
+                "获取服务器元数据失败，服务器返回了状态码 {}",
+                res.status()
+            ))
+            .into());
+        }
+        let data = res.body_bytes().await.map_err(AuthError::network)?;
+        let meta = AuthlibServerMeta::parse(&api_location, &data)?;
+        Ok(Self {
+            api_location,
+            meta,
+            raw_meta: BASE64_STANDARD.encode(data),
+            updated_at: super::unix_timestamp(),
+        })
+    }
+
+    /// 重新获取服务器元数据，失败时保留原有的数据
+    pub async fn refresh(&mut self) -> DynResult {
+        *self = Self::fetch_api_location(self.api_location.to_owned()).await?;
+        Ok(())
+    }
+
+    /// 将服务器的最新信息写入使用此服务器登录的账户中，返回账户是否属于此服务器
+    pub fn update_account(&self, account: &mut AuthMethod) -> bool {
+        match account {
+            AuthMethod::AuthlibInjector {
+                api_location,
+                server_name,
+                server_homepage,
+                server_meta,
+                ..
+            } if *api_location == self.api_location => {
+                server_name.clone_from(&self.meta.server_name);
+                server_homepage.clone_from(&self.meta.homepage);
+                server_meta.clone_from(&self.raw_meta);
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+/// 启动游戏前获取服务器元数据的超时时间，超时后将使用账户中保存的元数据
+const PREFETCH_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
+
+/// 获取启动游戏时预先提供给 authlib-injector 的元数据
+///
+/// 会尝试在短时间内重新获取服务器元数据并写入账户中，调用方应当保存更新后的账户；
+/// 如果失败或超时（例如处于离线状态）则使用账户中保存的元数据
+pub async fn fetch_prefetched_meta(account: &mut AuthMethod) -> String {
+    let AuthMethod::AuthlibInjector {
+        api_location,
+        server_meta,
+        ..
+    } = account
+    else {
+        return String::new();
+    };
+    let fetch = AuthlibServer::fetch_api_location(api_location.to_owned());
+    let timeout = async {
+        inner_future::Timer::after(PREFETCH_TIMEOUT).await;
+        Err(AuthError::network("获取服务器元数据超时").into())
+    };
+    match inner_future::future::or(fetch, timeout).await {
+        Ok(server) => {
+            server.update_account(account);
+            server.raw_meta
+        }
+        Err(err) => {
+            tracing::warn!(
+                "无法获取 Authlib 服务器 {} 的最新元数据，将使用保存的元数据：{:?}",
+                api_location,
+                err
+            );
+            server_meta.to_owned()
+        }
+    }
+}
+
+/// 外置登录服务器列表
+///
+/// 可以通过 [`AccountStorage`] 保存和读取，刷新失败的服务器会保留最后一次成功获取的元数据以供离线使用
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct AuthlibServerRegistry {
+    servers: Vec<AuthlibServer>,
+}
+
+impl AuthlibServerRegistry {
+    /// 创建一个空的服务器列表
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 从存储后端中读取服务器列表，如果还没有保存过则返回空列表
+    pub fn load(storage: &impl AccountStorage) -> DynResult<Self> {
+        match storage.read()? {
+            Some(data) => Ok(serde_json::from_slice(&data)?),
+            None => Ok(Self::default()),
+        }
+    }
+
+    /// 将服务器列表保存到存储后端中
+    pub fn save(&self, storage: &impl AccountStorage) -> DynResult {
+        storage.write(&serde_json::to_vec_pretty(self)?)
+    }
+
+    /// 获取所有的服务器
+    pub fn servers(&self) -> &[AuthlibServer] {
+        &self.servers
+    }
+
+    /// 根据 API 地址获取服务器
+    pub fn get(&self, api_location: &str) -> Option<&AuthlibServer> {
+        self.servers.iter().find(|x| x.api_location == api_location)
+    }
+
+    /// 通过服务器地址或拖放链接添加服务器，如果服务器已经存在则会更新其元数据
+    ///
+    /// 支持的格式参考 [`parse_server_uri`]
+    pub async fn add(&mut self, input: &str) -> DynResult<&AuthlibServer> {
+        let server = AuthlibServer::fetch(input).await?;
+        Ok(self.insert(server))
+    }
+
+    /// 添加一个已经获取到的服务器，如果 API 地址相同的服务器已经存在则会替换它
+    pub fn insert(&mut self, server: AuthlibServer) -> &AuthlibServer {
+        let index = if let Some(index) = self
+            .servers
+            .iter()
+            .position(|x| x.api_location == server.api_location)
+        {
+            self.servers[index] = server;
+            index
+        } else {
+            self.servers.push(server);
+            self.servers.len() - 1
+        };
+        &self.servers[index]
+    }
+
+    /// 根据 API 地址移除服务器，返回被移除的服务器
+    pub fn remove(&mut self, api_location: &str) -> Option<AuthlibServer> {
+        let index = self
+            .servers
+            .iter()
+            .position(|x| x.api_location == api_location)?;
+        Some(self.servers.remove(index))
+    }
+
+    /// 刷新所有服务器的元数据
+    ///
+    /// 刷新失败的服务器会保留原有的数据，返回所有刷新失败的服务器 API 地址及其错误
+    pub async fn refresh_all(&mut self) -> Vec<(String, anyhow::Error)> {
+        let results = futures::future::join_all(
+            self.servers
+                .iter()
+                .map(|x| AuthlibServer::fetch_api_location(x.api_location.to_owned())),
+        )
+        .await;
+        let mut errors = Vec::new();
+        for (server, result) in self.servers.iter_mut().zip(results) {
+            match result {
+                Ok(new_server) => *server = new_server,
+                Err(err) => errors.push((server.api_location.to_owned(), err)),
+            }
+        }
+        errors
+    }
+
+    /// 将服务器列表中的最新信息写入对应的账户中，返回被更新的账户数量
+    pub fn update_accounts<'a>(
+        &self,
+        accounts: impl IntoIterator<Item = &'a mut AuthMethod>,
+    ) -> usize {
+        accounts
+            .into_iter()
+            .map(|account| self.servers.iter().any(|x| x.update_account(account)))
+            .filter(|x| *x)
+            .count()
+    }
+}
+
+#[test]
+fn authlib_server_test() {
+    assert_eq!(
+        parse_server_uri(
+            "authlib-injector:yggdrasil-server:https%3A%2F%2Flittleskin.cn%2Fapi%2Fyggdrasil"
+        )
+        .unwrap(),
+        "https://littleskin.cn/api/yggdrasil"
+    );
+    assert_eq!(
+        parse_server_uri(" littleskin.cn/api/yggdrasil/ ").unwrap(),
+        "https://littleskin.cn/api/yggdrasil/"
+    );
+    assert!(parse_server_uri(DRAG_AND_DROP_PREFIX).is_err());
+
+    let meta = AuthlibServerMeta::parse(
+        "https://example.com/api/yggdrasil/",
+        br#"{
+            "meta": {
+                "implementationName": "yggdrasil-mock",
+                "links": { "register": "https://example.com/register" },
+                "feature.non_email_login": true,
+                "feature.legacy_skin_api": false
+            },
+            "skinDomains": ["example.com", ".example.com"],
+            "signaturePublickey": "-----BEGIN PUBLIC KEY-----"
+        }"#,
+    )
+    .unwrap();
+    assert_eq!(meta.server_name, "example.com");
+    assert_eq!(meta.homepage, "https://example.com");
+    assert_eq!(meta.register, "https://example.com/register");
+    assert_eq!(meta.skin_domains.len(), 2);
+    assert!(meta.has_feature("non_email_login"));
+    assert!(!meta.has_feature("legacy_skin_api"));
+    assert!(!meta.has_feature("username_check"));
+}
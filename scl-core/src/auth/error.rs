@@ -0,0 +1,210 @@
+//! 账户验证过程中可能出现的错误
+//!
+//! 验证函数依然返回 [`anyhow::Error`]，如需针对性地展示错误信息，
+//! 可以使用 [`anyhow::Error::downcast_ref`] 取出 [`AuthError`]。
+
+use std::fmt::Display;
+
+/// XSTS 授权失败时返回的 `XErr` 错误码
+///
+/// 具体请查阅 <https://wiki.vg/Microsoft_Authentication_Scheme#Authenticate_with_XSTS>
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum XstsError {
+    /// `2148916227`：该账户已被 Xbox 封禁
+    Banned,
+    /// `2148916229`：该账户受到限制，需要监护人授权才能进行在线游戏
+    GuardianConsentRequired,
+    /// `2148916233`：该微软账户没有关联的 Xbox 账户，需要先注册一个
+    NoXboxAccount,
+    /// `2148916234`：该账户尚未同意 Xbox 服务条款
+    TermsNotAccepted,
+    /// `2148916235`：该账户所在的国家或地区无法使用 Xbox Live
+    RegionUnavailable,
+    /// `2148916236`：该账户需要进行成人验证（韩国）
+    AdultVerificationRequired,
+    /// `2148916237`：该账户需要进行年龄验证（韩国）
+    AgeVerificationRequired,
+    /// `2148916238`：该账户为未满 18 岁的儿童账户，需要被成人添加到家庭组中
+    ChildAccount,
+    /// 其它未知的错误码
+    Unknown(u64),
+}
+
+impl XstsError {
+    /// 根据 `XErr` 错误码获取对应的错误
+    pub fn from_code(code: u64) -> Self {
+        match code {
+            2148916227 => Self::Banned,
+            2148916229 => Self::GuardianConsentRequired,
+            2148916233 => Self::NoXboxAccount,
+            2148916234 => Self::TermsNotAccepted,
+            2148916235 => Self::RegionUnavailable,
+            2148916236 => Self::AdultVerificationRequired,
+            2148916237 => Self::AgeVerificationRequired,
+            2148916238 => Self::ChildAccount,
+            code => Self::Unknown(code),
+        }
+    }
+
+    /// 获取该错误的 `XErr` 错误码
+    pub fn code(&self) -> u64 {
+        match self {
+            Self::Banned => 2148916227,
+            Self::GuardianConsentRequired => 2148916229,
+            Self::NoXboxAccount => 2148916233,
+            Self::TermsNotAccepted => 2148916234,
+            Self::RegionUnavailable => 2148916235,
+            Self::AdultVerificationRequired => 2148916236,
+            Self::AgeVerificationRequired => 2148916237,
+            Self::ChildAccount => 2148916238,
+            Self::Unknown(code) => *code,
+        }
+    }
+}
+
+impl Display for XstsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Banned => write!(f, "该账户已被 Xbox 封禁"),
+            Self::GuardianConsentRequired => {
+                write!(f, "该账户受到限制，需要监护人在家庭设置中允许在线游戏")
+            }
+            Self::NoXboxAccount => {
+                write!(f, "该微软账户还没有 Xbox 账户，请先登录 Xbox 官网创建一个")
+            }
+            Self::TermsNotAccepted => {
+                write!(
+                    f,
+                    "该账户尚未同意 Xbox 服务条款，请先登录 Xbox 官网完成设置"
+                )
+            }
+            Self::RegionUnavailable => write!(f, "该账户所在的国家或地区无法使用 Xbox Live"),
+            Self::AdultVerificationRequired => {
+                write!(f, "该账户需要先在 Xbox 官网完成成人验证")
+            }
+            Self::AgeVerificationRequired => {
+                write!(f, "该账户需要先在 Xbox 官网完成年龄验证")
+            }
+            Self::ChildAccount => write!(
+                f,
+                "该账户为儿童账户，需要由成人将其添加到 Microsoft 家庭组中才能登录"
+            ),
+            Self::Unknown(code) => write!(f, "未知的 XSTS 错误：{code}"),
+        }
+    }
+}
+
+/// 微软账户和 Yggdrasil 账户验证过程中可能出现的错误
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AuthError {
+    /// 网络请求失败，可以稍后重试
+    Network(String),
+    /// 刷新令牌已过期或者无效，需要重新登录
+    InvalidRefreshToken(String),
+    /// 验证 Xbox Live 账户失败
+    XboxLive(String),
+    /// XSTS 授权失败
+    Xsts {
+        /// 错误类型
+        error: XstsError,
+        /// 服务器返回的解决链接，可能为空
+        redirect: String,
+    },
+    /// 使用 Xbox 令牌登录 Minecraft 失败
+    MinecraftLogin(String),
+    /// 该账户没有购买 Minecraft
+    NotOwned,
+    /// 该账户拥有 Minecraft，但还没有创建游戏档案（即还没有设置玩家名称）
+    NoProfile,
+}
+
+impl AuthError {
+    pub(crate) fn network(err: impl Display) -> Self {
+        Self::Network(err.to_string())
+    }
+
+    /// 获取能够帮助用户解决该问题的链接，如果没有则返回 `None`
+    pub fn fix_link(&self) -> Option<&str> {
+        match self {
+            Self::Xsts { redirect, .. } if !redirect.is_empty() => Some(redirect),
+            Self::Xsts { error, .. } => match error {
+                XstsError::Banned => Some("https://enforcement.xbox.com/"),
+                XstsError::GuardianConsentRequired | XstsError::ChildAccount => {
+                    Some("https://account.microsoft.com/family/")
+                }
+                XstsError::NoXboxAccount => Some("https://signup.live.com/signup?lic=1"),
+                XstsError::TermsNotAccepted
+                | XstsError::AdultVerificationRequired
+                | XstsError::AgeVerificationRequired => {
+                    Some("https://www.xbox.com/")
+                }
+                XstsError::RegionUnavailable | XstsError::Unknown(_) => None,
+            },
+            Self::NotOwned => {
+                Some("https://www.xbox.com/games/store/minecraft-java-bedrock-edition-for-pc/9nxp44l49shj")
+            }
+            Self::NoProfile => Some("https://www.minecraft.net/msaprofile/mygames/editprofile"),
+            _ => None,
+        }
+    }
+}
+
+impl Display for AuthError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Network(err) => write!(f, "网络请求失败，请检查网络连接后重试：{err}"),
+            Self::InvalidRefreshToken(err) => {
+                write!(f, "登录状态已过期，请重新登录：{err}")
+            }
+            Self::XboxLive(err) => write!(f, "验证 Xbox Live 账户失败：{err}"),
+            Self::Xsts { error, .. } => write!(f, "获取 XSTS 授权失败：{error}"),
+            Self::MinecraftLogin(err) => write!(f, "登录 Minecraft 失败：{err}"),
+            Self::NotOwned => write!(
+                f,
+                "没有在已购项目中找到 Minecraft！请检查你的账户是否已购买 Minecraft！"
+            ),
+            Self::NoProfile => write!(f, "该账户还没有创建 Minecraft 游戏档案，请先设置玩家名称"),
+        }
+    }
+}
+
+impl std::error::Error for AuthError {}
+
+#[test]
+fn xsts_error_test() {
+    for code in [
+        2148916227, 2148916229, 2148916233, 2148916234, 2148916235, 2148916236, 2148916237,
+        2148916238, 1234,
+    ] {
+        assert_eq!(XstsError::from_code(code).code(), code);
+    }
+    assert_eq!(
+        XstsError::from_code(2148916237),
+        XstsError::AgeVerificationRequired
+    );
+    assert_eq!(XstsError::from_code(1234), XstsError::Unknown(1234));
+
+    let err = AuthError::Xsts {
+        error: XstsError::from_code(2148916233),
+        redirect: String::new(),
+    };
+    assert_eq!(
+        err.to_string(),
+        "获取 XSTS 授权失败：该微软账户还没有 Xbox 账户，请先登录 Xbox 官网创建一个"
+    );
+    assert_eq!(err.fix_link(), Some("https://signup.live.com/signup?lic=1"));
+    let err = AuthError::Xsts {
+        error: XstsError::Banned,
+        redirect: "https://example.com/fix".into(),
+    };
+    assert_eq!(err.fix_link(), Some("https://example.com/fix"));
+    assert_eq!(
+        AuthError::Xsts {
+            error: XstsError::Unknown(1234),
+            redirect: String::new(),
+        }
+        .to_string(),
+        "获取 XSTS 授权失败：未知的 XSTS 错误：1234"
+    );
+    assert_eq!(AuthError::Network("timeout".into()).fix_link(), None);
+}
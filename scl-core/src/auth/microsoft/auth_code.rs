@@ -0,0 +1,263 @@
+//! 授权码流程（附带 PKCE）所需的组件
+//!
+//! 通过在 `127.0.0.1` 上临时监听一个随机端口作为重定向地址，
+//! 让用户在系统浏览器中完成登录，浏览器跳转回本地地址时即可拿到授权码，
+//! 不再需要借助内嵌浏览器捕获回调链接。
+//!
+//! 具体请查阅 [Microsoft 标识平台和 OAuth 2.0 授权代码流](https://learn.microsoft.com/zh-cn/entra/identity-platform/v2-oauth2-auth-code-flow)
+
+use base64::prelude::*;
+use inner_future::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+};
+use sha2::{Digest, Sha256};
+
+use crate::{password::Password, prelude::*};
+
+/// 一对 PKCE 验证码和质询码
+///
+/// 验证码会在兑换令牌时提交，质询码为验证码的 SHA-256 摘要，会在授权链接中提交
+#[derive(Debug, Clone)]
+pub struct PkceChallenge {
+    verifier: Password,
+    challenge: String,
+}
+
+impl PkceChallenge {
+    /// 随机生成一对新的验证码和质询码
+    pub fn new() -> Self {
+        let verifier = BASE64_URL_SAFE_NO_PAD.encode(rand::random::<[u8; 32]>());
+        let challenge = BASE64_URL_SAFE_NO_PAD.encode(Sha256::digest(verifier.as_bytes()));
+        Self {
+            verifier: verifier.into(),
+            challenge,
+        }
+    }
+
+    /// 获取验证码，兑换令牌时使用
+    pub fn verifier(&self) -> &str {
+        self.verifier.as_str()
+    }
+
+    /// 获取质询码，构建授权链接时使用，质询方式固定为 `S256`
+    pub fn challenge(&self) -> &str {
+        &self.challenge
+    }
+}
+
+impl Default for PkceChallenge {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+const SUCCESS_PAGE: &str =
+    "<!DOCTYPE html><html><head><meta charset=\"utf-8\"><title>SCL</title></head>\
+<body><h2>登录完成</h2><p>现在可以关闭此页面并返回启动器了。</p></body></html>";
+
+const FAILED_PAGE: &str =
+    "<!DOCTYPE html><html><head><meta charset=\"utf-8\"><title>SCL</title></head>\
+<body><h2>登录失败</h2><p>请返回启动器查看错误信息。</p></body></html>";
+
+/// 一个只监听 `127.0.0.1` 的临时 HTTP 服务器，用于接收授权完成后的重定向
+pub struct LoopbackListener {
+    listener: TcpListener,
+    port: u16,
+}
+
+impl LoopbackListener {
+    /// 在 `127.0.0.1` 上绑定一个由系统分配的空闲端口
+    pub async fn bind() -> DynResult<Self> {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).await?;
+        let port = listener.local_addr()?.port();
+        Ok(Self { listener, port })
+    }
+
+    /// 获取监听的端口
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+
+    /// 获取需要填写在授权链接中的重定向地址
+    pub fn redirect_uri(&self) -> String {
+        format!("http://127.0.0.1:{}", self.port)
+    }
+
+    /// 等待浏览器跳转回本地地址，并返回其中的授权码
+    ///
+    /// 无法解析的请求、不带有 `code` 或 `error` 参数的请求（例如浏览器自动请求的 `/favicon.ico`）
+    /// 以及 `state` 参数与传入的不一致的请求都会以 `400 Bad Request` 响应并被忽略，然后继续等待。
+    /// 只有 `state` 参数一致时，授权服务器返回的错误才会作为错误返回。
+    ///
+    /// 此函数会一直等待，如有需要请自行配合超时使用。
+    pub async fn wait_for_code(&self, expected_state: &str) -> DynResult<String> {
+        loop {
+            let (mut stream, _) = self.listener.accept().await?;
+            let url = match read_request_target(&mut stream)
+                .await
+                .and_then(|target| Ok(url::Url::parse(&format!("http://127.0.0.1{target}"))?))
+            {
+                Ok(url) => url,
+                Err(err) => {
+                    tracing::debug!("读取回调请求失败 {:?}", err);
+                    let _ = write_response(&mut stream, "400 Bad Request", "").await;
+                    continue;
+                }
+            };
+            let mut code = None;
+            let mut state = None;
+            let mut error = None;
+            let mut error_description = None;
+            for (key, value) in url.query_pairs() {
+                match key.as_ref() {
+                    "code" => code = Some(value.into_owned()),
+                    "state" => state = Some(value.into_owned()),
+                    "error" => error = Some(value.into_owned()),
+                    "error_description" => error_description = Some(value.into_owned()),
+                    _ => {}
+                }
+            }
+            if code.is_none() && error.is_none() {
+                let _ = write_response(&mut stream, "400 Bad Request", "").await;
+                continue;
+            }
+            if state.as_deref() != Some(expected_state) {
+                tracing::warn!("忽略 state 参数不匹配的回调请求，登录请求可能被篡改");
+                let _ = write_response(&mut stream, "400 Bad Request", FAILED_PAGE).await;
+                continue;
+            }
+            if let Some(error) = error {
+                let _ = write_response(&mut stream, "200 OK", FAILED_PAGE).await;
+                anyhow::bail!(
+                    "授权服务器返回了错误：{} {}",
+                    error,
+                    error_description.unwrap_or_default()
+                );
+            }
+            let _ = write_response(&mut stream, "200 OK", SUCCESS_PAGE).await;
+            return Ok(code.unwrap_or_default());
+        }
+    }
+}
+
+/// 读取一个 HTTP 请求头，并返回请求行中的目标路径
+async fn read_request_target(stream: &mut TcpStream) -> DynResult<String> {
+    let mut buf = Vec::with_capacity(1024);
+    let mut chunk = [0u8; 1024];
+    while !buf.windows(4).any(|x| x == b"\r\n\r\n") {
+        let len = stream.read(&mut chunk).await?;
+        if len == 0 {
+            break;
+        }
+        buf.extend_from_slice(&chunk[..len]);
+        anyhow::ensure!(buf.len() <= 16 * 1024, "请求头过长");
+    }
+    let head = String::from_utf8_lossy(&buf);
+    let mut request_line = head.lines().next().unwrap_or_default().split(' ');
+    let method = request_line.next().unwrap_or_default();
+    let target = request_line.next().unwrap_or_default();
+    anyhow::ensure!(method == "GET", "不支持的请求方法 {}", method);
+    anyhow::ensure!(target.starts_with('/'), "无效的请求路径 {}", target);
+    Ok(target.to_owned())
+}
+
+async fn write_response(stream: &mut TcpStream, status: &str, body: &str) -> DynResult {
+    stream
+        .write_all(
+            format!(
+                "HTTP/1.1 {status}\r\nContent-Type: text/html; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                body.len()
+            )
+            .as_bytes(),
+        )
+        .await?;
+    stream.flush().await?;
+    Ok(())
+}
+
+/// 一个进行中的授权码登录流程，由 [`super::MicrosoftOAuth::begin_auth_code_flow`] 创建
+///
+/// 将 [`AuthCodeFlow::authorize_url`] 在系统浏览器中打开后，
+/// 交给 [`super::MicrosoftOAuth::finish_auth_code_flow`] 等待用户完成登录即可
+pub struct AuthCodeFlow {
+    pub(super) listener: LoopbackListener,
+    pub(super) pkce: PkceChallenge,
+    pub(super) state: String,
+    pub(super) authorize_url: String,
+}
+
+impl AuthCodeFlow {
+    /// 需要在系统浏览器中打开的授权链接
+    pub fn authorize_url(&self) -> &str {
+        &self.authorize_url
+    }
+
+    /// 本次流程使用的重定向地址
+    pub fn redirect_uri(&self) -> String {
+        self.listener.redirect_uri()
+    }
+}
+
+#[cfg(test)]
+async fn send_test_request(port: u16, target: &str) -> String {
+    let mut stream = TcpStream::connect(("127.0.0.1", port)).await.unwrap();
+    stream
+        .write_all(format!("GET {target} HTTP/1.1\r\nHost: 127.0.0.1:{port}\r\n\r\n").as_bytes())
+        .await
+        .unwrap();
+    let mut resp = String::new();
+    stream.read_to_string(&mut resp).await.unwrap();
+    resp
+}
+
+#[test]
+fn loopback_listener_test() {
+    inner_future::block_on(async {
+        let listener = LoopbackListener::bind().await.unwrap();
+        let port = listener.port();
+        let (code, (favicon, callback)) = futures::join!(listener.wait_for_code("abc"), async {
+            let favicon = send_test_request(port, "/favicon.ico").await;
+            let callback = send_test_request(port, "/?code=M.C123%2Btest&state=abc").await;
+            (favicon, callback)
+        });
+        assert_eq!(code.unwrap(), "M.C123+test");
+        assert!(favicon.starts_with("HTTP/1.1 400"));
+        assert!(callback.starts_with("HTTP/1.1 200"));
+
+        // state 不匹配的请求和无法解析的请求不会中断登录
+        let (code, (evil, malformed, _)) = futures::join!(listener.wait_for_code("abc"), async {
+            let evil = send_test_request(port, "/?code=123&state=evil").await;
+            let evil_error = send_test_request(port, "/?error=access_denied&state=evil").await;
+            let mut stream = TcpStream::connect(("127.0.0.1", port)).await.unwrap();
+            stream
+                .write_all(b"OPTIONS * HTTP/1.1\r\n\r\n")
+                .await
+                .unwrap();
+            let mut malformed = String::new();
+            stream.read_to_string(&mut malformed).await.unwrap();
+            assert!(evil_error.starts_with("HTTP/1.1 400"));
+            (
+                evil,
+                malformed,
+                send_test_request(port, "/?code=456&state=abc").await,
+            )
+        });
+        assert_eq!(code.unwrap(), "456");
+        assert!(evil.starts_with("HTTP/1.1 400"));
+        assert!(malformed.starts_with("HTTP/1.1 400"));
+
+        let (code, _) = futures::join!(
+            listener.wait_for_code("abc"),
+            send_test_request(port, "/?error=access_denied&state=abc")
+        );
+        assert!(code.is_err());
+    });
+
+    let pkce = PkceChallenge::new();
+    assert_eq!(pkce.verifier().len(), 43);
+    assert_eq!(
+        pkce.challenge(),
+        BASE64_URL_SAFE_NO_PAD.encode(Sha256::digest(pkce.verifier().as_bytes()))
+    );
+}
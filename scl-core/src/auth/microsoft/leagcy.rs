@@ -1,9 +1,14 @@
 //! 传统微软登录模块，通过模仿 Minecraft 官方启动器来接收授权令牌回调链接完成登录验证
 
+use anyhow::Context;
 use serde::Deserialize;
 
 use crate::{
-    auth::{parse_head_skin, structs::AuthMethod},
+    auth::{
+        error::{AuthError, XstsError},
+        parse_head_skin,
+        structs::{AuthMethod, MinecraftOwnership},
+    },
     password::Password,
     prelude::*,
 };
@@ -27,7 +32,7 @@ pub const MICROSOFT_TOKEN_URL: &str = "https://login.live.com/oauth20_token.srf"
 #[serde(default)]
 struct OAuth20TokenResponse {
     // token_type: String,
-    // expires_in: usize,
+    pub expires_in: u64,
     // scope: String,
     pub error: String,
     pub access_token: Password,
@@ -44,6 +49,15 @@ pub(super) struct XBoxAuthResponse {
     pub display_claims: XBoxAuthResponse1,
 }
 
+#[derive(Debug, Clone, Deserialize, Default)]
+#[serde(default)]
+pub(super) struct XstsErrorResponse {
+    #[serde(rename = "XErr")]
+    pub x_err: u64,
+    #[serde(rename = "Redirect")]
+    pub redirect: String,
+}
+
 #[derive(Debug, Clone, Deserialize)]
 pub(super) struct XBoxAuthResponse1 {
     pub xui: Vec<XBoxAuthResponse2>,
@@ -54,9 +68,16 @@ pub(super) struct XBoxAuthResponse2 {
     pub uhs: String,
 }
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Deserialize, Default)]
+#[serde(default)]
 pub(super) struct MinecraftStoreResponse {
-    pub items: Vec<serde_json::Value>,
+    pub items: Vec<MinecraftStoreItem>,
+}
+
+#[derive(Debug, Clone, Deserialize, Default)]
+#[serde(default)]
+pub(super) struct MinecraftStoreItem {
+    pub name: String,
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -64,6 +85,8 @@ pub(super) struct MinecraftXBoxLoginResponse {
     // pub username: String,
     pub access_token: Password,
     // pub token_type: String,
+    #[serde(default)]
+    pub expires_in: u64,
 }
 
 #[derive(Debug, Clone, Deserialize, Default)]
@@ -107,6 +130,11 @@ pub async fn get_xuid(userhash: &str, token: &str) -> DynResult<String> {
 ///
 /// 如果续期一个令牌，则 credit 为需要续期的旧令牌
 pub async fn request_token(credit: &str, is_refresh: bool) -> DynResult<(Password, String)> {
+    let res = request_oauth20_token(credit, is_refresh).await?;
+    Ok((res.access_token, res.refresh_token))
+}
+
+async fn request_oauth20_token(credit: &str, is_refresh: bool) -> DynResult<OAuth20TokenResponse> {
     let body = format!(
         "client_id=00000000402b5328&{}={}&grant_type={}&redirect_uri=https%3A%2F%2Flogin.live.com%2Foauth20_desktop.srf&scope=service%3A%3Auser.auth.xboxlive.com%3A%3AMBI_SSL",
         if is_refresh { "refresh_token" } else { "code" }, // Grant Tag
@@ -118,14 +146,69 @@ pub async fn request_token(credit: &str, is_refresh: bool) -> DynResult<(Passwor
         .body(body.as_bytes())
         .recv_json()
         .await
-        .map_err(|e| anyhow::anyhow!(e))?;
+        .map_err(AuthError::network)?;
+    if is_refresh && res.error == "invalid_grant" {
+        return Err(AuthError::InvalidRefreshToken(res.error).into());
+    }
     anyhow::ensure!(
         res.error.is_empty(),
         "{}令牌失败: {}",
         if is_refresh { "刷新" } else { "请求" },
         res.error
     );
-    Ok((res.access_token, res.refresh_token))
+    Ok(res)
+}
+
+/// 使用 `RpsTicket` 验证 Xbox Live 账户
+pub(super) async fn authenticate_xbox_live(rps_ticket: &str) -> DynResult<XBoxAuthResponse> {
+    let xbox_auth_body = format!("{{\"Properties\":{{\"AuthMethod\":\"RPS\",\"SiteName\":\"user.auth.xboxlive.com\",\"RpsTicket\":\"{rps_ticket}\"}},\"RelyingParty\":\"http://auth.xboxlive.com\",\"TokenType\":\"JWT\"}}");
+    let mut res = crate::http::post("https://user.auth.xboxlive.com/user/authenticate")
+        .header("Content-Type", "application/json")
+        .header("Accept", "application/json")
+        .body(xbox_auth_body.as_bytes())
+        .await
+        .map_err(AuthError::network)?;
+    if !res.status().is_success() {
+        return Err(AuthError::XboxLive(format!("服务器返回了状态码 {}", res.status())).into());
+    }
+    let res = res
+        .body_json()
+        .await
+        .map_err(|e| AuthError::XboxLive(e.to_string()))?;
+    Ok(res)
+}
+
+/// 使用 Xbox Live 的用户令牌获取 Minecraft 服务使用的 XSTS 令牌
+///
+/// 授权失败时会根据 `XErr` 错误码返回 [`AuthError::Xsts`]
+pub(super) async fn authorize_xsts(user_token: &str) -> DynResult<XBoxAuthResponse> {
+    let xsts_body = format!("{{\"Properties\":{{\"SandboxId\":\"RETAIL\",\"UserTokens\":[\"{user_token}\"]}},\"RelyingParty\":\"rp://api.minecraftservices.com/\",\"TokenType\":\"JWT\"}}");
+    let mut res = crate::http::post("https://xsts.auth.xboxlive.com/xsts/authorize")
+        .header("Content-Type", "application/json")
+        .header("Accept", "application/json")
+        .body(xsts_body.as_bytes())
+        .await
+        .map_err(AuthError::network)?;
+    if !res.status().is_success() {
+        let err: XstsErrorResponse = res.body_json().await.unwrap_or_default();
+        if err.x_err != 0 {
+            return Err(AuthError::Xsts {
+                error: XstsError::from_code(err.x_err),
+                redirect: err.redirect,
+            }
+            .into());
+        }
+        return Err(AuthError::XboxLive(format!(
+            "获取 XSTS 授权时服务器返回了状态码 {}",
+            res.status()
+        ))
+        .into());
+    }
+    let res = res
+        .body_json()
+        .await
+        .map_err(|e| AuthError::XboxLive(e.to_string()))?;
+    Ok(res)
 }
 
 /// 根据微软登录传回的访问令牌 access_token 返回 user_hash 和 xsts_token
@@ -133,63 +216,56 @@ pub async fn request_token(credit: &str, is_refresh: bool) -> DynResult<(Passwor
 /// 传递给 [`get_mojang_access_token`] 进行下一步验证
 pub async fn get_userhash_and_token(access_token: &str) -> DynResult<(String, String)> {
     // tracing::trace!("Getting xbox auth body");
-    let xbox_auth_body = format!("{{\"Properties\":{{\"AuthMethod\":\"RPS\",\"SiteName\":\"user.auth.xboxlive.com\",\"RpsTicket\":\"{access_token}\"}},\"RelyingParty\":\"http://auth.xboxlive.com\",\"TokenType\":\"JWT\"}}");
-    let xbox_auth_resp: XBoxAuthResponse =
-        crate::http::post("https://user.auth.xboxlive.com/user/authenticate")
-            .header("Content-Type", "application/json")
-            .header("Accept", "application/json")
-            .body(xbox_auth_body.as_bytes())
-            .await
-            .map_err(|e| anyhow::anyhow!(e))?
-            .body_json()
-            .await
-            .map_err(|e| anyhow::anyhow!(e))?;
-    let token = xbox_auth_resp.token.to_owned();
-    if let Some(uhs) = xbox_auth_resp.display_claims.xui.first() {
-        let uhs = uhs.uhs.to_owned();
-        let xsts_body = format!("{{\"Properties\":{{\"SandboxId\":\"RETAIL\",\"UserTokens\":[\"{token}\"]}},\"RelyingParty\":\"rp://api.minecraftservices.com/\",\"TokenType\":\"JWT\"}}");
-        tracing::trace!("Getting xbox xsts token");
-        let xsts_resp: XBoxAuthResponse =
-            crate::http::post("https://xsts.auth.xboxlive.com/xsts/authorize")
-                .header("Content-Type", "application/json")
-                .header("Accept", "application/json")
-                .body(xsts_body.as_bytes())
-                .await
-                .map_err(|e| anyhow::anyhow!(e))?
-                .body_json()
-                .await
-                .map_err(|e| anyhow::anyhow!(e))?;
-        let xsts_token = xsts_resp.token;
-        Ok((uhs, xsts_token))
-    } else {
-        anyhow::bail!("获取 UserHash 失败")
-    }
+    let xbox_auth_resp = authenticate_xbox_live(access_token).await?;
+    let uhs = xbox_auth_resp
+        .display_claims
+        .xui
+        .first()
+        .map(|x| x.uhs.to_owned())
+        .ok_or_else(|| AuthError::XboxLive("获取 UserHash 失败".into()))?;
+    tracing::trace!("Getting xbox xsts token");
+    let xsts_resp = authorize_xsts(&xbox_auth_resp.token).await?;
+    Ok((uhs, xsts_resp.token))
 }
 
 /// 通过 [`get_userhash_and_token`] 返回的 `userhash` 和 `xsts_token` 获取 Mojang 的访问令牌
 ///
 /// 在拥有 Minecraft 游戏的情况下，此令牌可用于正版启动游戏
 pub async fn get_mojang_access_token(uhs: &str, xsts_token: &str) -> DynResult<Password> {
+    Ok(login_with_xbox(uhs, xsts_token).await?.0)
+}
+
+/// 同 [`get_mojang_access_token`]，但会一并返回令牌的过期时间戳
+pub(super) async fn login_with_xbox(uhs: &str, xsts_token: &str) -> DynResult<(Password, u64)> {
     if !uhs.is_empty() && !xsts_token.is_empty() {
         // tracing::trace!("Getting mojang access token");
         let minecraft_xbox_body = format!("{{\"identityToken\":\"XBL3.0 x={uhs};{xsts_token}\"}}");
-        let minecraft_xbox_resp =
+        let mut res =
             crate::http::post("https://api.minecraftservices.com/authentication/login_with_xbox")
                 .header("Content-Type", "application/json")
                 .header("Accept", "application/json")
                 .body(minecraft_xbox_body.as_bytes())
                 .await
-                .map_err(|e| anyhow::anyhow!(e))?
-                .body_string()
-                .await
-                .map_err(|e| anyhow::anyhow!(e))?;
+                .map_err(AuthError::network)?;
+        let minecraft_xbox_resp = res.body_string().await.map_err(AuthError::network)?;
+        if !res.status().is_success() {
+            return Err(AuthError::MinecraftLogin(format!(
+                "服务器返回了状态码 {}：{}",
+                res.status(),
+                minecraft_xbox_resp
+            ))
+            .into());
+        }
         let minecraft_xbox_resp: MinecraftXBoxLoginResponse =
-            serde_json::from_str(&minecraft_xbox_resp)?;
+            serde_json::from_str(&minecraft_xbox_resp)
+                .map_err(|e| AuthError::MinecraftLogin(e.to_string()))?;
         // tracing::trace!("Getting minecraft access token");
-        let access_token = minecraft_xbox_resp.access_token;
-        Ok(access_token)
+        Ok((
+            minecraft_xbox_resp.access_token,
+            crate::auth::expires_at(minecraft_xbox_resp.expires_in),
+        ))
     } else {
-        Ok(Password::default())
+        Ok((Password::default(), 0))
     }
 }
 
@@ -199,19 +275,22 @@ pub async fn refresh_auth(method: &mut AuthMethod) -> DynResult {
         AuthMethod::Microsoft {
             access_token,
             refresh_token,
+            microsoft_expires_at,
+            minecraft_expires_at,
             ..
         } => {
-            let (new_access_token, new_refresh_token) =
-                request_token(refresh_token.as_str(), true).await?;
-            let (uhs, xsts_token) = get_userhash_and_token(&new_access_token).await?;
-            let new_access_token = get_mojang_access_token(&uhs, &xsts_token).await?;
+            let new_token = request_oauth20_token(refresh_token.as_str(), true).await?;
+            let (uhs, xsts_token) = get_userhash_and_token(&new_token.access_token).await?;
+            let (new_access_token, new_expires_at) = login_with_xbox(&uhs, &xsts_token).await?;
             anyhow::ensure!(
                 !new_access_token.is_empty(),
                 "刷新令牌失败: {}",
                 new_access_token
             );
             *access_token = new_access_token;
-            *refresh_token = new_refresh_token.into();
+            *minecraft_expires_at = new_expires_at;
+            *refresh_token = new_token.refresh_token.into();
+            *microsoft_expires_at = crate::auth::expires_at(new_token.expires_in);
         }
         _ => {
             anyhow::bail!("不支持的方法");
@@ -220,70 +299,192 @@ pub async fn refresh_auth(method: &mut AuthMethod) -> DynResult {
     Ok(())
 }
 
+/// 根据权益列表判断账户持有 Minecraft 的方式，如果没有找到 Java 版的相关权益则返回 `None`
+pub(super) fn parse_ownership(items: &[MinecraftStoreItem]) -> Option<MinecraftOwnership> {
+    if items
+        .iter()
+        .any(|x| x.name == "product_minecraft" || x.name == "game_minecraft")
+    {
+        Some(MinecraftOwnership::Purchased)
+    } else if items
+        .iter()
+        .any(|x| x.name.starts_with("product_game_pass_"))
+    {
+        Some(MinecraftOwnership::GamePass)
+    } else {
+        None
+    }
+}
+
+/// 查询账户持有 Minecraft 的方式
+pub(super) async fn get_ownership(access_token: &str) -> DynResult<Option<MinecraftOwnership>> {
+    tracing::debug!("正在检查是否拥有 Minecraft");
+    let mut res = crate::http::get("https://api.minecraftservices.com/entitlements/mcstore")
+        .header("Authorization", format!("Bearer {access_token}"))
+        .await
+        .map_err(AuthError::network)?;
+    if !res.status().is_success() {
+        return Err(AuthError::MinecraftLogin(format!(
+            "查询已购项目时服务器返回了状态码 {}",
+            res.status()
+        ))
+        .into());
+    }
+    let mcstore_resp: MinecraftStoreResponse = res.body_json().await.map_err(AuthError::network)?;
+    Ok(parse_ownership(&mcstore_resp.items))
+}
+
+/// 获取账户的游戏档案，如果账户还没有创建游戏档案则返回 `None`
+pub(super) async fn get_profile(
+    access_token: &str,
+) -> DynResult<Option<MinecraftXBoxProfileResponse>> {
+    tracing::debug!("正在获取 Minecraft 账户信息");
+    let mut res = crate::http::get("https://api.minecraftservices.com/minecraft/profile")
+        .header("Authorization", format!("Bearer {access_token}"))
+        .await
+        .map_err(AuthError::network)?;
+    if res.status() == surf::StatusCode::NotFound {
+        return Ok(None);
+    }
+    if !res.status().is_success() {
+        return Err(AuthError::MinecraftLogin(format!(
+            "获取账户信息时服务器返回了状态码 {}",
+            res.status()
+        ))
+        .into());
+    }
+    let profile_resp: MinecraftXBoxProfileResponse =
+        res.body_json().await.map_err(AuthError::network)?;
+    if profile_resp.error.is_empty() && !profile_resp.id.is_empty() {
+        Ok(Some(profile_resp))
+    } else {
+        Ok(None)
+    }
+}
+
+/// 下载游戏档案中正在使用的皮肤，并解析出头像位图
+pub(super) async fn get_profile_head_skin(
+    profile: &MinecraftXBoxProfileResponse,
+) -> DynResult<(Vec<u8>, Vec<u8>)> {
+    if let Some(skin) = profile.skins.iter().find(|a| a.state == "ACTIVE") {
+        tracing::debug!("正在解析皮肤: {}", skin.url);
+        let skin_data = crate::http::get(&skin.url)
+            .await
+            .map_err(|e| anyhow::anyhow!(e))?
+            .body_bytes()
+            .await
+            .map_err(|e| anyhow::anyhow!(e))?;
+        parse_head_skin(skin_data).context("解析皮肤数据失败")
+    } else {
+        anyhow::bail!("皮肤获取失败！");
+    }
+}
+
+/// 使用获取到的 Minecraft 访问令牌检查游戏持有情况和游戏档案，并组合出微软账户
+///
+/// 如果账户拥有 Minecraft 但还没有创建游戏档案，依然会返回账户，
+/// 但其 `has_profile` 字段为 `false`
+///
+/// `client_id` 为登录时使用的客户端 ID，通过本模块的方式登录时传入空字符串
+pub(super) async fn build_microsoft_account(
+    (access_token, minecraft_expires_at): (Password, u64),
+    (refresh_token, microsoft_expires_at): (Password, u64),
+    xuid: String,
+    client_id: String,
+) -> DynResult<AuthMethod> {
+    let ownership = get_ownership(access_token.as_str()).await?;
+    let profile = get_profile(access_token.as_str()).await?;
+    match (ownership, profile) {
+        (None, None) => Err(AuthError::NotOwned.into()),
+        (ownership, None) => {
+            tracing::debug!("该账户尚未创建游戏档案");
+            Ok(AuthMethod::Microsoft {
+                access_token,
+                refresh_token,
+                uuid: String::new(),
+                xuid,
+                player_name: String::new(),
+                head_skin: vec![],
+                hat_skin: vec![],
+                ownership: ownership.unwrap_or_default(),
+                has_profile: false,
+                client_id,
+                microsoft_expires_at,
+                minecraft_expires_at,
+            })
+        }
+        (ownership, Some(profile)) => {
+            let (head_skin, hat_skin) =
+                get_profile_head_skin(&profile).await.unwrap_or_else(|err| {
+                    tracing::warn!("获取玩家皮肤头像失败：{err}");
+                    Default::default()
+                });
+            tracing::debug!("微软账户验证成功！");
+            Ok(AuthMethod::Microsoft {
+                access_token,
+                refresh_token,
+                uuid: profile.id,
+                xuid,
+                player_name: profile.name,
+                head_skin,
+                hat_skin,
+                ownership: ownership.unwrap_or_default(),
+                has_profile: true,
+                client_id,
+                microsoft_expires_at,
+                minecraft_expires_at,
+            })
+        }
+    }
+}
+
 /// 执行微软登录，需要形如 `https://login.live.com/oauth20_desktop.srf?code=[ANYCODE]&lc=1033` 的链接作为参数
 pub async fn start_auth(_ctx: Option<impl Reporter>, url: &str) -> DynResult<AuthMethod> {
     let url = url.parse::<url::Url>()?;
     if let Some((_, code)) = url.query_pairs().find(|a| a.0 == "code") {
-        let (access_token, refresh_token) = request_token(&code, false).await?;
-        let (uhs, xsts_token) = get_userhash_and_token(&access_token).await?;
+        let token = request_oauth20_token(&code, false).await?;
+        let (uhs, xsts_token) = get_userhash_and_token(&token.access_token).await?;
         let xuid = get_xuid(&uhs, &xsts_token).await?;
-        let access_token = get_mojang_access_token(&uhs, &xsts_token).await?;
+        let (access_token, minecraft_expires_at) = login_with_xbox(&uhs, &xsts_token).await?;
         if access_token.is_empty() {
             return Err(anyhow::anyhow!("获取令牌失败"));
         } else {
-            let mcstore_resp: MinecraftStoreResponse =
-                crate::http::get("https://api.minecraftservices.com/entitlements/mcstore")
-                    .header(
-                        "Authorization",
-                        &format!("Bearer {}", access_token.as_string()),
-                    )
-                    .await
-                    .map_err(|e| anyhow::anyhow!(e))?
-                    .body_json()
-                    .await
-                    .map_err(|e| anyhow::anyhow!(e))?;
-            if mcstore_resp.items.is_empty() {
-                anyhow::bail!(
-                    "没有在已购项目中找到 Minecraft！请检查你的账户是否已购买 Minecraft！"
-                );
-            }
-            let profile_resp: MinecraftXBoxProfileResponse =
-                crate::http::get("https://api.minecraftservices.com/minecraft/profile")
-                    .header(
-                        "Authorization",
-                        &format!("Bearer {}", access_token.as_string()),
-                    )
-                    .await
-                    .map_err(|e| anyhow::anyhow!(e))?
-                    .body_json()
-                    .await
-                    .map_err(|e| anyhow::anyhow!(e))?;
-            if profile_resp.error.is_empty() {
-                if let Some(skin) = profile_resp.skins.iter().find(|a| a.state == "ACTIVE") {
-                    let skin_data = crate::http::get(&skin.url)
-                        .await
-                        .map_err(|e| anyhow::anyhow!(e))?
-                        .body_bytes()
-                        .await
-                        .map_err(|e| anyhow::anyhow!(e))?;
-                    let (head_skin, hat_skin) = parse_head_skin(skin_data)?;
-                    tracing::trace!("Successfully authed!");
-                    return Ok(AuthMethod::Microsoft {
-                        access_token,
-                        refresh_token: refresh_token.into(),
-                        xuid,
-                        head_skin,
-                        hat_skin,
-                        player_name: profile_resp.name,
-                        uuid: profile_resp.id,
-                    });
-                }
-            } else {
-                anyhow::bail!(
-                    "没有在账户中找到 Minecraft 账户信息！请检查你的账户是否已购买 Minecraft！"
-                );
-            }
+            return build_microsoft_account(
+                (access_token, minecraft_expires_at),
+                (
+                    token.refresh_token.into(),
+                    crate::auth::expires_at(token.expires_in),
+                ),
+                xuid,
+                String::new(),
+            )
+            .await;
         }
     }
     anyhow::bail!("链接不合法");
 }
+
+#[test]
+fn parse_ownership_test() {
+    let items = |names: &[&str]| {
+        names
+            .iter()
+            .map(|name| MinecraftStoreItem {
+                name: name.to_string(),
+            })
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(
+        parse_ownership(&items(&["product_minecraft", "game_minecraft"])),
+        Some(MinecraftOwnership::Purchased)
+    );
+    assert_eq!(
+        parse_ownership(&items(&["product_game_pass_pc", "game_minecraft_bedrock"])),
+        Some(MinecraftOwnership::GamePass)
+    );
+    assert_eq!(
+        parse_ownership(&items(&["product_minecraft_bedrock"])),
+        None
+    );
+    assert_eq!(parse_ownership(&[]), None);
+}
@@ -1,20 +1,24 @@
-//! 微软登录模块，通过设备码方式获取玩家的 Microsoft 账户令牌，进而获取 Minecraft 用户令牌
+//! 微软登录模块，通过设备码方式或授权码方式获取玩家的 Microsoft 账户令牌，进而获取 Minecraft 用户令牌
 
 use std::fmt::Display;
 
-use anyhow::Context;
 use serde::Deserialize;
 
-use super::structs::AuthMethod;
+use super::{error::AuthError, structs::AuthMethod};
 use crate::{password::Password, prelude::*};
+pub mod auth_code;
 pub mod leagcy;
+use auth_code::*;
 use leagcy::*;
 
-/// 使用设备流方式验证的微软账户验证对象
+/// 使用设备流方式或授权码方式验证的微软账户验证对象
 ///
 /// 使用这个对象前，你需要通过 Azure Active Directory
 /// 注册一个应用，并将其客户端 ID 提供至此使用。
 ///
+/// 如需使用授权码方式，还需要在应用中添加 `http://127.0.0.1` 作为“移动和桌面应用程序”平台的重定向 URI，
+/// 登录时会监听一个随机端口，Azure 在匹配回环地址时会忽略端口号。
+///
 /// 具体请查阅 <https://wiki.vg/Microsoft_Authentication_Scheme>
 pub struct MicrosoftOAuth<T> {
     client_id: T,
@@ -38,7 +42,7 @@ impl<T: Display> MicrosoftOAuth<T> {
         .content_type("application/x-www-form-urlencoded")
         .recv_json::<DeviceCodeResponse>()
         .await
-        .map_err(|err| anyhow::anyhow!("请求设备码时发生错误：{}", err))?;
+        .map_err(AuthError::network)?;
 
         Ok(res)
     }
@@ -54,13 +58,78 @@ impl<T: Display> MicrosoftOAuth<T> {
                 .content_type("application/x-www-form-urlencoded")
                 .recv_json::<TokenResponse>()
                 .await
-                .map_err(|err| anyhow::anyhow!("请求设备码时发生错误：{}", err))?;
+                .map_err(AuthError::network)?;
+
+        Ok(res)
+    }
+
+    /// 开始一个授权码登录流程
+    ///
+    /// 这会在本地 `127.0.0.1` 上监听一个随机端口作为重定向地址，
+    /// 请将返回的 [`AuthCodeFlow::authorize_url`] 在系统浏览器中打开，
+    /// 然后调用 [`MicrosoftOAuth::finish_auth_code_flow`] 等待用户完成登录。
+    pub async fn begin_auth_code_flow(&self) -> DynResult<AuthCodeFlow> {
+        let listener = LoopbackListener::bind().await?;
+        let pkce = PkceChallenge::new();
+        let state = format!("{:032x}", rand::random::<u128>());
+        let authorize_url = format!(
+            "https://login.microsoftonline.com/consumers/oauth2/v2.0/authorize?client_id={}&response_type=code&response_mode=query&scope=XboxLive.signin%20offline_access&prompt=select_account&redirect_uri={}&state={}&code_challenge={}&code_challenge_method=S256",
+            self.client_id,
+            urlencoding::encode(&listener.redirect_uri()),
+            state,
+            pkce.challenge(),
+        );
+        Ok(AuthCodeFlow {
+            listener,
+            pkce,
+            state,
+            authorize_url,
+        })
+    }
+
+    /// 等待用户在浏览器中完成授权，兑换令牌后通过 [`MicrosoftOAuth::start_auth_with_token`] 获取 Minecraft 账户
+    ///
+    /// 此函数会一直等待浏览器的回调，如有需要请自行配合超时使用。
+    pub async fn finish_auth_code_flow(&self, flow: AuthCodeFlow) -> DynResult<AuthMethod> {
+        tracing::debug!("正在等待浏览器回调");
+        let code = flow.listener.wait_for_code(&flow.state).await?;
+        tracing::debug!("正在兑换授权码");
+        let token = self
+            .exchange_auth_code(&code, &flow.redirect_uri(), flow.pkce.verifier())
+            .await?;
+        self.start_auth_with_token(&token).await
+    }
+
+    /// 使用授权码和 PKCE 验证码兑换访问令牌和刷新令牌
+    pub async fn exchange_auth_code(
+        &self,
+        code: &str,
+        redirect_uri: &str,
+        code_verifier: &str,
+    ) -> DynResult<TokenResponse> {
+        let res =
+            crate::http::post("https://login.microsoftonline.com/consumers/oauth2/v2.0/token")
+                .body_string(format!(
+                    "grant_type=authorization_code&client_id={}&scope=XboxLive.signin%20offline_access&code={}&redirect_uri={}&code_verifier={}",
+                    self.client_id,
+                    urlencoding::encode(code),
+                    urlencoding::encode(redirect_uri),
+                    urlencoding::encode(code_verifier),
+                ))
+                .content_type("application/x-www-form-urlencoded")
+                .recv_json::<TokenResponse>()
+                .await
+                .map_err(AuthError::network)?;
+
+        anyhow::ensure!(res.error.is_empty(), "兑换授权码时发生错误：{}", res.error);
 
         Ok(res)
     }
 
     /// 重新刷新令牌，获取新的访问令牌和刷新令牌
-    async fn refresh_token(&self, refresh_token: &str) -> DynResult<TokenResponse> {
+    ///
+    /// 如果刷新令牌已经失效，则返回 [`AuthError::InvalidRefreshToken`]，此时需要让用户重新登录
+    pub async fn refresh_token(&self, refresh_token: &str) -> DynResult<TokenResponse> {
         let res =
             crate::http::post("https://login.microsoftonline.com/consumers/oauth2/v2.0/token")
                 .body_string(format!(
@@ -70,74 +139,57 @@ impl<T: Display> MicrosoftOAuth<T> {
                 .content_type("application/x-www-form-urlencoded")
                 .recv_json::<TokenResponse>()
                 .await
-                .map_err(|err| anyhow::anyhow!("请求设备码时发生错误：{}", err))?;
+                .map_err(AuthError::network)?;
+
+        if res.error == "invalid_grant" {
+            return Err(AuthError::InvalidRefreshToken(res.error_description).into());
+        }
+        anyhow::ensure!(
+            res.error.is_empty(),
+            "刷新令牌时发生错误：{} {}",
+            res.error,
+            res.error_description
+        );
 
         Ok(res)
     }
 
     async fn auth_xbox_live(&self, access_token: &str) -> DynResult<(String, String)> {
         tracing::debug!("正在验证 Xbox Live 账户");
-        let xbox_auth_body = format!(
-            "{\
-            {\
-                \"Properties\":{\
-                    {\
-                        \"AuthMethod\":\"RPS\",\
-                        \"SiteName\":\"user.auth.xboxlive.com\",\
-                        \"RpsTicket\":\"d={access_token}\"\
-                    }\
-                },\
-                \"RelyingParty\":\"http://auth.xboxlive.com\",\
-                \"TokenType\":\"JWT\"\
-            }\
-        }"
-        );
-        let xbox_auth_resp: XBoxAuthResponse =
-            crate::http::post("https://user.auth.xboxlive.com/user/authenticate")
-                .header("Content-Type", "application/json")
-                .header("Accept", "application/json")
-                .body(xbox_auth_body.as_bytes())
-                .recv_json()
-                .await
-                .map_err(|e| anyhow::anyhow!("验证 Xbox Live 账户失败：{}", e))?;
-        let token = xbox_auth_resp.token.to_owned();
-        if let Some(uhs) = xbox_auth_resp.display_claims.xui.first() {
-            let uhs = uhs.uhs.to_owned();
-            let xsts_body = format!(
-                "{\
-                {\
-                    \"Properties\":{\
-                        {\
-                            \"SandboxId\":\"RETAIL\",\
-                            \"UserTokens\":[\"{token}\"]\
-                        }\
-                    },\
-                    \"RelyingParty\":\"rp://api.minecraftservices.com/\",\
-                    \"TokenType\":\"JWT\"\
-                }\
-            }"
-            );
-            tracing::debug!("正在获取 XSTS");
-            let xsts_resp: XBoxAuthResponse =
-                crate::http::post("https://xsts.auth.xboxlive.com/xsts/authorize")
-                    .header("Content-Type", "application/json")
-                    .header("Accept", "application/json")
-                    .body(xsts_body.as_bytes())
-                    .recv_json()
-                    .await
-                    .map_err(|e| anyhow::anyhow!("获取 XSTS 账户失败：{}", e))?;
-            let xsts_token = xsts_resp.token;
-            Ok((uhs, xsts_token))
-        } else {
-            anyhow::bail!("获取 UserHash 失败")
-        }
+        get_userhash_and_token(&format!("d={access_token}")).await
     }
 
-    /// 通过设备码验证获取到的 Microsoft 访问令牌获取 Minecraft 账户
+    /// 通过设备码验证或授权码兑换获取到的 Microsoft 访问令牌获取 Minecraft 账户
+    ///
+    /// 如果账户拥有 Minecraft 但还没有创建游戏档案，返回账户的 `has_profile` 字段将为 `false`，
+    /// 此时需要通过 [`create_profile`] 设置玩家名称后才能启动游戏
+    ///
+    /// 此方法无法得知微软访问令牌的过期时间，如果可以的话请使用 [`MicrosoftOAuth::start_auth_with_token`]
     pub async fn start_auth(
         &self,
         access_token: &str,
         refresh_token: &str,
+    ) -> DynResult<AuthMethod> {
+        self.auth_minecraft(access_token, (refresh_token.to_string().into(), 0))
+            .await
+    }
+
+    /// 同 [`MicrosoftOAuth::start_auth`]，但会一并记录微软访问令牌的过期时间
+    pub async fn start_auth_with_token(&self, token: &TokenResponse) -> DynResult<AuthMethod> {
+        self.auth_minecraft(
+            &token.access_token,
+            (
+                token.refresh_token.to_owned().into(),
+                crate::auth::expires_at(token.expires_in as u64),
+            ),
+        )
+        .await
+    }
+
+    async fn auth_minecraft(
+        &self,
+        access_token: &str,
+        refresh_token: (Password, u64),
     ) -> DynResult<AuthMethod> {
         let (uhs, xsts_token) = self.auth_xbox_live(access_token).await?;
 
@@ -145,70 +197,18 @@ impl<T: Display> MicrosoftOAuth<T> {
         let xuid = leagcy::get_xuid(&uhs, &xsts_token).await?;
 
         tracing::debug!("正在获取 Mojang 访问令牌");
-        let access_token = leagcy::get_mojang_access_token(&uhs, &xsts_token).await?;
+        let access_token = leagcy::login_with_xbox(&uhs, &xsts_token).await?;
 
-        if access_token.is_empty() {
-            anyhow::bail!("获取令牌失败")
+        if access_token.0.is_empty() {
+            Err(AuthError::MinecraftLogin("获取令牌失败".into()).into())
         } else {
-            tracing::debug!("正在检查是否拥有 Minecraft");
-            let mcstore_resp =
-                crate::http::get("https://api.minecraftservices.com/entitlements/mcstore")
-                    .header(
-                        "Authorization",
-                        &format!("Bearer {}", &access_token.as_string()),
-                    )
-                    .await
-                    .map_err(|e| anyhow::anyhow!(e))?
-                    .body_string()
-                    .await
-                    .map_err(|e| anyhow::anyhow!(e))?;
-            let mcstore_resp: MinecraftStoreResponse = serde_json::from_str(&mcstore_resp)?;
-            if mcstore_resp.items.is_empty() {
-                anyhow::bail!(
-                    "没有在已购项目中找到 Minecraft！请检查你的账户是否已购买 Minecraft！"
-                );
-            }
-            tracing::debug!("正在获取 Minecraft 账户信息");
-            let profile_resp: MinecraftXBoxProfileResponse =
-                crate::http::get("https://api.minecraftservices.com/minecraft/profile")
-                    .header(
-                        "Authorization",
-                        &format!("Bearer {}", &access_token.as_string()),
-                    )
-                    .await
-                    .map_err(|e| anyhow::anyhow!(e))?
-                    .body_json()
-                    .await
-                    .map_err(|e| anyhow::anyhow!(e))?;
-            if profile_resp.error.is_empty() {
-                if let Some(skin) = profile_resp.skins.iter().find(|a| a.state == "ACTIVE") {
-                    tracing::debug!("正在解析皮肤: {}", skin.url);
-                    let skin_data = crate::http::get(&skin.url)
-                        .await
-                        .map_err(|e| anyhow::anyhow!(e))?
-                        .body_bytes()
-                        .await
-                        .map_err(|e| anyhow::anyhow!(e))?;
-                    let (head_skin, hat_skin) =
-                        crate::auth::parse_head_skin(skin_data).context("解析皮肤数据失败")?;
-                    tracing::debug!("微软账户验证成功！");
-                    Ok(AuthMethod::Microsoft {
-                        access_token,
-                        refresh_token: refresh_token.to_string().into(),
-                        xuid,
-                        head_skin,
-                        hat_skin,
-                        player_name: profile_resp.name,
-                        uuid: profile_resp.id,
-                    })
-                } else {
-                    anyhow::bail!("皮肤获取失败！");
-                }
-            } else {
-                anyhow::bail!(
-                    "没有在账户中找到 Minecraft 账户信息！请检查你的账户是否已购买 Minecraft！"
-                );
-            }
+            build_microsoft_account(
+                access_token,
+                refresh_token,
+                xuid,
+                self.client_id.to_string(),
+            )
+            .await
         }
     }
 
@@ -217,6 +217,8 @@ impl<T: Display> MicrosoftOAuth<T> {
         if let AuthMethod::Microsoft {
             access_token,
             refresh_token,
+            microsoft_expires_at,
+            minecraft_expires_at,
             ..
         } = method
         {
@@ -224,11 +226,13 @@ impl<T: Display> MicrosoftOAuth<T> {
             let new_token = self.refresh_token(refresh_token.as_str()).await?;
 
             *refresh_token = new_token.refresh_token.into();
+            *microsoft_expires_at = crate::auth::expires_at(new_token.expires_in as u64);
 
             let (uhs, xsts_token) = self.auth_xbox_live(&new_token.access_token).await?;
 
             tracing::debug!("正在获取 Mojang 访问令牌");
-            let new_access_token = leagcy::get_mojang_access_token(&uhs, &xsts_token).await?;
+            let (new_access_token, new_expires_at) =
+                leagcy::login_with_xbox(&uhs, &xsts_token).await?;
 
             anyhow::ensure!(
                 !new_access_token.is_empty(),
@@ -237,6 +241,7 @@ impl<T: Display> MicrosoftOAuth<T> {
             );
 
             *access_token = new_access_token;
+            *minecraft_expires_at = new_expires_at;
             Ok(())
         } else {
             anyhow::bail!("不支持的方法");
@@ -265,9 +270,11 @@ pub struct DeviceCodeResponse {
     pub message: String,
     /// 错误信息，如果请求正常则此处是空字符串
     pub error: String,
+    /// 错误的详细描述，如果请求正常则此处是空字符串
+    pub error_description: String,
 }
 
-/// 请求设备码身份验证的响应结构
+/// 请求设备码身份验证或兑换授权码的响应结构
 ///
 /// 关于此结构的详情可以查阅 [Microsoft 标识平台和 OAuth 2.0 设备权限授予流 - 成功的身份验证响应](https://learn.microsoft.com/zh-cn/azure/active-directory/develop/v2-oauth2-device-code#successful-authentication-response)
 #[derive(Debug, Clone, Deserialize, Default)]
@@ -287,4 +294,108 @@ pub struct TokenResponse {
     pub refresh_token: String,
     /// 错误信息，如果请求正常则此处是空字符串
     pub error: String,
+    /// 错误的详细描述，如果请求正常则此处是空字符串
+    pub error_description: String,
+}
+
+/// 玩家名称的可用状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProfileNameStatus {
+    /// 名称可用
+    Available,
+    /// 名称已被其他玩家使用
+    Duplicate,
+    /// 名称不合法，例如包含不允许的字符、长度不符或包含敏感词
+    NotAllowed,
+}
+
+#[derive(Debug, Clone, Deserialize, Default)]
+#[serde(default)]
+struct ProfileNameStatusResponse {
+    status: String,
+}
+
+#[derive(Debug, Clone, Deserialize, Default)]
+#[serde(default)]
+struct ProfileErrorResponse {
+    error: String,
+    details: ProfileNameStatusResponse,
+    #[serde(rename = "errorMessage")]
+    error_message: String,
+}
+
+/// 检查玩家名称是否可以用于创建游戏档案
+pub async fn check_profile_name(access_token: &str, name: &str) -> DynResult<ProfileNameStatus> {
+    let resp: ProfileNameStatusResponse = crate::http::get(format!(
+        "https://api.minecraftservices.com/minecraft/profile/name/{}/available",
+        urlencoding::encode(name)
+    ))
+    .header("Authorization", format!("Bearer {access_token}"))
+    .recv_json()
+    .await
+    .map_err(AuthError::network)?;
+    match resp.status.as_str() {
+        "AVAILABLE" => Ok(ProfileNameStatus::Available),
+        "DUPLICATE" => Ok(ProfileNameStatus::Duplicate),
+        "NOT_ALLOWED" => Ok(ProfileNameStatus::NotAllowed),
+        status => anyhow::bail!("未知的名称状态：{}", status),
+    }
+}
+
+/// 为拥有 Minecraft 但还没有游戏档案的微软账户创建游戏档案
+///
+/// 创建成功后会更新账户的 `uuid`、`player_name`、头像和 `has_profile` 字段
+pub async fn create_profile(method: &mut AuthMethod, name: &str) -> DynResult {
+    if let AuthMethod::Microsoft {
+        access_token,
+        uuid,
+        player_name,
+        head_skin,
+        hat_skin,
+        has_profile,
+        ..
+    } = method
+    {
+        anyhow::ensure!(!*has_profile, "该账户已经拥有游戏档案");
+        tracing::debug!("正在创建游戏档案 {}", name);
+        let mut res = crate::http::post("https://api.minecraftservices.com/minecraft/profile")
+            .header("Authorization", format!("Bearer {}", access_token.as_str()))
+            .body_json(&serde_json::json!({ "profileName": name }))
+            .map_err(|e| anyhow::anyhow!(e))?
+            .await
+            .map_err(AuthError::network)?;
+        if !res.status().is_success() {
+            let err: ProfileErrorResponse = res.body_json().await.unwrap_or_default();
+            let status = if err.details.status.is_empty() {
+                err.error.as_str()
+            } else {
+                err.details.status.as_str()
+            };
+            match status {
+                "DUPLICATE" => anyhow::bail!("名称 {} 已被其他玩家使用", name),
+                "NOT_ALLOWED" | "CONSTRAINT_VIOLATION" => {
+                    anyhow::bail!("名称 {} 不合法", name)
+                }
+                "NOT_ENTITLED" => return Err(AuthError::NotOwned.into()),
+                _ => anyhow::bail!(
+                    "创建游戏档案失败：{} {} {}",
+                    res.status(),
+                    status,
+                    err.error_message
+                ),
+            }
+        }
+        let profile: MinecraftXBoxProfileResponse =
+            res.body_json().await.map_err(AuthError::network)?;
+        let (new_head_skin, new_hat_skin) =
+            get_profile_head_skin(&profile).await.unwrap_or_default();
+        *uuid = profile.id;
+        *player_name = profile.name;
+        *head_skin = new_head_skin;
+        *hat_skin = new_hat_skin;
+        *has_profile = true;
+        Ok(())
+    } else {
+        anyhow::bail!("不支持的方法");
+    }
 }
@@ -9,15 +9,18 @@ use image::{GenericImageView, Pixel};
 use structs::mojang::{ProfileResponse, ProfileTexture};
 
 use self::structs::AuthMethod;
-use crate::{
-    http::{no_retry::*, RequestResult},
-    password::Password,
-    prelude::*,
-};
+use crate::{password::Password, prelude::*};
 
 pub mod authlib;
+pub mod authlib_server;
+pub mod error;
 pub mod microsoft;
+pub mod offline;
+pub mod skin;
+pub mod skin_render;
+pub mod store;
 pub mod structs;
+pub mod yggdrasil;
 
 /// 根据玩家名称生成一个固定的离线 UUID
 ///
@@ -32,6 +35,8 @@ pub mod structs;
 /// # }
 /// ```
 /// 生成方式参考： <https://github.com/PrismarineJS/node-minecraft-protocol/blob/21240f8ab2fd41c76f50b64e3b3a945f50b25b5e/src/datatypes/uuid.js#L14>
+///
+/// 如果需要其它的 UUID 生成方式，可以使用 [`offline::OfflineAccountBuilder`] 创建离线账户
 pub fn generate_offline_uuid(player_name: &str) -> md5::Digest {
     let mut ctx = md5::Context::new();
     ctx.consume("OfflinePlayer:");
@@ -112,80 +117,136 @@ async fn get_head_skin(uuid: &str) -> DynResult<(Vec<u8>, Vec<u8>)> {
 
 /// 进行 Mojang 正版验证
 ///
-/// **此验证方式已经弃用**，请开发者建议用户迁移到 Microsoft 账户后使用 [`crate::auth::microsoft::start_auth`] 进行 Microsoft 正版验证
+/// **此验证方式已经弃用**，请开发者建议用户迁移到 Microsoft 账户后使用 [`crate::auth::microsoft::MicrosoftOAuth`] 进行 Microsoft 正版验证
+///
+/// 如果服务器已经选择了角色或账户只有一个角色，则只会返回那个角色，且令牌已经绑定到该角色上。
+/// 如果返回了多个角色，则需要在用户选择其中一个后调用 [`select_profile`] 进行绑定。
 pub async fn auth_mojang(
     _ctx: Option<impl Reporter>,
     username: &str,
     password: &Password,
     client_token: &str,
-) -> DynResult<AuthMethod> {
-    // https://authserver.mojang.com/authenticate
-    let body = structs::mojang::AuthenticateBody {
-        username: username.into(),
-        password: password.to_owned(),
-        client_token: client_token.into(),
-        ..Default::default()
-    };
-    let result: RequestResult<structs::mojang::AuthenticateResponse> =
-        post_data("https://authserver.mojang.com/authenticate", &body).await?;
-    match result {
-        RequestResult::Ok(a) => {
-            let selected_profile = if let Some(selected_profile) = a.selected_profile {
-                selected_profile
-            } else if let Some(profile) = a.available_profiles.into_iter().next() {
-                profile // TODO: 选择所需要添加的多角色
-            } else {
-                anyhow::bail!("该账户没有可用的角色！")
-            };
-            let (head_skin, hat_skin) = get_head_skin(&selected_profile.id).await?;
-            Ok(AuthMethod::Mojang {
-                access_token: a.access_token,
-                uuid: selected_profile.id,
-                player_name: selected_profile.name,
+) -> DynResult<Vec<AuthMethod>> {
+    let selection = yggdrasil::authenticate_and_select(
+        yggdrasil::MOJANG_AUTH_SERVER,
+        username,
+        password,
+        client_token,
+    )
+    .await?;
+    match selection {
+        yggdrasil::ProfileSelection::Bound {
+            access_token,
+            profile,
+        } => {
+            let (head_skin, hat_skin) = get_head_skin(&profile.id).await?;
+            Ok(vec![AuthMethod::Mojang {
+                access_token,
+                uuid: profile.id,
+                player_name: profile.name,
                 head_skin,
                 hat_skin,
-            })
+            }])
         }
-        RequestResult::Err(_) => Ok(AuthMethod::Offline {
-            player_name: "".into(),
-            uuid: "".into(),
-        }),
+        yggdrasil::ProfileSelection::Unbound {
+            access_token,
+            profiles,
+        } => Ok(
+            futures::future::join_all(profiles.into_iter().map(|x| async {
+                let (head_skin, hat_skin) = get_head_skin(&x.id)
+                    .await
+                    .unwrap_or_else(|_| (vec![0; 2 * 4 * 64], vec![0; 2 * 4 * 64]));
+                AuthMethod::Mojang {
+                    access_token: access_token.to_owned(),
+                    uuid: x.id,
+                    player_name: x.name,
+                    head_skin,
+                    hat_skin,
+                }
+            }))
+            .await,
+        ),
+    }
+}
+
+/// 将尚未绑定角色的令牌绑定到账户当前的角色上
+///
+/// 用于 [`auth_mojang`] 或 [`authlib::start_auth`] 返回了多个角色的情况，
+/// 在用户选择其中一个账户后调用此函数，该账户的令牌会被替换为绑定了该角色的新令牌，
+/// 同一次登录返回的其它账户的令牌随之失效。
+///
+/// 仅支持 Mojang 账户和外置登录账户。
+pub async fn select_profile(am: &mut AuthMethod, client_token: &str) -> DynResult<()> {
+    match am {
+        AuthMethod::Mojang {
+            access_token,
+            uuid,
+            player_name,
+            ..
+        } => {
+            let profile = yggdrasil::YggdrasilProfile {
+                id: uuid.to_owned(),
+                name: player_name.to_owned(),
+            };
+            let res = yggdrasil::refresh(
+                yggdrasil::MOJANG_AUTH_SERVER,
+                access_token.as_str(),
+                client_token,
+                Some(&profile),
+            )
+            .await?;
+            let profile = res.selected_profile.unwrap_or(profile);
+            *access_token = res.access_token;
+            *uuid = profile.id;
+            *player_name = profile.name;
+            Ok(())
+        }
+        AuthMethod::AuthlibInjector { .. } => {
+            *am = authlib::refresh_token(am.to_owned(), client_token, true).await?;
+            Ok(())
+        }
+        _ => anyhow::bail!("该账户类型不支持选择角色"),
+    }
+}
+
+/// 使用 Yggdrasil 协议刷新 Mojang 账户的令牌，令牌有效时不会刷新
+///
+/// 返回令牌是否被刷新了
+async fn refresh_mojang(am: &mut AuthMethod, client_token: &str) -> DynResult<bool> {
+    if let AuthMethod::Mojang { access_token, .. } = am {
+        if yggdrasil::validate(
+            yggdrasil::MOJANG_AUTH_SERVER,
+            access_token.as_str(),
+            client_token,
+        )
+        .await?
+        {
+            return Ok(false);
+        }
+        let res = yggdrasil::refresh(
+            yggdrasil::MOJANG_AUTH_SERVER,
+            access_token.as_str(),
+            client_token,
+            None,
+        )
+        .await?;
+        *access_token = res.access_token;
+        Ok(true)
+    } else {
+        anyhow::bail!("此函数只支持 Mojang 账户")
     }
 }
 
 /// 刷新/续期访问令牌
 pub async fn refresh_auth(am: &mut AuthMethod, client_token: &str) -> DynResult<bool> {
-    if let &mut AuthMethod::Microsoft { .. } = am {
-        return Ok(microsoft::leagcy::refresh_auth(am).await.is_ok());
-    }
     match am {
-        AuthMethod::Mojang { access_token, .. } => {
-            let body = structs::mojang::ValidateResponse {
-                access_token: access_token.to_owned(),
-                client_token: client_token.to_owned(),
-            };
-            let result = crate::http::post("https://authserver.mojang.com/validate")
-                .body(serde_json::to_vec(&body)?)
-                .header("Content-Type", "application/json")
-                .await
-                .map_err(|e| anyhow::anyhow!("发送用户信息请求失败，可能是网络问题：{:?}", e))?;
-            if result.status().is_success() {
-                Ok(true)
-            } else {
-                Ok(false)
+        AuthMethod::Microsoft { .. } => {
+            if !am.needs_refresh() {
+                return Ok(true);
             }
+            Ok(refresh_microsoft(am).await.is_ok())
         }
-        AuthMethod::Microsoft { access_token, .. } => {
-            // TODO: 增加正确的检测方式
-            let profile_resp =
-                crate::http::get("https://api.minecraftservices.com/minecraft/profile")
-                    .header("Authorization", &format!("Bearer {}", &access_token))
-                    .await
-                    .map_err(|e| {
-                        anyhow::anyhow!("发送用户信息请求失败，有可能是网络问题：{:?}", e)
-                    })?;
-            Ok(profile_resp.status().is_success())
-        }
+        AuthMethod::Mojang { .. } => Ok(refresh_mojang(am, client_token).await.is_ok()),
         AuthMethod::AuthlibInjector { .. } => {
             if let Ok(new_am) =
                 crate::auth::authlib::refresh_token(am.to_owned(), client_token, false).await
@@ -199,3 +260,130 @@ pub async fn refresh_auth(am: &mut AuthMethod, client_token: &str) -> DynResult<
         _ => Ok(true),
     }
 }
+
+/// 使用账户登录时的客户端 ID 刷新微软账户的令牌
+async fn refresh_microsoft(am: &mut AuthMethod) -> DynResult {
+    match am {
+        AuthMethod::Microsoft { client_id, .. } if !client_id.is_empty() => {
+            microsoft::MicrosoftOAuth::new(client_id.to_owned())
+                .refresh_auth(am)
+                .await
+        }
+        _ => microsoft::leagcy::refresh_auth(am).await,
+    }
+}
+
+/// 获取当前的 UNIX 时间戳（秒）
+pub(crate) fn unix_timestamp() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|x| x.as_secs())
+        .unwrap_or_default()
+}
+
+/// 将服务器返回的有效秒数转换为过期时间戳，有效秒数为 `0` 时视为未知，返回 `0`
+pub(crate) fn expires_at(expires_in: u64) -> u64 {
+    if expires_in == 0 {
+        0
+    } else {
+        unix_timestamp() + expires_in
+    }
+}
+
+/// [`refresh_if_needed`] 的结果
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RefreshOutcome {
+    /// 令牌依然有效，无需刷新
+    Valid,
+    /// 令牌已刷新，调用方应当保存更新后的账户
+    Refreshed,
+    /// 令牌已失效且无法刷新，需要让用户重新登录
+    ReloginRequired,
+}
+
+/// 在启动游戏前检查账户令牌，并在需要时刷新令牌
+///
+/// 微软账户会使用登录时记录的客户端 ID 通过 [`microsoft::MicrosoftOAuth`] 刷新，
+/// 没有记录客户端 ID 的账户会按照 [`microsoft::leagcy`] 的方式刷新。
+///
+/// 网络错误等暂时性的错误会直接返回，只有令牌确实无法继续使用时才会返回 [`RefreshOutcome::ReloginRequired`]。
+pub async fn refresh_if_needed(
+    am: &mut AuthMethod,
+    client_token: &str,
+) -> DynResult<RefreshOutcome> {
+    if !am.needs_refresh() {
+        return Ok(RefreshOutcome::Valid);
+    }
+    let result = match am {
+        AuthMethod::Offline { .. } => return Ok(RefreshOutcome::Valid),
+        AuthMethod::Microsoft { .. } => refresh_microsoft(am).await,
+        AuthMethod::Mojang { .. } => match refresh_mojang(am, client_token).await {
+            Ok(false) => return Ok(RefreshOutcome::Valid),
+            Ok(true) => Ok(()),
+            Err(err) => Err(err),
+        },
+        AuthMethod::AuthlibInjector {
+            api_location,
+            access_token,
+            ..
+        } => {
+            if authlib::validate(api_location, access_token.as_str(), client_token).await? {
+                return Ok(RefreshOutcome::Valid);
+            }
+            authlib::refresh_token(am.to_owned(), client_token, false)
+                .await
+                .map(|new_am| *am = new_am)
+        }
+    };
+    match result {
+        Ok(()) => Ok(RefreshOutcome::Refreshed),
+        Err(err) => match err.downcast_ref::<error::AuthError>() {
+            Some(error::AuthError::InvalidRefreshToken(_)) => Ok(RefreshOutcome::ReloginRequired),
+            _ => Err(err),
+        },
+    }
+}
+
+#[test]
+fn refresh_if_needed_test() {
+    let microsoft = |microsoft_expires_at, minecraft_expires_at| AuthMethod::Microsoft {
+        access_token: "mc-access-token".into(),
+        refresh_token: "ms-refresh-token".into(),
+        uuid: "uuid".into(),
+        xuid: "xuid".into(),
+        player_name: "Steve".into(),
+        head_skin: vec![],
+        hat_skin: vec![],
+        ownership: Default::default(),
+        has_profile: true,
+        client_id: "client-id".into(),
+        microsoft_expires_at,
+        minecraft_expires_at,
+    };
+    let now = unix_timestamp();
+    let day = 24 * 60 * 60;
+    assert!(microsoft(0, 0).needs_refresh());
+    assert!(microsoft(0, now - 1).needs_refresh());
+    assert!(microsoft(0, now + 60).needs_refresh());
+    assert!(microsoft(now + 60, now + day).needs_refresh());
+    assert!(!microsoft(0, now + day).needs_refresh());
+    assert!(!microsoft(now + day, now + day).needs_refresh());
+
+    let mut offline = AuthMethod::Offline {
+        player_name: "Alex".into(),
+        uuid: "uuid".into(),
+    };
+    assert!(!offline.needs_refresh());
+    let mut valid = microsoft(now + day, now + day);
+    inner_future::block_on(async {
+        assert_eq!(
+            refresh_if_needed(&mut offline, "").await.unwrap(),
+            RefreshOutcome::Valid
+        );
+        assert_eq!(
+            refresh_if_needed(&mut valid, "").await.unwrap(),
+            RefreshOutcome::Valid
+        );
+    });
+    assert_eq!(valid, microsoft(now + day, now + day));
+}
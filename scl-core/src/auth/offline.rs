@@ -0,0 +1,258 @@
+//! 离线账户的创建模块，支持多种统一标识（UUID）的生成方式及玩家名称的检查
+
+use std::fmt::Display;
+
+use super::{generate_offline_uuid, skin::SkinVariant, structs::AuthMethod};
+use crate::prelude::*;
+
+/// 玩家名称的最短长度
+pub const PLAYER_NAME_MIN_LEN: usize = 3;
+/// 玩家名称的最长长度
+pub const PLAYER_NAME_MAX_LEN: usize = 16;
+
+/// 玩家名称不符合要求的原因
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PlayerNameError {
+    /// 玩家名称过短
+    TooShort,
+    /// 玩家名称过长
+    TooLong,
+    /// 玩家名称包含了不允许的字符，只允许英文字母、数字和下划线
+    InvalidChar(char),
+}
+
+impl Display for PlayerNameError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::TooShort => write!(f, "玩家名称至少需要 {PLAYER_NAME_MIN_LEN} 个字符"),
+            Self::TooLong => write!(f, "玩家名称最多只能有 {PLAYER_NAME_MAX_LEN} 个字符"),
+            Self::InvalidChar(c) => write!(
+                f,
+                "玩家名称包含了不允许的字符“{c}”，只能使用英文字母、数字和下划线"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for PlayerNameError {}
+
+/// 检查玩家名称是否符合正版玩家名称的要求，即由 3 到 16 个英文字母、数字或下划线组成
+///
+/// 不符合要求的名称可能会导致无法加入服务器或者游戏内显示异常
+pub fn validate_player_name(player_name: &str) -> Result<(), PlayerNameError> {
+    if let Some(c) = player_name
+        .chars()
+        .find(|c| !c.is_ascii_alphanumeric() && *c != '_')
+    {
+        return Err(PlayerNameError::InvalidChar(c));
+    }
+    if player_name.len() < PLAYER_NAME_MIN_LEN {
+        Err(PlayerNameError::TooShort)
+    } else if player_name.len() > PLAYER_NAME_MAX_LEN {
+        Err(PlayerNameError::TooLong)
+    } else {
+        Ok(())
+    }
+}
+
+/// 将带或不带连字符的 UUID 转换为无符号的小写十六进制形式
+pub fn parse_uuid(uuid: &str) -> DynResult<String> {
+    let result: String = uuid
+        .trim()
+        .chars()
+        .filter(|c| *c != '-')
+        .map(|c| c.to_ascii_lowercase())
+        .collect();
+    if result.len() != 32 || !result.chars().all(|c| c.is_ascii_hexdigit()) {
+        anyhow::bail!("UUID 格式不正确：{}", uuid);
+    }
+    Ok(result)
+}
+
+/// 计算和 Java 的 `UUID.hashCode()` 一致的哈希值
+pub fn java_uuid_hash_code(uuid: &str) -> DynResult<i32> {
+    let uuid = u128::from_str_radix(&parse_uuid(uuid)?, 16)?;
+    let hilo = (uuid >> 64) as u64 ^ uuid as u64;
+    Ok(((hilo >> 32) as i32) ^ (hilo as i32))
+}
+
+/// 原版在玩家没有设置皮肤时使用的默认皮肤
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DefaultSkin {
+    /// Steve，使用经典模型
+    Steve,
+    /// Alex，使用纤细模型
+    Alex,
+}
+
+impl DefaultSkin {
+    /// 根据玩家的 UUID 选择默认皮肤，和原版的规则一致，即 UUID 的哈希值为奇数时使用 Alex
+    pub fn from_uuid(uuid: &str) -> DynResult<Self> {
+        if java_uuid_hash_code(uuid)? & 1 == 1 {
+            Ok(Self::Alex)
+        } else {
+            Ok(Self::Steve)
+        }
+    }
+
+    /// 默认皮肤使用的模型
+    pub fn variant(&self) -> SkinVariant {
+        match self {
+            Self::Steve => SkinVariant::Classic,
+            Self::Alex => SkinVariant::Slim,
+        }
+    }
+}
+
+/// 离线账户 UUID 的生成方式
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum OfflineUuid {
+    /// 使用原版离线模式的规则，根据玩家名称生成固定的 UUID，参考 [`generate_offline_uuid`]
+    #[default]
+    Standard,
+    /// 随机生成一个版本 4 的 UUID
+    Random,
+    /// 使用用户提供的 UUID，带或不带连字符均可，通常用于从其它启动器或服务器迁移存档
+    Custom(String),
+    /// 使用该玩家名称对应的正版账户的 UUID，需要联网查询
+    ///
+    /// 如果该名称没有对应的正版账户或者查询失败，则会返回错误，此时可以改用其它方式
+    Mojang,
+}
+
+/// 生成一个随机的版本 4 UUID，返回无符号的十六进制形式
+pub fn generate_random_uuid() -> String {
+    let mut bytes: [u8; 16] = rand::random();
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+    format!("{:032x}", u128::from_be_bytes(bytes))
+}
+
+#[derive(Debug, Deserialize)]
+struct MojangProfileResponse {
+    id: String,
+}
+
+/// 查询玩家名称对应的正版账户 UUID，如果该名称没有对应的正版账户则返回 `None`
+pub async fn lookup_mojang_uuid(player_name: &str) -> DynResult<Option<String>> {
+    let mut resp = crate::http::get(format!(
+        "https://api.mojang.com/users/profiles/minecraft/{}",
+        urlencoding::encode(player_name)
+    ))
+    .await
+    .map_err(super::error::AuthError::network)?;
+    match u16::from(resp.status()) {
+        200 => {
+            let profile: MojangProfileResponse = resp
+                .body_json()
+                .await
+                .map_err(|e| anyhow::anyhow!("无法解析正版账户信息：{:?}", e))?;
+            Ok(Some(parse_uuid(&profile.id)?))
+        }
+        204 | 404 => Ok(None),
+        status => anyhow::bail!("查询正版账户信息失败，状态码：{}", status),
+    }
+}
+
+/// 离线账户构建器
+#[derive(Debug, Clone)]
+pub struct OfflineAccountBuilder {
+    player_name: String,
+    uuid: OfflineUuid,
+    check_name: bool,
+}
+
+impl OfflineAccountBuilder {
+    /// 使用玩家名称创建构建器，默认使用 [`OfflineUuid::Standard`] 生成 UUID
+    pub fn new(player_name: impl Into<String>) -> Self {
+        Self {
+            player_name: player_name.into(),
+            uuid: OfflineUuid::Standard,
+            check_name: true,
+        }
+    }
+
+    /// 设置 UUID 的生成方式
+    pub fn uuid(mut self, uuid: OfflineUuid) -> Self {
+        self.uuid = uuid;
+        self
+    }
+
+    /// 设置是否检查玩家名称，默认会检查，参考 [`validate_player_name`]
+    ///
+    /// 如果用户需要沿用在其它启动器中使用的不规范名称，可以关闭检查
+    pub fn check_name(mut self, check_name: bool) -> Self {
+        self.check_name = check_name;
+        self
+    }
+
+    /// 构建离线账户，只有 [`OfflineUuid::Mojang`] 需要联网
+    pub async fn build(self) -> DynResult<AuthMethod> {
+        if self.check_name {
+            validate_player_name(&self.player_name)?;
+        } else if self.player_name.is_empty() {
+            return Err(PlayerNameError::TooShort.into());
+        }
+        let uuid = match self.uuid {
+            OfflineUuid::Standard => format!("{:x}", generate_offline_uuid(&self.player_name)),
+            OfflineUuid::Random => generate_random_uuid(),
+            OfflineUuid::Custom(uuid) => parse_uuid(&uuid)?,
+            OfflineUuid::Mojang => {
+                lookup_mojang_uuid(&self.player_name)
+                    .await?
+                    .ok_or_else(|| {
+                        anyhow::anyhow!("玩家名称 {} 没有对应的正版账户", self.player_name)
+                    })?
+            }
+        };
+        Ok(AuthMethod::Offline {
+            player_name: self.player_name,
+            uuid,
+        })
+    }
+}
+
+#[test]
+fn offline_account_test() {
+    assert_eq!(validate_player_name("Steve_2011"), Ok(()));
+    assert_eq!(validate_player_name("ab"), Err(PlayerNameError::TooShort));
+    assert_eq!(
+        validate_player_name("abcdefghijklmnopq"),
+        Err(PlayerNameError::TooLong)
+    );
+    assert_eq!(
+        validate_player_name("史蒂夫"),
+        Err(PlayerNameError::InvalidChar('史'))
+    );
+    assert_eq!(
+        validate_player_name("Steve Jobs"),
+        Err(PlayerNameError::InvalidChar(' '))
+    );
+
+    assert_eq!(
+        parse_uuid("069A79F4-44E9-4726-A5BE-FCA90E38AAF5").unwrap(),
+        "069a79f444e94726a5befca90e38aaf5"
+    );
+    assert!(parse_uuid("069a79f4-44e9-4726-a5be").is_err());
+    let random = generate_random_uuid();
+    assert_eq!(random.len(), 32);
+    assert_eq!(&random[12..13], "4");
+
+    assert_eq!(
+        DefaultSkin::from_uuid("069a79f4-44e9-4726-a5be-fca90e38aaf5").unwrap(),
+        DefaultSkin::Steve
+    );
+    assert_eq!(
+        DefaultSkin::from_uuid("853c80ef3c3749fdaa49938b674adae6").unwrap(),
+        DefaultSkin::Alex
+    );
+
+    let account = inner_future::block_on(OfflineAccountBuilder::new("Steve").build()).unwrap();
+    assert_eq!(
+        account,
+        AuthMethod::Offline {
+            player_name: "Steve".into(),
+            uuid: "5627dd98e6be3c21b8a8e92344183641".into(),
+        }
+    );
+}
@@ -0,0 +1,596 @@
+//! 皮肤和披风管理模块
+//!
+//! 微软账户通过 Minecraft 服务 API 管理皮肤和披风，
+//! 具体请查阅 <https://wiki.vg/Mojang_API#Profile_Information>
+//!
+//! 外置登录账户通过 Yggdrasil 服务端的材质上传接口管理皮肤和披风，
+//! 具体请查阅 [Yggdrasil 服务端技术规范 - 材质上传](https://github.com/yushijinhun/authlib-injector/wiki/Yggdrasil-%E6%9C%8D%E5%8A%A1%E7%AB%AF%E6%8A%80%E6%9C%AF%E8%A7%84%E8%8C%83#%E6%9D%90%E8%B4%A8%E4%B8%8A%E4%BC%A0)
+
+use std::fmt::Display;
+
+use super::{error::AuthError, structs::AuthMethod};
+use crate::prelude::*;
+
+const PROFILE_URL: &str = "https://api.minecraftservices.com/minecraft/profile";
+
+/// 皮肤的模型，决定了手臂的宽度
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize)]
+pub enum SkinVariant {
+    /// 经典模型（Steve），手臂宽 4 像素
+    #[default]
+    #[serde(rename = "CLASSIC", alias = "classic")]
+    Classic,
+    /// 纤细模型（Alex），手臂宽 3 像素
+    #[serde(rename = "SLIM", alias = "slim")]
+    Slim,
+}
+
+impl SkinVariant {
+    /// 获取请求接口时使用的模型名称
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Classic => "classic",
+            Self::Slim => "slim",
+        }
+    }
+
+    /// 模型的手臂宽度
+    pub(crate) fn arm_width(&self) -> u32 {
+        match self {
+            Self::Classic => 4,
+            Self::Slim => 3,
+        }
+    }
+}
+
+impl Display for SkinVariant {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// 材质的种类，用于外置登录的材质接口
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextureType {
+    /// 皮肤
+    Skin,
+    /// 披风
+    Cape,
+}
+
+impl TextureType {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Skin => "skin",
+            Self::Cape => "cape",
+        }
+    }
+}
+
+/// 微软账户游戏档案中的一个皮肤
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct ProfileSkin {
+    /// 皮肤 ID
+    pub id: String,
+    /// 皮肤状态，正在使用的皮肤为 `ACTIVE`
+    pub state: String,
+    /// 皮肤的贴图链接
+    pub url: String,
+    /// 皮肤的模型
+    pub variant: SkinVariant,
+    /// 皮肤的别名，只有默认皮肤会有，例如 `STEVE`
+    pub alias: String,
+}
+
+impl ProfileSkin {
+    /// 是否是正在使用的皮肤
+    pub fn is_active(&self) -> bool {
+        self.state == "ACTIVE"
+    }
+}
+
+/// 微软账户游戏档案中的一个披风
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct ProfileCape {
+    /// 披风 ID，用于 [`show_cape`]
+    pub id: String,
+    /// 披风状态，正在展示的披风为 `ACTIVE`
+    pub state: String,
+    /// 披风的贴图链接
+    pub url: String,
+    /// 披风的名称，例如 `Migrator`
+    pub alias: String,
+}
+
+impl ProfileCape {
+    /// 是否是正在展示的披风
+    pub fn is_active(&self) -> bool {
+        self.state == "ACTIVE"
+    }
+}
+
+/// 微软账户的游戏档案，包含所有的皮肤和披风
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct MinecraftProfile {
+    /// 正版玩家的统一标识
+    pub id: String,
+    /// 正版玩家的名称
+    pub name: String,
+    /// 皮肤列表
+    pub skins: Vec<ProfileSkin>,
+    /// 拥有的披风列表
+    pub capes: Vec<ProfileCape>,
+}
+
+impl MinecraftProfile {
+    /// 获取正在使用的皮肤
+    pub fn active_skin(&self) -> Option<&ProfileSkin> {
+        self.skins.iter().find(|x| x.is_active())
+    }
+
+    /// 获取正在展示的披风，如果隐藏了披风则返回 `None`
+    pub fn active_cape(&self) -> Option<&ProfileCape> {
+        self.capes.iter().find(|x| x.is_active())
+    }
+}
+
+/// 皮肤模型中的部件
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum SkinPart {
+    Head,
+    Body,
+    RightArm,
+    LeftArm,
+    RightLeg,
+    LeftLeg,
+}
+
+impl SkinPart {
+    pub(crate) const ALL: [Self; 6] = [
+        Self::Head,
+        Self::Body,
+        Self::RightArm,
+        Self::LeftArm,
+        Self::RightLeg,
+        Self::LeftLeg,
+    ];
+
+    /// 旧版 64x32 皮肤中存在的部件，左手和左腿使用右手和右腿镜像得到
+    pub(crate) const LEGACY: [Self; 4] = [Self::Head, Self::Body, Self::RightArm, Self::RightLeg];
+
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Head => "头部",
+            Self::Body => "身体",
+            Self::RightArm => "右臂",
+            Self::LeftArm => "左臂",
+            Self::RightLeg => "右腿",
+            Self::LeftLeg => "左腿",
+        }
+    }
+
+    /// 部件在贴图上的展开位置，`overlay` 为 `true` 时返回外层（帽子、外套等）的位置
+    pub(crate) fn uv_box(&self, variant: SkinVariant, overlay: bool) -> SkinBox {
+        let arm = variant.arm_width();
+        let (u, v, w, h, d) = match (self, overlay) {
+            (Self::Head, false) => (0, 0, 8, 8, 8),
+            (Self::Head, true) => (32, 0, 8, 8, 8),
+            (Self::Body, false) => (16, 16, 8, 12, 4),
+            (Self::Body, true) => (16, 32, 8, 12, 4),
+            (Self::RightArm, false) => (40, 16, arm, 12, 4),
+            (Self::RightArm, true) => (40, 32, arm, 12, 4),
+            (Self::LeftArm, false) => (32, 48, arm, 12, 4),
+            (Self::LeftArm, true) => (48, 48, arm, 12, 4),
+            (Self::RightLeg, false) => (0, 16, 4, 12, 4),
+            (Self::RightLeg, true) => (0, 32, 4, 12, 4),
+            (Self::LeftLeg, false) => (16, 48, 4, 12, 4),
+            (Self::LeftLeg, true) => (0, 48, 4, 12, 4),
+        };
+        SkinBox { u, v, w, h, d }
+    }
+}
+
+/// 一个长方体部件在贴图上的展开位置，`w` `h` `d` 分别为宽、高、厚
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct SkinBox {
+    pub u: u32,
+    pub v: u32,
+    pub w: u32,
+    pub h: u32,
+    pub d: u32,
+}
+
+/// 贴图上的一个矩形区域，分别为 `(x, y, 宽, 高)`
+pub(crate) type Rect = (u32, u32, u32, u32);
+
+impl SkinBox {
+    pub(crate) fn top(&self) -> Rect {
+        (self.u + self.d, self.v, self.w, self.d)
+    }
+
+    pub(crate) fn bottom(&self) -> Rect {
+        (self.u + self.d + self.w, self.v, self.w, self.d)
+    }
+
+    pub(crate) fn right(&self) -> Rect {
+        (self.u, self.v + self.d, self.d, self.h)
+    }
+
+    pub(crate) fn front(&self) -> Rect {
+        (self.u + self.d, self.v + self.d, self.w, self.h)
+    }
+
+    pub(crate) fn left(&self) -> Rect {
+        (self.u + self.d + self.w, self.v + self.d, self.d, self.h)
+    }
+
+    pub(crate) fn back(&self) -> Rect {
+        (
+            self.u + self.d * 2 + self.w,
+            self.v + self.d,
+            self.w,
+            self.h,
+        )
+    }
+
+    pub(crate) fn faces(&self) -> [Rect; 6] {
+        [
+            self.top(),
+            self.bottom(),
+            self.right(),
+            self.front(),
+            self.left(),
+            self.back(),
+        ]
+    }
+}
+
+/// 检查皮肤文件是否可以上传
+///
+/// 皮肤必须是大小为 64x64 或 64x32（旧版格式）的 PNG 图片，否则返回错误。
+///
+/// 基础层（除帽子、外套等外层以外的部分）存在透明像素时依然可以上传，但游戏中会显示为黑色，
+/// 所以会以警告的形式返回，每个存在透明像素的部位返回一条，可用于提示用户。
+/// 纤细模型的皮肤需要以 [`SkinVariant::Slim`] 检查，否则手臂的第四列会被当作透明像素。
+pub fn validate_skin(data: &[u8], variant: SkinVariant) -> DynResult<Vec<String>> {
+    let skin = image::load_from_memory_with_format(data, image::ImageFormat::Png)
+        .map_err(|_| anyhow::anyhow!("皮肤文件不是有效的 PNG 图片"))?
+        .to_rgba8();
+    let parts: &[SkinPart] = match skin.dimensions() {
+        (64, 64) => &SkinPart::ALL,
+        (64, 32) => &SkinPart::LEGACY,
+        (width, height) => anyhow::bail!(
+            "皮肤大小必须为 64x64 或 64x32，但这个皮肤的大小为 {}x{}",
+            width,
+            height
+        ),
+    };
+    let mut warnings = vec![];
+    for part in parts {
+        let transparent = part
+            .uv_box(variant, false)
+            .faces()
+            .into_iter()
+            .flat_map(|(x, y, w, h)| {
+                (y..y + h).flat_map(move |py| (x..x + w).map(move |px| (px, py)))
+            })
+            .find(|&(px, py)| skin.get_pixel(px, py).0[3] != 0xFF);
+        if let Some((px, py)) = transparent {
+            warnings.push(format!(
+                "皮肤的{}存在透明像素，位于 ({}, {})，游戏中会显示为黑色",
+                part.name(),
+                px,
+                py
+            ));
+        }
+    }
+    Ok(warnings)
+}
+
+/// 构建一个 `multipart/form-data` 请求体，返回对应的 `Content-Type` 和请求体
+fn multipart_body(fields: &[(&str, &str)], file: &[u8]) -> (String, Vec<u8>) {
+    let boundary = format!("----SCLFormBoundary{:016x}", rand::random::<u64>());
+    let mut body = Vec::with_capacity(file.len() + 512);
+    for (name, value) in fields {
+        body.extend_from_slice(
+            format!(
+                "--{boundary}\r\nContent-Disposition: form-data; name=\"{name}\"\r\n\r\n{value}\r\n"
+            )
+            .as_bytes(),
+        );
+    }
+    body.extend_from_slice(
+        format!(
+            "--{boundary}\r\nContent-Disposition: form-data; name=\"file\"; filename=\"texture.png\"\r\nContent-Type: image/png\r\n\r\n"
+        )
+        .as_bytes(),
+    );
+    body.extend_from_slice(file);
+    body.extend_from_slice(format!("\r\n--{boundary}--\r\n").as_bytes());
+    (format!("multipart/form-data; boundary={boundary}"), body)
+}
+
+async fn recv_profile(res: surf::RequestBuilder, action: &str) -> DynResult<MinecraftProfile> {
+    let mut res = res.await.map_err(AuthError::network)?;
+    if !res.status().is_success() {
+        let body = res.body_string().await.unwrap_or_default();
+        anyhow::bail!(
+            "{}失败：服务器返回了状态码 {} {}",
+            action,
+            res.status(),
+            body
+        );
+    }
+    Ok(res.body_json().await.map_err(AuthError::network)?)
+}
+
+async fn send_yggdrasil(res: surf::RequestBuilder, action: &str) -> DynResult {
+    let mut res = res.await.map_err(AuthError::network)?;
+    if !res.status().is_success() {
+        let body = res.body_string().await.unwrap_or_default();
+        anyhow::bail!(
+            "{}失败：服务器返回了状态码 {} {}",
+            action,
+            res.status(),
+            body
+        );
+    }
+    Ok(())
+}
+
+fn yggdrasil_texture_url(api_location: &str, uuid: &str, texture_type: TextureType) -> String {
+    format!(
+        "{api_location}api/user/profile/{uuid}/{}",
+        texture_type.as_str()
+    )
+}
+
+/// 获取微软账户的游戏档案，包含所有的皮肤和披风
+pub async fn get_profile(access_token: &str) -> DynResult<MinecraftProfile> {
+    recv_profile(
+        crate::http::get(PROFILE_URL).header("Authorization", format!("Bearer {access_token}")),
+        "获取游戏档案",
+    )
+    .await
+}
+
+/// 上传一个皮肤文件并设置为当前皮肤，上传前会使用 [`validate_skin`] 检查皮肤
+///
+/// 支持微软账户和外置登录账户，上传成功后会同时更新账户的头像
+pub async fn upload_skin(method: &mut AuthMethod, variant: SkinVariant, png: &[u8]) -> DynResult {
+    for warning in validate_skin(png, variant)? {
+        tracing::warn!("{warning}");
+    }
+    match method {
+        AuthMethod::Microsoft { access_token, .. } => {
+            let (content_type, body) = multipart_body(&[("variant", variant.as_str())], png);
+            recv_profile(
+                crate::http::post(format!("{PROFILE_URL}/skins"))
+                    .header("Authorization", format!("Bearer {}", access_token.as_str()))
+                    .header("Content-Type", content_type)
+                    .body_bytes(body),
+                "上传皮肤",
+            )
+            .await?;
+        }
+        AuthMethod::AuthlibInjector {
+            api_location,
+            access_token,
+            uuid,
+            ..
+        } => {
+            // 经典模型需要提交空字符串
+            let model = match variant {
+                SkinVariant::Classic => "",
+                SkinVariant::Slim => "slim",
+            };
+            let (content_type, body) = multipart_body(&[("model", model)], png);
+            send_yggdrasil(
+                crate::http::put(yggdrasil_texture_url(api_location, uuid, TextureType::Skin))
+                    .header("Authorization", format!("Bearer {}", access_token.as_str()))
+                    .header("Content-Type", content_type)
+                    .body_bytes(body),
+                "上传皮肤",
+            )
+            .await?;
+        }
+        _ => anyhow::bail!("该账户类型不支持修改皮肤"),
+    }
+    let (new_head_skin, new_hat_skin) = crate::auth::parse_head_skin(png.to_vec())?;
+    match method {
+        AuthMethod::Microsoft {
+            head_skin,
+            hat_skin,
+            ..
+        }
+        | AuthMethod::AuthlibInjector {
+            head_skin,
+            hat_skin,
+            ..
+        } => {
+            *head_skin = new_head_skin;
+            *hat_skin = new_hat_skin;
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// 使用一个公开的皮肤链接设置微软账户的当前皮肤
+pub async fn set_skin_by_url(
+    access_token: &str,
+    variant: SkinVariant,
+    url: &str,
+) -> DynResult<MinecraftProfile> {
+    recv_profile(
+        crate::http::post(format!("{PROFILE_URL}/skins"))
+            .header("Authorization", format!("Bearer {access_token}"))
+            .body_json(&serde_json::json!({
+                "variant": variant.as_str(),
+                "url": url,
+            }))
+            .map_err(|e| anyhow::anyhow!(e))?,
+        "设置皮肤",
+    )
+    .await
+}
+
+/// 将皮肤重置为默认皮肤，支持微软账户和外置登录账户
+///
+/// 账户的头像不会自动更新，如有需要请重新获取
+pub async fn reset_skin(method: &AuthMethod) -> DynResult {
+    match method {
+        AuthMethod::Microsoft { access_token, .. } => {
+            recv_profile(
+                crate::http::delete(format!("{PROFILE_URL}/skins/active"))
+                    .header("Authorization", format!("Bearer {}", access_token.as_str())),
+                "重置皮肤",
+            )
+            .await?;
+            Ok(())
+        }
+        AuthMethod::AuthlibInjector {
+            api_location,
+            access_token,
+            uuid,
+            ..
+        } => {
+            send_yggdrasil(
+                crate::http::delete(yggdrasil_texture_url(api_location, uuid, TextureType::Skin))
+                    .header("Authorization", format!("Bearer {}", access_token.as_str())),
+                "重置皮肤",
+            )
+            .await
+        }
+        _ => anyhow::bail!("该账户类型不支持修改皮肤"),
+    }
+}
+
+/// 展示微软账户拥有的一个披风，披风 ID 可以通过 [`get_profile`] 获取
+pub async fn show_cape(access_token: &str, cape_id: &str) -> DynResult<MinecraftProfile> {
+    recv_profile(
+        crate::http::put(format!("{PROFILE_URL}/capes/active"))
+            .header("Authorization", format!("Bearer {access_token}"))
+            .body_json(&serde_json::json!({ "capeId": cape_id }))
+            .map_err(|e| anyhow::anyhow!(e))?,
+        "展示披风",
+    )
+    .await
+}
+
+/// 隐藏披风，支持微软账户和外置登录账户
+///
+/// 外置登录账户的披风会被直接删除
+pub async fn hide_cape(method: &AuthMethod) -> DynResult {
+    match method {
+        AuthMethod::Microsoft { access_token, .. } => {
+            recv_profile(
+                crate::http::delete(format!("{PROFILE_URL}/capes/active"))
+                    .header("Authorization", format!("Bearer {}", access_token.as_str())),
+                "隐藏披风",
+            )
+            .await?;
+            Ok(())
+        }
+        AuthMethod::AuthlibInjector {
+            api_location,
+            access_token,
+            uuid,
+            ..
+        } => {
+            send_yggdrasil(
+                crate::http::delete(yggdrasil_texture_url(api_location, uuid, TextureType::Cape))
+                    .header("Authorization", format!("Bearer {}", access_token.as_str())),
+                "删除披风",
+            )
+            .await
+        }
+        _ => anyhow::bail!("该账户类型不支持修改披风"),
+    }
+}
+
+/// 为外置登录账户上传一个披风，微软账户无法上传披风
+pub async fn upload_cape(method: &AuthMethod, png: &[u8]) -> DynResult {
+    if let AuthMethod::AuthlibInjector {
+        api_location,
+        access_token,
+        uuid,
+        ..
+    } = method
+    {
+        image::load_from_memory_with_format(png, image::ImageFormat::Png)
+            .map_err(|_| anyhow::anyhow!("披风文件不是有效的 PNG 图片"))?;
+        let (content_type, body) = multipart_body(&[], png);
+        send_yggdrasil(
+            crate::http::put(yggdrasil_texture_url(api_location, uuid, TextureType::Cape))
+                .header("Authorization", format!("Bearer {}", access_token.as_str()))
+                .header("Content-Type", content_type)
+                .body_bytes(body),
+            "上传披风",
+        )
+        .await
+    } else {
+        anyhow::bail!("只有外置登录账户可以上传披风")
+    }
+}
+
+#[test]
+fn validate_skin_test() {
+    let encode = |img: &image::RgbaImage| {
+        let mut data = std::io::Cursor::new(vec![]);
+        img.write_to(&mut data, image::ImageFormat::Png).unwrap();
+        data.into_inner()
+    };
+    let opaque = image::RgbaImage::from_pixel(64, 64, image::Rgba([0x80, 0x60, 0x40, 0xFF]));
+    assert!(validate_skin(&encode(&opaque), SkinVariant::Classic)
+        .unwrap()
+        .is_empty());
+
+    let legacy = image::RgbaImage::from_pixel(64, 32, image::Rgba([0x80, 0x60, 0x40, 0xFF]));
+    assert!(validate_skin(&encode(&legacy), SkinVariant::Classic)
+        .unwrap()
+        .is_empty());
+
+    let wrong_size = image::RgbaImage::from_pixel(64, 48, image::Rgba([0x80, 0x60, 0x40, 0xFF]));
+    assert!(validate_skin(&encode(&wrong_size), SkinVariant::Classic).is_err());
+    assert!(validate_skin(b"not a png", SkinVariant::Classic).is_err());
+
+    // 外层允许透明
+    let mut hat = opaque.to_owned();
+    hat.put_pixel(40, 8, image::Rgba([0, 0, 0, 0]));
+    assert!(validate_skin(&encode(&hat), SkinVariant::Classic)
+        .unwrap()
+        .is_empty());
+
+    let mut face = opaque.to_owned();
+    face.put_pixel(8, 8, image::Rgba([0, 0, 0, 0]));
+    // 基础层的透明像素只作为警告，不影响上传
+    let warnings = validate_skin(&encode(&face), SkinVariant::Classic).unwrap();
+    assert_eq!(warnings.len(), 1);
+    assert!(warnings[0].contains("(8, 8)"));
+
+    // 纤细模型的手臂比经典模型窄一列，展开后贴图右侧的两列不会被使用
+    let mut slim = opaque;
+    for x in 54..56 {
+        for y in 16..32 {
+            slim.put_pixel(x, y, image::Rgba([0, 0, 0, 0]));
+        }
+    }
+    for x in 46..48 {
+        for y in 48..64 {
+            slim.put_pixel(x, y, image::Rgba([0, 0, 0, 0]));
+        }
+    }
+    assert!(validate_skin(&encode(&slim), SkinVariant::Slim)
+        .unwrap()
+        .is_empty());
+    assert_eq!(
+        validate_skin(&encode(&slim), SkinVariant::Classic)
+            .unwrap()
+            .len(),
+        2
+    );
+}
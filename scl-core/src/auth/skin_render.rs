@@ -0,0 +1,429 @@
+//! 皮肤预览渲染模块，使用 CPU 将皮肤贴图渲染为正面、背面或等轴测视角的预览图
+//!
+//! 渲染时会将皮肤的每个部件视为长方体，把贴图上对应的像素投影到画面中，
+//! 并使用深度缓冲处理遮挡关系，外层（帽子、外套等）会比基础层稍大一圈并叠加在基础层之上。
+//!
+//! 支持 64x64（经典和纤细模型）、旧版 64x32 以及它们的等比例高清皮肤。
+
+use image::{Rgba, RgbaImage};
+
+use super::skin::{Rect, SkinBox, SkinPart, SkinVariant};
+use crate::prelude::*;
+
+/// 预览图的视角
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SkinView {
+    /// 正面平视图，大小为 16x32 乘以缩放倍数
+    Front,
+    /// 背面平视图，大小为 16x32 乘以缩放倍数
+    Back,
+    /// 等轴测视角的半身像，只包含头部、身体和手臂
+    IsometricBust,
+    /// 等轴测视角的全身像
+    IsometricFullBody,
+}
+
+/// 将旧版 64x32 皮肤转换为 64x64 皮肤
+///
+/// 和游戏中的处理方式一致，左手和左腿由右手和右腿镜像得到；
+/// 如果帽子部分完全不透明，则视为没有帽子。
+pub fn convert_legacy_skin(skin: &RgbaImage) -> RgbaImage {
+    let k = (skin.width() / 64).max(1);
+    let mut result = RgbaImage::new(skin.width(), skin.width());
+    image::imageops::replace(&mut result, skin, 0, 0);
+
+    let hat = (32 * k)..(64 * k);
+    let hat_rows = 0..(16 * k);
+    let hat_opaque = hat_rows
+        .clone()
+        .all(|y| hat.clone().all(|x| result.get_pixel(x, y).0[3] >= 128));
+    if hat_opaque {
+        for y in hat_rows {
+            for x in hat.clone() {
+                result.get_pixel_mut(x, y).0[3] = 0;
+            }
+        }
+    }
+
+    for (src, dst) in [
+        (SkinPart::RightLeg, SkinPart::LeftLeg),
+        (SkinPart::RightArm, SkinPart::LeftArm),
+    ] {
+        let src = src.uv_box(SkinVariant::Classic, false);
+        let dst = dst.uv_box(SkinVariant::Classic, false);
+        for (dst_rect, src_rect) in [
+            (dst.top(), src.top()),
+            (dst.bottom(), src.bottom()),
+            (dst.front(), src.front()),
+            (dst.back(), src.back()),
+            (dst.right(), src.left()),
+            (dst.left(), src.right()),
+        ] {
+            let (dx, dy, w, h) = dst_rect;
+            let (sx, sy, _, _) = src_rect;
+            for y in 0..h * k {
+                for x in 0..w * k {
+                    let pixel = *result.get_pixel(sx * k + (w * k - 1 - x), sy * k + y);
+                    result.put_pixel(dx * k + x, dy * k + y, pixel);
+                }
+            }
+        }
+    }
+    result
+}
+
+/// 渲染皮肤预览图，`scale` 为一个模型像素在预览图中的边长
+///
+/// 旧版 64x32 皮肤会先通过 [`convert_legacy_skin`] 转换
+pub fn render_skin(
+    skin: &RgbaImage,
+    variant: SkinVariant,
+    view: SkinView,
+    scale: u32,
+) -> DynResult<RgbaImage> {
+    anyhow::ensure!(scale > 0, "缩放倍数必须大于 0");
+    let (width, height) = skin.dimensions();
+    anyhow::ensure!(
+        width >= 64 && width % 64 == 0 && (height == width || height * 2 == width),
+        "不支持的皮肤大小 {}x{}",
+        width,
+        height
+    );
+    let converted;
+    let skin = if height * 2 == width {
+        converted = convert_legacy_skin(skin);
+        &converted
+    } else {
+        skin
+    };
+
+    let parts: &[SkinPart] = match view {
+        SkinView::IsometricBust => &[
+            SkinPart::Head,
+            SkinPart::Body,
+            SkinPart::RightArm,
+            SkinPart::LeftArm,
+        ],
+        _ => &SkinPart::ALL,
+    };
+    let (projection, inflate) = match view {
+        SkinView::Front => (Projection::FRONT, false),
+        SkinView::Back => (Projection::BACK, false),
+        SkinView::IsometricBust | SkinView::IsometricFullBody => (Projection::ISOMETRIC, true),
+    };
+
+    let mut faces = Vec::with_capacity(parts.len() * 10);
+    for overlay in [false, true] {
+        for part in parts {
+            let (mut min, mut max) = part_bounds(*part, variant);
+            if overlay && inflate {
+                let inflation = if *part == SkinPart::Head { 0.5 } else { 0.25 };
+                min = min.map(|x| x - inflation);
+                max = max.map(|x| x + inflation);
+            }
+            let uv = part.uv_box(variant, overlay);
+            faces.extend(
+                cuboid_faces(uv, min, max)
+                    .into_iter()
+                    .filter(|face| dot(face.normal, projection.view) > 0.)
+                    .map(|face| (face, overlay)),
+            );
+        }
+    }
+
+    let bounds = match view {
+        SkinView::Front | SkinView::Back => (-8., -32., 8., 0.),
+        _ => {
+            let mut bounds = (f32::MAX, f32::MAX, f32::MIN, f32::MIN);
+            for (face, _) in &faces {
+                let (w, h) = (face.rect.2 as f32, face.rect.3 as f32);
+                for (a, b) in [(0., 0.), (w, 0.), (0., h), (w, h)] {
+                    let (x, y) = projection.project(add(
+                        face.origin,
+                        add(scale_vec(face.u, a), scale_vec(face.v, b)),
+                    ));
+                    bounds.0 = bounds.0.min(x);
+                    bounds.1 = bounds.1.min(y);
+                    bounds.2 = bounds.2.max(x);
+                    bounds.3 = bounds.3.max(y);
+                }
+            }
+            bounds
+        }
+    };
+
+    Ok(rasterize(skin, &faces, &projection, bounds, scale))
+}
+
+/// 长方体的一个面，`u` `v` 为贴图上每个像素对应的模型空间向量
+struct Face {
+    rect: Rect,
+    origin: [f32; 3],
+    u: [f32; 3],
+    v: [f32; 3],
+    normal: [f32; 3],
+}
+
+/// 部件在模型空间中的范围，模型面朝 +Z，+X 为模型的左侧，+Y 向上，脚底位于 Y = 0
+fn part_bounds(part: SkinPart, variant: SkinVariant) -> ([f32; 3], [f32; 3]) {
+    let arm = variant.arm_width() as f32;
+    match part {
+        SkinPart::Head => ([-4., 24., -4.], [4., 32., 4.]),
+        SkinPart::Body => ([-4., 12., -2.], [4., 24., 2.]),
+        SkinPart::RightArm => ([-4. - arm, 12., -2.], [-4., 24., 2.]),
+        SkinPart::LeftArm => ([4., 12., -2.], [4. + arm, 24., 2.]),
+        SkinPart::RightLeg => ([-4., 0., -2.], [0., 12., 2.]),
+        SkinPart::LeftLeg => ([0., 0., -2.], [4., 12., 2.]),
+    }
+}
+
+/// 生成长方体除底面以外的五个面，底面在所有视角中都不可见
+fn cuboid_faces(uv: SkinBox, min: [f32; 3], max: [f32; 3]) -> [Face; 5] {
+    let sx = (max[0] - min[0]) / uv.w as f32;
+    let sy = (max[1] - min[1]) / uv.h as f32;
+    let sz = (max[2] - min[2]) / uv.d as f32;
+    [
+        Face {
+            rect: uv.front(),
+            origin: [min[0], max[1], max[2]],
+            u: [sx, 0., 0.],
+            v: [0., -sy, 0.],
+            normal: [0., 0., 1.],
+        },
+        Face {
+            rect: uv.back(),
+            origin: [max[0], max[1], min[2]],
+            u: [-sx, 0., 0.],
+            v: [0., -sy, 0.],
+            normal: [0., 0., -1.],
+        },
+        Face {
+            rect: uv.right(),
+            origin: [min[0], max[1], min[2]],
+            u: [0., 0., sz],
+            v: [0., -sy, 0.],
+            normal: [-1., 0., 0.],
+        },
+        Face {
+            rect: uv.left(),
+            origin: [max[0], max[1], max[2]],
+            u: [0., 0., -sz],
+            v: [0., -sy, 0.],
+            normal: [1., 0., 0.],
+        },
+        Face {
+            rect: uv.top(),
+            origin: [min[0], max[1], min[2]],
+            u: [sx, 0., 0.],
+            v: [0., 0., sz],
+            normal: [0., 1., 0.],
+        },
+    ]
+}
+
+/// 正交投影，`view` 为指向观察者的方向，用于剔除背面和计算深度
+struct Projection {
+    x: [f32; 3],
+    y: [f32; 3],
+    view: [f32; 3],
+}
+
+const COS_30: f32 = 0.866_025_4;
+
+impl Projection {
+    const FRONT: Self = Self {
+        x: [1., 0., 0.],
+        y: [0., -1., 0.],
+        view: [0., 0., 1.],
+    };
+    const BACK: Self = Self {
+        x: [-1., 0., 0.],
+        y: [0., -1., 0.],
+        view: [0., 0., -1.],
+    };
+    /// 从模型的右前上方观察，可以看到正面、右侧面和顶面
+    const ISOMETRIC: Self = Self {
+        x: [COS_30, 0., COS_30],
+        y: [-0.5, -1., 0.5],
+        view: [-1., 1., 1.],
+    };
+
+    fn project(&self, p: [f32; 3]) -> (f32, f32) {
+        (dot(p, self.x), dot(p, self.y))
+    }
+}
+
+fn dot(a: [f32; 3], b: [f32; 3]) -> f32 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn add(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [a[0] + b[0], a[1] + b[1], a[2] + b[2]]
+}
+
+fn scale_vec(a: [f32; 3], s: f32) -> [f32; 3] {
+    [a[0] * s, a[1] * s, a[2] * s]
+}
+
+fn blend(dst: Rgba<u8>, src: Rgba<u8>) -> Rgba<u8> {
+    let sa = src.0[3] as f32 / 255.;
+    let da = dst.0[3] as f32 / 255.;
+    let out_a = sa + da * (1. - sa);
+    if out_a <= 0. {
+        return Rgba([0, 0, 0, 0]);
+    }
+    let channel = |i: usize| {
+        ((src.0[i] as f32 * sa + dst.0[i] as f32 * da * (1. - sa)) / out_a).round() as u8
+    };
+    Rgba([
+        channel(0),
+        channel(1),
+        channel(2),
+        (out_a * 255.).round() as u8,
+    ])
+}
+
+fn rasterize(
+    skin: &RgbaImage,
+    faces: &[(Face, bool)],
+    projection: &Projection,
+    (min_x, min_y, max_x, max_y): (f32, f32, f32, f32),
+    scale: u32,
+) -> RgbaImage {
+    let k = skin.width() / 64;
+    let scale_f = scale as f32;
+    let width = ((max_x - min_x) * scale_f).ceil() as u32;
+    let height = ((max_y - min_y) * scale_f).ceil() as u32;
+    let mut result = RgbaImage::new(width, height);
+    let mut depth = vec![f32::NEG_INFINITY; (width * height) as usize];
+
+    for (face, overlay) in faces {
+        let (o_x, o_y) = projection.project(face.origin);
+        let u = projection.project(face.u);
+        let v = projection.project(face.v);
+        let det = u.0 * v.1 - u.1 * v.0;
+        if det.abs() < 1e-6 {
+            continue;
+        }
+        let (rect_x, rect_y, rect_w, rect_h) = face.rect;
+        let (w, h) = (rect_w as f32, rect_h as f32);
+
+        let corners = [(0., 0.), (w, 0.), (0., h), (w, h)].map(|(a, b)| {
+            (
+                o_x + u.0 * a + v.0 * b - min_x,
+                o_y + u.1 * a + v.1 * b - min_y,
+            )
+        });
+        let px_start = corners.iter().map(|c| c.0).fold(f32::MAX, f32::min) * scale_f;
+        let px_end = corners.iter().map(|c| c.0).fold(f32::MIN, f32::max) * scale_f;
+        let py_start = corners.iter().map(|c| c.1).fold(f32::MAX, f32::min) * scale_f;
+        let py_end = corners.iter().map(|c| c.1).fold(f32::MIN, f32::max) * scale_f;
+
+        for py in (py_start.floor().max(0.) as u32)..(py_end.ceil() as u32).min(height) {
+            for px in (px_start.floor().max(0.) as u32)..(px_end.ceil() as u32).min(width) {
+                let p_x = (px as f32 + 0.5) / scale_f + min_x - o_x;
+                let p_y = (py as f32 + 0.5) / scale_f + min_y - o_y;
+                let a = (p_x * v.1 - p_y * v.0) / det;
+                let b = (u.0 * p_y - u.1 * p_x) / det;
+                if a < 0. || b < 0. || a >= w || b >= h {
+                    continue;
+                }
+                let texel_x = (rect_x * k + (a * k as f32) as u32).min((rect_x + rect_w) * k - 1);
+                let texel_y = (rect_y * k + (b * k as f32) as u32).min((rect_y + rect_h) * k - 1);
+                let mut color = *skin.get_pixel(texel_x, texel_y);
+                let z = dot(
+                    add(face.origin, add(scale_vec(face.u, a), scale_vec(face.v, b))),
+                    projection.view,
+                );
+                let index = (py * width + px) as usize;
+                if *overlay {
+                    if color.0[3] == 0 || z < depth[index] - 1e-3 {
+                        continue;
+                    }
+                    let dst = *result.get_pixel(px, py);
+                    result.put_pixel(px, py, blend(dst, color));
+                    if color.0[3] == 0xFF {
+                        depth[index] = z;
+                    }
+                } else {
+                    // 游戏中基础层的透明度会被忽略
+                    if z < depth[index] {
+                        continue;
+                    }
+                    color.0[3] = 0xFF;
+                    result.put_pixel(px, py, color);
+                    depth[index] = z;
+                }
+            }
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+fn face_test_skin(variant: SkinVariant) -> RgbaImage {
+    let mut skin = RgbaImage::new(64, 64);
+    for (i, part) in SkinPart::ALL.iter().enumerate() {
+        let uv = part.uv_box(variant, false);
+        for (j, (x, y, w, h)) in uv.faces().into_iter().enumerate() {
+            for py in y..y + h {
+                for px in x..x + w {
+                    skin.put_pixel(px, py, Rgba([i as u8 * 40, j as u8 * 40, 0x80, 0xFF]));
+                }
+            }
+        }
+    }
+    skin
+}
+
+#[test]
+fn render_skin_test() {
+    // 部件颜色的红色分量为部件序号 * 40，绿色分量为面的序号 * 40（上、下、右、前、左、后）
+    let color = |part: u8, face: u8| Rgba([part * 40, face * 40, 0x80, 0xFF]);
+    let mut skin = face_test_skin(SkinVariant::Classic);
+    // 帽子正面的左上角
+    skin.put_pixel(40, 8, Rgba([0xFF, 0, 0, 0xFF]));
+
+    let front = render_skin(&skin, SkinVariant::Classic, SkinView::Front, 2).unwrap();
+    assert_eq!(front.dimensions(), (32, 64));
+    assert_eq!(*front.get_pixel(8, 0), Rgba([0xFF, 0, 0, 0xFF]));
+    assert_eq!(*front.get_pixel(12, 4), color(0, 3));
+    assert_eq!(*front.get_pixel(16, 30), color(1, 3));
+    assert_eq!(*front.get_pixel(2, 20), color(2, 3));
+    assert_eq!(*front.get_pixel(28, 20), color(3, 3));
+    assert_eq!(*front.get_pixel(10, 50), color(4, 3));
+    assert_eq!(*front.get_pixel(20, 50), color(5, 3));
+
+    let back = render_skin(&skin, SkinVariant::Classic, SkinView::Back, 1).unwrap();
+    assert_eq!(*back.get_pixel(8, 4), color(0, 5));
+    assert_eq!(*back.get_pixel(14, 12), color(2, 5));
+    assert_eq!(*back.get_pixel(1, 12), color(3, 5));
+
+    let slim_skin = face_test_skin(SkinVariant::Slim);
+    let slim = render_skin(&slim_skin, SkinVariant::Slim, SkinView::Front, 1).unwrap();
+    assert_eq!(slim.get_pixel(0, 12).0[3], 0);
+    assert_eq!(*slim.get_pixel(1, 12), color(2, 3));
+
+    let iso = render_skin(&skin, SkinVariant::Classic, SkinView::IsometricFullBody, 4).unwrap();
+    let contains = |c: Rgba<u8>| iso.pixels().any(|x| *x == c);
+    assert!(contains(color(0, 0)));
+    assert!(contains(color(0, 2)));
+    assert!(contains(color(0, 3)));
+    assert!(contains(color(4, 3)));
+    assert!(!contains(color(0, 4)));
+    assert!(!contains(color(0, 5)));
+    let bust = render_skin(&skin, SkinVariant::Classic, SkinView::IsometricBust, 4).unwrap();
+    assert!(bust.height() < iso.height());
+    assert!(!bust.pixels().any(|x| *x == color(4, 3)));
+
+    // 旧版皮肤的左腿正面由右腿正面镜像得到
+    let mut legacy = RgbaImage::new(64, 32);
+    image::imageops::replace(&mut legacy, &skin, 0, 0);
+    legacy.put_pixel(4, 20, Rgba([0, 0xFF, 0, 0xFF]));
+    let converted = convert_legacy_skin(&legacy);
+    assert_eq!(converted.dimensions(), (64, 64));
+    assert_eq!(*converted.get_pixel(23, 52), Rgba([0, 0xFF, 0, 0xFF]));
+    assert_eq!(*converted.get_pixel(20, 52), color(4, 3));
+    let legacy_front = render_skin(&legacy, SkinVariant::Classic, SkinView::Front, 1).unwrap();
+    assert_eq!(*legacy_front.get_pixel(11, 20), Rgba([0, 0xFF, 0, 0xFF]));
+}
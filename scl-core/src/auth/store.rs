@@ -0,0 +1,455 @@
+//! 账户存储模块，用于保存和读取多个账户，并记录默认选中的账户
+//!
+//! 账户中的访问令牌和刷新令牌会使用 AES-256-GCM 加密后再保存，
+//! 密钥可以由用户提供的密码通过 PBKDF2 派生，也可以在启用 `keyring` 特性后随机生成并保存在系统密钥链中。
+//!
+//! 读取时会自动迁移旧版本的存储格式（直接保存的 [`AuthMethod`] 数组，令牌为明文），
+//! 迁移后的数据会在下一次调用 [`AccountStore::save`] 时以加密形式写回。
+
+use std::{
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use aes_gcm::{
+    aead::{Aead, KeyInit},
+    Aes256Gcm, Nonce,
+};
+use anyhow::Context;
+use base64::prelude::*;
+use serde_json::Value;
+use sha2::Sha256;
+
+use super::structs::AuthMethod;
+use crate::{password::Password, prelude::*};
+
+/// 当前的存储格式版本
+const STORE_VERSION: u32 = 1;
+/// 使用密码派生密钥时的 PBKDF2 迭代次数
+const PBKDF2_ROUNDS: u32 = 100_000;
+/// 加密后的字段值的前缀
+const ENCRYPTED_PREFIX: &str = "enc:";
+/// 需要加密保存的字段，即 [`AuthMethod`] 中所有的 [`Password`] 字段
+const SECRET_FIELDS: &[&str] = &["access_token", "refresh_token"];
+
+/// 账户数据的存储后端，只负责原样读写数据
+pub trait AccountStorage {
+    /// 读取保存的数据，如果还没有保存过则返回 `None`
+    fn read(&self) -> DynResult<Option<Vec<u8>>>;
+    /// 覆盖写入数据
+    fn write(&self, data: &[u8]) -> DynResult;
+}
+
+/// 将账户数据保存在文件中的存储后端
+///
+/// 写入时会先写入同目录下的临时文件再进行替换，避免写入中途出错导致账户数据丢失
+#[derive(Debug, Clone)]
+pub struct FileStorage {
+    path: PathBuf,
+}
+
+impl FileStorage {
+    /// 使用指定的文件路径创建存储后端，文件不存在时视为没有保存过数据
+    pub fn new(path: impl AsRef<Path>) -> Self {
+        Self {
+            path: path.as_ref().to_path_buf(),
+        }
+    }
+
+    /// 获取保存数据的文件路径
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl AccountStorage for FileStorage {
+    fn read(&self) -> DynResult<Option<Vec<u8>>> {
+        match std::fs::read(&self.path) {
+            Ok(data) => Ok(Some(data)),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    fn write(&self, data: &[u8]) -> DynResult {
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let mut tmp_path = self.path.clone().into_os_string();
+        tmp_path.push(".tmp");
+        std::fs::write(&tmp_path, data)?;
+        std::fs::rename(&tmp_path, &self.path)?;
+        Ok(())
+    }
+}
+
+/// 将账户数据保存在内存中的存储后端，主要用于测试
+#[derive(Debug, Default)]
+pub struct MemoryStorage {
+    data: Mutex<Option<Vec<u8>>>,
+}
+
+impl MemoryStorage {
+    /// 创建一个空的存储后端
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 使用已有的数据创建存储后端
+    pub fn with_data(data: impl Into<Vec<u8>>) -> Self {
+        Self {
+            data: Mutex::new(Some(data.into())),
+        }
+    }
+
+    /// 复制出当前保存的数据
+    pub fn data(&self) -> Option<Vec<u8>> {
+        self.data.lock().unwrap().to_owned()
+    }
+}
+
+impl AccountStorage for MemoryStorage {
+    fn read(&self) -> DynResult<Option<Vec<u8>>> {
+        Ok(self.data())
+    }
+
+    fn write(&self, data: &[u8]) -> DynResult {
+        *self.data.lock().unwrap() = Some(data.to_vec());
+        Ok(())
+    }
+}
+
+/// 加密账户令牌所用密钥的来源
+#[derive(Debug, Clone)]
+pub enum StoreKey {
+    /// 由用户提供的密码派生密钥
+    Passphrase(Password),
+    /// 将随机生成的密钥保存在系统密钥链中
+    #[cfg(feature = "keyring")]
+    Keyring {
+        /// 密钥链条目的服务名称，通常为启动器名称
+        service: String,
+        /// 密钥链条目的用户名称
+        user: String,
+    },
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct StoreFile {
+    version: u32,
+    #[serde(default)]
+    selected: Option<usize>,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "String::is_empty")]
+    salt: String,
+    #[serde(default)]
+    accounts: Vec<Value>,
+}
+
+/// 多账户存储，保存账户列表和默认选中的账户
+pub struct AccountStore<S> {
+    storage: S,
+    cipher: Aes256Gcm,
+    salt: Vec<u8>,
+    accounts: Vec<AuthMethod>,
+    selected: Option<usize>,
+}
+
+impl<S: AccountStorage> AccountStore<S> {
+    /// 从存储后端中读取账户列表，如果还没有保存过则得到一个空的账户列表
+    ///
+    /// 如果密钥与保存时所用的不一致，则会返回错误
+    pub fn open(storage: S, key: StoreKey) -> DynResult<Self> {
+        let file = match storage.read()? {
+            Some(data) => Some(parse_store_file(&data)?),
+            None => None,
+        };
+        let salt = match &file {
+            Some(file) if !file.salt.is_empty() => BASE64_STANDARD
+                .decode(&file.salt)
+                .context("无法解析账户存储的密钥盐值")?,
+            _ => rand::random::<[u8; 16]>().to_vec(),
+        };
+        let cipher = Aes256Gcm::new(&derive_key(&key, &salt)?.into());
+        let (accounts, selected) = if let Some(file) = file {
+            let accounts = file
+                .accounts
+                .into_iter()
+                .map(|mut account| {
+                    transform_secrets(&mut account, |value| decrypt(&cipher, value))?;
+                    Ok(serde_json::from_value(account)?)
+                })
+                .collect::<DynResult<Vec<AuthMethod>>>()?;
+            let selected = file.selected.filter(|x| *x < accounts.len());
+            (accounts, selected)
+        } else {
+            (vec![], None)
+        };
+        Ok(Self {
+            storage,
+            cipher,
+            salt,
+            accounts,
+            selected,
+        })
+    }
+
+    /// 将账户列表加密后写入存储后端
+    pub fn save(&self) -> DynResult {
+        let accounts = self
+            .accounts
+            .iter()
+            .map(|account| {
+                let mut account = serde_json::to_value(account)?;
+                transform_secrets(&mut account, |value| encrypt(&self.cipher, value))?;
+                Ok(account)
+            })
+            .collect::<DynResult<Vec<_>>>()?;
+        let file = StoreFile {
+            version: STORE_VERSION,
+            selected: self.selected,
+            salt: BASE64_STANDARD.encode(&self.salt),
+            accounts,
+        };
+        self.storage.write(&serde_json::to_vec_pretty(&file)?)
+    }
+
+    /// 获取所有账户
+    pub fn accounts(&self) -> &[AuthMethod] {
+        &self.accounts
+    }
+
+    /// 获取指定位置的账户的可变引用，可用于刷新令牌后更新账户
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut AuthMethod> {
+        self.accounts.get_mut(index)
+    }
+
+    /// 添加一个账户并返回其位置
+    ///
+    /// 如果已经存在同一个账户（相同的账户类型和 UUID，外置登录还需要相同的验证服务器），则会替换掉旧的账户
+    pub fn add(&mut self, account: AuthMethod) -> usize {
+        if let Some(index) = self
+            .accounts
+            .iter()
+            .position(|x| is_same_account(x, &account))
+        {
+            self.accounts[index] = account;
+            index
+        } else {
+            self.accounts.push(account);
+            self.accounts.len() - 1
+        }
+    }
+
+    /// 移除指定位置的账户，如果移除的是默认账户，则会取消默认选中
+    pub fn remove(&mut self, index: usize) -> Option<AuthMethod> {
+        if index >= self.accounts.len() {
+            return None;
+        }
+        self.selected = match self.selected {
+            Some(selected) if selected == index => None,
+            Some(selected) if selected > index => Some(selected - 1),
+            selected => selected,
+        };
+        Some(self.accounts.remove(index))
+    }
+
+    /// 将指定位置的账户设置为默认账户
+    pub fn select(&mut self, index: usize) -> DynResult {
+        anyhow::ensure!(index < self.accounts.len(), "账户 {} 不存在", index);
+        self.selected = Some(index);
+        Ok(())
+    }
+
+    /// 获取默认账户的位置
+    pub fn selected_index(&self) -> Option<usize> {
+        self.selected
+    }
+
+    /// 获取默认账户
+    pub fn selected(&self) -> Option<&AuthMethod> {
+        self.selected.and_then(|x| self.accounts.get(x))
+    }
+
+    /// 获取存储后端
+    pub fn storage(&self) -> &S {
+        &self.storage
+    }
+}
+
+fn parse_store_file(data: &[u8]) -> DynResult<StoreFile> {
+    let value: Value = serde_json::from_slice(data).context("无法解析账户存储数据")?;
+    match value {
+        // 旧版本直接保存了 AuthMethod 数组，令牌均为明文
+        Value::Array(accounts) => Ok(StoreFile {
+            version: 0,
+            selected: None,
+            salt: String::new(),
+            accounts,
+        }),
+        value => {
+            let file: StoreFile = serde_json::from_value(value).context("无法解析账户存储数据")?;
+            anyhow::ensure!(
+                file.version <= STORE_VERSION,
+                "账户存储的版本 {} 过新，请更新启动器",
+                file.version
+            );
+            Ok(file)
+        }
+    }
+}
+
+fn derive_key(key: &StoreKey, salt: &[u8]) -> DynResult<[u8; 32]> {
+    match key {
+        StoreKey::Passphrase(passphrase) => {
+            let mut result = [0; 32];
+            pbkdf2::pbkdf2_hmac::<Sha256>(passphrase.as_bytes(), salt, PBKDF2_ROUNDS, &mut result);
+            Ok(result)
+        }
+        #[cfg(feature = "keyring")]
+        StoreKey::Keyring { service, user } => {
+            let entry = keyring::Entry::new(service, user)?;
+            match entry.get_password() {
+                Ok(saved) => {
+                    let saved = BASE64_STANDARD
+                        .decode(saved)
+                        .context("系统密钥链中保存的密钥已损坏")?;
+                    saved
+                        .try_into()
+                        .map_err(|_| anyhow::anyhow!("系统密钥链中保存的密钥长度不正确"))
+                }
+                Err(keyring::Error::NoEntry) => {
+                    let result = rand::random::<[u8; 32]>();
+                    entry.set_password(&BASE64_STANDARD.encode(result))?;
+                    Ok(result)
+                }
+                Err(err) => Err(err.into()),
+            }
+        }
+    }
+}
+
+/// 对账户中所有的令牌字段执行转换，账户的格式为 `{ "账户类型": { 字段... } }`
+fn transform_secrets(account: &mut Value, f: impl Fn(&str) -> DynResult<String>) -> DynResult {
+    if let Some(account) = account.as_object_mut() {
+        for fields in account.values_mut().filter_map(Value::as_object_mut) {
+            for field in SECRET_FIELDS {
+                if let Some(Value::String(value)) = fields.get_mut(*field) {
+                    *value = f(value)?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+fn encrypt(cipher: &Aes256Gcm, value: &str) -> DynResult<String> {
+    let nonce = rand::random::<[u8; 12]>();
+    let mut data = cipher
+        .encrypt(Nonce::from_slice(&nonce), value.as_bytes())
+        .map_err(|_| anyhow::anyhow!("加密账户令牌失败"))?;
+    data.splice(0..0, nonce);
+    Ok(format!(
+        "{ENCRYPTED_PREFIX}{}",
+        BASE64_STANDARD.encode(data)
+    ))
+}
+
+fn decrypt(cipher: &Aes256Gcm, value: &str) -> DynResult<String> {
+    // 没有前缀的值是旧版本保存的明文令牌，原样读取
+    let Some(value) = value.strip_prefix(ENCRYPTED_PREFIX) else {
+        return Ok(value.to_owned());
+    };
+    let data = BASE64_STANDARD
+        .decode(value)
+        .context("账户令牌数据已损坏")?;
+    anyhow::ensure!(data.len() > 12, "账户令牌数据已损坏");
+    let (nonce, data) = data.split_at(12);
+    let data = cipher
+        .decrypt(Nonce::from_slice(nonce), data)
+        .map_err(|_| anyhow::anyhow!("解密账户令牌失败，密钥不正确或数据已损坏"))?;
+    Ok(String::from_utf8(data)?)
+}
+
+fn is_same_account(a: &AuthMethod, b: &AuthMethod) -> bool {
+    match (a, b) {
+        (
+            AuthMethod::Offline {
+                player_name: a_name,
+                uuid: a_uuid,
+            },
+            AuthMethod::Offline {
+                player_name: b_name,
+                uuid: b_uuid,
+            },
+        ) => a_name == b_name && a_uuid == b_uuid,
+        (AuthMethod::Mojang { uuid: a, .. }, AuthMethod::Mojang { uuid: b, .. }) => a == b,
+        (AuthMethod::Microsoft { uuid: a, .. }, AuthMethod::Microsoft { uuid: b, .. }) => {
+            !a.is_empty() && a == b
+        }
+        (
+            AuthMethod::AuthlibInjector {
+                api_location: a_api,
+                uuid: a_uuid,
+                ..
+            },
+            AuthMethod::AuthlibInjector {
+                api_location: b_api,
+                uuid: b_uuid,
+                ..
+            },
+        ) => a_api == b_api && a_uuid == b_uuid,
+        _ => false,
+    }
+}
+
+#[test]
+fn account_store_test() {
+    let microsoft = AuthMethod::Microsoft {
+        access_token: "mc-access-token".into(),
+        refresh_token: "ms-refresh-token".into(),
+        uuid: "uuid".into(),
+        xuid: "xuid".into(),
+        player_name: "Steve".into(),
+        head_skin: vec![],
+        hat_skin: vec![],
+        ownership: Default::default(),
+        has_profile: true,
+        client_id: String::new(),
+        microsoft_expires_at: 0,
+        minecraft_expires_at: 0,
+    };
+    let offline = AuthMethod::Offline {
+        player_name: "Alex".into(),
+        uuid: "offline-uuid".into(),
+    };
+    let key = || StoreKey::Passphrase("passphrase".into());
+
+    // 迁移旧版本的明文数组
+    let legacy = serde_json::to_vec(&[&offline, &microsoft]).unwrap();
+    let mut store = AccountStore::open(MemoryStorage::with_data(legacy), key()).unwrap();
+    assert_eq!(store.accounts(), [offline.to_owned(), microsoft.to_owned()]);
+    assert_eq!(store.selected(), None);
+    store.select(1).unwrap();
+    assert_eq!(store.add(microsoft.to_owned()), 1);
+    store.save().unwrap();
+
+    let data = store.storage().data().unwrap();
+    let raw = String::from_utf8_lossy(&data);
+    assert!(!raw.contains("mc-access-token"));
+    assert!(!raw.contains("ms-refresh-token"));
+
+    let store = AccountStore::open(MemoryStorage::with_data(data.to_owned()), key()).unwrap();
+    assert_eq!(store.accounts(), [offline, microsoft.to_owned()]);
+    assert_eq!(store.selected(), Some(&microsoft));
+
+    let wrong_key = StoreKey::Passphrase("wrong".into());
+    assert!(AccountStore::open(MemoryStorage::with_data(data), wrong_key).is_err());
+
+    let mut store = AccountStore::open(MemoryStorage::new(), key()).unwrap();
+    assert!(store.accounts().is_empty());
+    store.add(microsoft);
+    store.select(0).unwrap();
+    assert!(store.remove(0).is_some());
+    assert_eq!(store.selected_index(), None);
+}
@@ -3,6 +3,22 @@ use serde::{Deserialize, Serialize};
 
 use crate::password::Password;
 
+/// 微软账户持有 Minecraft 的方式，通过查询账户的权益列表得出
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MinecraftOwnership {
+    /// 无法确定，例如旧版本保存的账户，或者权益列表为空但确实拥有游戏档案的账户
+    #[default]
+    Unknown,
+    /// 已购买 Minecraft
+    Purchased,
+    /// 通过 Xbox Game Pass 获得 Minecraft
+    GamePass,
+}
+
+fn default_has_profile() -> bool {
+    true
+}
+
 /**
    账户类型枚举，需要提供一个账户种类方可启动游戏
 */
@@ -44,6 +60,26 @@ pub enum AuthMethod {
         head_skin: Vec<u8>,
         /// 正版玩家的头发皮肤位图信息，格式为 RGBA，大小为 8x8，用于展示头像
         hat_skin: Vec<u8>,
+        /// 该账户持有 Minecraft 的方式
+        #[serde(default)]
+        ownership: MinecraftOwnership,
+        /// 该账户是否已经创建了游戏档案
+        ///
+        /// 如果为 `false`，则 `uuid` 和 `player_name` 均为空，且无法用于启动游戏，
+        /// 需要先通过 [`crate::auth::microsoft::create_profile`] 设置玩家名称
+        #[serde(default = "default_has_profile")]
+        has_profile: bool,
+        /// 登录时使用的 Azure 应用客户端 ID，刷新令牌时需要使用同一个客户端 ID
+        ///
+        /// 为空时代表账户是通过 [`crate::auth::microsoft::leagcy`] 的方式登录的
+        #[serde(default)]
+        client_id: String,
+        /// 微软访问令牌的过期时间，为 UNIX 时间戳（秒），为 `0` 时代表未知
+        #[serde(default)]
+        microsoft_expires_at: u64,
+        /// Minecraft 登录令牌（即 `access_token`）的过期时间，为 UNIX 时间戳（秒），为 `0` 时代表未知
+        #[serde(default)]
+        minecraft_expires_at: u64,
     },
     /// 外置登录（Authlib-Injector）
     AuthlibInjector {
@@ -68,6 +104,34 @@ pub enum AuthMethod {
     },
 }
 
+/// 提前刷新令牌的时间（秒），避免令牌在游戏过程中过期
+const REFRESH_AHEAD_SECS: u64 = 5 * 60;
+
+impl AuthMethod {
+    /// 是否需要在启动前刷新令牌
+    ///
+    /// 微软账户会根据记录的微软令牌和 Minecraft 令牌的过期时间判断，任意一个即将过期时都需要刷新，
+    /// Minecraft 令牌的过期时间未知时也视为需要刷新；
+    /// Mojang 和外置登录账户的令牌没有过期时间，只能交由验证服务器判断，故总是返回 `true`；
+    /// 离线账户总是返回 `false`。
+    pub fn needs_refresh(&self) -> bool {
+        match self {
+            Self::Offline { .. } => false,
+            Self::Microsoft {
+                microsoft_expires_at,
+                minecraft_expires_at,
+                ..
+            } => {
+                let deadline = crate::auth::unix_timestamp() + REFRESH_AHEAD_SECS;
+                *minecraft_expires_at == 0
+                    || deadline >= *minecraft_expires_at
+                    || (*microsoft_expires_at != 0 && deadline >= *microsoft_expires_at)
+            }
+            Self::Mojang { .. } | Self::AuthlibInjector { .. } => true,
+        }
+    }
+}
+
 pub(crate) mod mojang {
     use serde::{Deserialize, Serialize};
 
@@ -116,6 +180,7 @@ pub(crate) mod mojang {
     pub(crate) struct AuthenticateResponse {
         pub access_token: Password,
         pub client_token: String,
+        #[serde(default)]
         pub available_profiles: Vec<AvaliableProfile>,
         pub selected_profile: Option<AvaliableProfile>,
     }
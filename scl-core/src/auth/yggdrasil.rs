@@ -0,0 +1,259 @@
+//! Mojang 账户和外置登录账户共用的 Yggdrasil 协议实现
+//!
+//! 详情参考 [Yggdrasil 服务端技术规范](https://github.com/yushijinhun/authlib-injector/wiki/Yggdrasil-%E6%9C%8D%E5%8A%A1%E7%AB%AF%E6%8A%80%E6%9C%AF%E8%A7%84%E8%8C%83)
+//!
+//! 此处的函数均需要传入验证服务器地址，即 `authserver/authenticate` 等接口的上一级，需要以 `/` 结尾，
+//! Mojang 账户为 [`MOJANG_AUTH_SERVER`]，外置登录账户为 `{api_location}authserver/`。
+
+use super::{
+    error::AuthError,
+    structs::mojang::{
+        AuthenticateBody, AuthenticateResponse, AvaliableProfile, ErrorResponse, ValidateResponse,
+    },
+};
+use crate::{http::RequestResult, password::Password, prelude::*};
+
+/// Mojang 的验证服务器地址
+pub const MOJANG_AUTH_SERVER: &str = "https://authserver.mojang.com/";
+
+/// 账户下的一个角色
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct YggdrasilProfile {
+    /// 角色的统一标识，为无符号 UUID
+    pub id: String,
+    /// 角色的名称
+    pub name: String,
+}
+
+impl From<AvaliableProfile> for YggdrasilProfile {
+    fn from(value: AvaliableProfile) -> Self {
+        Self {
+            id: value.id,
+            name: value.name,
+        }
+    }
+}
+
+/// 登录或刷新后得到的会话
+#[derive(Debug, Clone)]
+pub struct YggdrasilSession {
+    /// 访问令牌
+    pub access_token: Password,
+    /// 账户下所有可用的角色，刷新时服务器通常不会返回此项
+    pub available_profiles: Vec<YggdrasilProfile>,
+    /// 令牌绑定的角色，如果为 `None` 则需要通过 [`refresh`] 绑定一个角色后才能用于游戏
+    pub selected_profile: Option<YggdrasilProfile>,
+}
+
+impl From<AuthenticateResponse> for YggdrasilSession {
+    fn from(value: AuthenticateResponse) -> Self {
+        Self {
+            access_token: value.access_token,
+            available_profiles: value
+                .available_profiles
+                .into_iter()
+                .map(YggdrasilProfile::from)
+                .collect(),
+            selected_profile: value.selected_profile.map(YggdrasilProfile::from),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct RefreshBody<'a> {
+    access_token: &'a str,
+    #[serde(skip_serializing_if = "str::is_empty")]
+    client_token: &'a str,
+    request_user: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    selected_profile: Option<AvaliableProfile>,
+}
+
+/// 将服务器返回的错误转换为对应的错误
+///
+/// 刷新时的 `ForbiddenOperationException` 代表令牌已经失效，会转换为 [`AuthError::InvalidRefreshToken`]
+fn yggdrasil_error(err: ErrorResponse, refreshing: bool) -> anyhow::Error {
+    if refreshing && err.error == "ForbiddenOperationException" {
+        return AuthError::InvalidRefreshToken(if err.error_message.is_empty() {
+            "未授权的访问".into()
+        } else {
+            err.error_message
+        })
+        .into();
+    }
+    if err.error_message.is_empty() {
+        match err.error.as_str() {
+            "ForbiddenOperationException" => anyhow::anyhow!("用户名或密码错误"),
+            "IllegalArgumentException" => anyhow::anyhow!("非法令牌绑定"),
+            _ => anyhow::anyhow!("未知原因：{}", err.error),
+        }
+    } else {
+        anyhow::anyhow!("{}：{}", err.error, err.error_message)
+    }
+}
+
+/// 使用账户密码登录
+pub async fn authenticate(
+    auth_server: &str,
+    username: &str,
+    password: &Password,
+    client_token: &str,
+) -> DynResult<YggdrasilSession> {
+    let url = format!("{auth_server}authenticate");
+    let body = AuthenticateBody {
+        username: username.to_owned(),
+        password: password.to_owned(),
+        client_token: client_token.to_owned(),
+        ..Default::default()
+    };
+    let resp: RequestResult<AuthenticateResponse> = crate::http::no_retry::post_data(&url, &body)
+        .await
+        .map_err(AuthError::network)?;
+    match resp {
+        RequestResult::Ok(resp) => Ok(resp.into()),
+        RequestResult::Err(err) => Err(yggdrasil_error(err, false)),
+    }
+}
+
+/// 刷新令牌，旧的令牌会失效
+///
+/// 如果传入了 `selected_profile`，则新的令牌会绑定到该角色上，
+/// 只有尚未绑定角色的令牌可以选择角色。
+pub async fn refresh(
+    auth_server: &str,
+    access_token: &str,
+    client_token: &str,
+    selected_profile: Option<&YggdrasilProfile>,
+) -> DynResult<YggdrasilSession> {
+    let url = format!("{auth_server}refresh");
+    let body = RefreshBody {
+        access_token,
+        client_token,
+        request_user: false,
+        selected_profile: selected_profile.map(|x| AvaliableProfile {
+            id: x.id.to_owned(),
+            name: x.name.to_owned(),
+        }),
+    };
+    let resp: RequestResult<AuthenticateResponse> = crate::http::no_retry::post_data(&url, &body)
+        .await
+        .map_err(AuthError::network)?;
+    match resp {
+        RequestResult::Ok(resp) => Ok(resp.into()),
+        RequestResult::Err(err) => Err(yggdrasil_error(err, true)),
+    }
+}
+
+/// 登录后角色的选择结果，由 [`authenticate_and_select`] 返回
+#[derive(Debug, Clone)]
+pub enum ProfileSelection {
+    /// 令牌已经绑定到了该角色上，可以直接用于游戏
+    Bound {
+        /// 绑定了角色的访问令牌
+        access_token: Password,
+        /// 绑定的角色
+        profile: YggdrasilProfile,
+    },
+    /// 账户下有多个角色，需要让用户选择其中一个后通过 [`refresh`] 绑定
+    Unbound {
+        /// 尚未绑定角色的访问令牌
+        access_token: Password,
+        /// 账户下所有可用的角色
+        profiles: Vec<YggdrasilProfile>,
+    },
+}
+
+/// 使用账户密码登录，并尽可能自动选择角色
+///
+/// 如果服务器已经选择了角色，则直接使用该角色；
+/// 如果账户下只有一个角色，或者有与用户名同名的角色，则会通过 [`refresh`] 绑定该角色；
+/// 否则返回所有的角色交由用户选择。
+///
+/// 如果账户下没有任何角色，则返回错误。
+pub async fn authenticate_and_select(
+    auth_server: &str,
+    username: &str,
+    password: &Password,
+    client_token: &str,
+) -> DynResult<ProfileSelection> {
+    let session = authenticate(auth_server, username, password, client_token).await?;
+    if let Some(profile) = session.selected_profile {
+        return Ok(ProfileSelection::Bound {
+            access_token: session.access_token,
+            profile,
+        });
+    }
+    let candidate = if session.available_profiles.len() == 1 {
+        session.available_profiles.first()
+    } else {
+        session
+            .available_profiles
+            .iter()
+            .find(|x| x.name == username)
+    };
+    if let Some(candidate) = candidate {
+        let bound = refresh(
+            auth_server,
+            session.access_token.as_str(),
+            client_token,
+            Some(candidate),
+        )
+        .await?;
+        Ok(ProfileSelection::Bound {
+            access_token: bound.access_token,
+            profile: bound
+                .selected_profile
+                .unwrap_or_else(|| candidate.to_owned()),
+        })
+    } else if session.available_profiles.is_empty() {
+        anyhow::bail!("该账户没有可用的角色！")
+    } else {
+        Ok(ProfileSelection::Unbound {
+            access_token: session.access_token,
+            profiles: session.available_profiles,
+        })
+    }
+}
+
+/// 验证令牌是否可以用于现在进行游戏
+pub async fn validate(
+    auth_server: &str,
+    access_token: &str,
+    client_token: &str,
+) -> DynResult<bool> {
+    let resp = crate::http::post(format!("{auth_server}validate"))
+        .body_json(&ValidateResponse {
+            access_token: access_token.into(),
+            client_token: client_token.to_owned(),
+        })
+        .map_err(|_| anyhow::anyhow!("无法序列化请求"))?
+        .await
+        .map_err(AuthError::network)?;
+    Ok(resp.status().is_success())
+}
+
+#[test]
+fn yggdrasil_error_test() {
+    let forbidden = ErrorResponse {
+        error: "ForbiddenOperationException".into(),
+        ..Default::default()
+    };
+    assert!(matches!(
+        yggdrasil_error(forbidden.to_owned(), true).downcast_ref::<AuthError>(),
+        Some(AuthError::InvalidRefreshToken(_))
+    ));
+    assert_eq!(
+        yggdrasil_error(forbidden, false).to_string(),
+        "用户名或密码错误"
+    );
+    let err = ErrorResponse {
+        error: "IllegalArgumentException".into(),
+        error_message: "Access token already has a profile assigned.".into(),
+        ..Default::default()
+    };
+    assert_eq!(
+        yggdrasil_error(err, true).to_string(),
+        "IllegalArgumentException：Access token already has a profile assigned."
+    );
+}
@@ -14,6 +14,7 @@ use super::{
 };
 use crate::{
     java::JavaRuntime,
+    jvm_args::{validate_jvm_args, JvmArgsPreset, JvmArgsTarget},
     prelude::*,
     utils::{get_full_path, CLASSPATH_SEPARATOR, NATIVE_ARCH_LAZY, TARGET_OS},
     version::structs::{Allowed, VersionMeta},
@@ -37,6 +38,10 @@ pub struct ClientConfig {
     pub version_type: String,
     /// 自定义 JVM 参数，这将会附加在 Class Path 之前的位置
     pub custom_java_args: Vec<String>,
+    /// 使用的 JVM 参数预设，展开后的参数会附加在自定义 JVM 参数之前
+    ///
+    /// 如果版本设置中指定了预设，则优先使用版本设置中的预设
+    pub jvm_args_preset: Option<JvmArgsPreset>,
     /// 自定义游戏参数，这将会附加在参数的最后部分
     pub custom_args: Vec<String>,
     /// 需要使用的 Java 运行时
@@ -45,6 +50,15 @@ pub struct ClientConfig {
     pub max_mem: u32,
     /// 是否进行预先资源及依赖检查
     pub recheck: bool,
+    /// 使用外置登录账户时使用的 authlib-injector 版本，需要事先通过
+    /// [`crate::download::AuthlibDownloadExt::install_authlib_injector_version`] 安装
+    ///
+    /// 如果为 `None` 则使用已安装的最新版本
+    pub authlib_injector_version: Option<String>,
+    /// 启动前应用的模组配置方案名称，参考 [`crate::version::mod_profile`]
+    ///
+    /// 如果为 `None` 则保持当前模组的启用状态不变
+    pub mod_profile: Option<String>,
 }
 
 /// 一个客户端结构，通过 [`ClientConfig`] 提供的信息组合启动参数，运行游戏
@@ -59,6 +73,10 @@ pub struct Client {
     pub args: Vec<String>,
     /// 正在运行的进程对象
     pub process: Option<Child>,
+    /// 启动时使用的账户
+    ///
+    /// 启动时可能会更新账户中的信息（例如外置登录服务器的元数据），调用方应当保存这里的账户
+    pub auth: AuthMethod,
 }
 
 fn get_game_directory(cfg: &ClientConfig) -> String {
@@ -122,6 +140,15 @@ impl Client {
         if cfg.version_info.meta.is_none() {
             anyhow::bail!("version_info is empty");
         }
+        if let AuthMethod::Microsoft {
+            has_profile: false, ..
+        } = &cfg.auth
+        {
+            return Err(crate::auth::error::AuthError::NoProfile.into());
+        }
+        if let Some(profile) = &cfg.mod_profile {
+            cfg.version_info.apply_mod_profile(profile, false).await?;
+        }
         // let build_args_timer = std::time::Instant::now();
         let mut args = Vec::<String>::with_capacity(64);
 
@@ -398,34 +425,72 @@ impl Client {
         // 禁用 JNDI
         args.push("-Dlog4j2.formatMsgNoLookups=true".into());
 
+        // JVM 参数预设
+        let jvm_args_preset = cfg
+            .version_info
+            .scl_launch_config
+            .as_ref()
+            .and_then(|x| x.jvm_args_preset)
+            .or(cfg.jvm_args_preset);
+        let mut user_jvm_args = if let Some(preset) = jvm_args_preset {
+            preset.expand(java_runtime.main_version(), cfg.max_mem)?
+        } else {
+            vec![]
+        };
+
         // 用户自定义JVM参数
         for arg in &cfg.custom_java_args {
-            args.push(arg.to_owned());
+            user_jvm_args.push(arg.to_owned());
         }
         if let Some(scl_config) = &cfg.version_info.scl_launch_config {
             if !scl_config.jvm_args.trim().is_empty() {
                 if let Ok(jvm_args) = shell_words::split(&scl_config.jvm_args) {
                     for arg in jvm_args {
-                        args.push(arg);
+                        user_jvm_args.push(arg);
                     }
                 } else {
-                    args.push(scl_config.jvm_args.to_owned());
+                    user_jvm_args.push(scl_config.jvm_args.to_owned());
                 }
             }
         }
 
-        if let AuthMethod::AuthlibInjector {
-            api_location,
-            server_meta,
-            ..
-        } = &cfg.auth
-        {
+        // 检查 JVM 参数，会导致无法启动的问题直接返回错误
+        let issues = validate_jvm_args(
+            user_jvm_args.as_slice(),
+            &JvmArgsTarget::new(&java_runtime, cfg.max_mem),
+        );
+        for issue in issues.iter().filter(|x| !x.is_error()) {
+            tracing::warn!("JVM 参数可能存在问题 {}", issue);
+        }
+        let errors: Vec<_> = issues
+            .iter()
+            .filter(|x| x.is_error())
+            .map(|x| x.to_string())
+            .collect();
+        if !errors.is_empty() {
+            anyhow::bail!("JVM 参数检查未通过：\n{}", errors.join("\n"));
+        }
+        args.append(&mut user_jvm_args);
+
+        if let AuthMethod::AuthlibInjector { api_location, .. } = &cfg.auth {
+            let api_location = api_location.to_owned();
+            // 服务器的签名公钥等元数据可能会变化，尽量使用最新的元数据，离线时使用账户中保存的元数据
+            let server_meta =
+                crate::auth::authlib_server::fetch_prefetched_meta(&mut cfg.auth).await;
             // 注入 Authlib Injector
-            let authlib_injector_path = get_full_path(format!(
-                "{}{sep}..{sep}authlib-injector.jar",
-                cfg.version_info.version_base,
-                sep = std::path::MAIN_SEPARATOR
-            ));
+            let minecraft_path = Path::new(&cfg.version_info.version_base).join("..");
+            let authlib_injector_path = crate::download::authlib::find_authlib_injector(
+                &minecraft_path,
+                cfg.authlib_injector_version.as_deref(),
+            )
+            .ok_or_else(|| {
+                if let Some(version) = &cfg.authlib_injector_version {
+                    anyhow::anyhow!("Authlib-Injector {} 尚未安装", version)
+                } else {
+                    anyhow::anyhow!("Authlib-Injector 尚未安装")
+                }
+            })?;
+            let authlib_injector_path = get_full_path(authlib_injector_path);
             args.push(format!("-javaagent:{authlib_injector_path}={api_location}"));
             args.push(format!(
                 "-Dauthlibinjector.yggdrasil.prefetched={server_meta}"
@@ -581,6 +646,7 @@ impl Client {
             java_path: java_runtime.path().to_owned(),
             args,
             process: None,
+            auth: cfg.auth,
         })
     }
 
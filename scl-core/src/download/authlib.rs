@@ -1,71 +1,314 @@
 //! 获取 authlib-injector 第三方登录代理 jar
+//!
+//! 不同版本的 authlib-injector 会以 `authlib-injector-{版本}.jar` 的文件名保存在 `.minecraft/authlib-injector` 文件夹中，
+//! 可以通过 [`crate::client::ClientConfig::authlib_injector_version`] 指定启动时使用的版本，以便在新版本出现问题时回退。
+
+use std::path::{Path, PathBuf};
+
+use sha2::{Digest, Sha256};
 
 use super::{DownloadSource, Downloader};
 use crate::prelude::*;
 
+/// 存放各个版本的 authlib-injector 的文件夹名称
+pub const AUTHLIB_INJECTOR_DIR: &str = "authlib-injector";
+/// 旧版本安装 authlib-injector 时使用的文件名称，直接存放在 `.minecraft` 文件夹中
+pub const LEGACY_AUTHLIB_INJECTOR_FILE: &str = "authlib-injector.jar";
+
+/// authlib-injector 的一个版本，来自 `artifacts.json`
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct AuthlibInjectorVersion {
+    /// 构建编号
+    pub build_number: u32,
+    /// 版本号
+    pub version: String,
+}
+
 #[derive(Debug, Deserialize)]
-struct LatestData {
+struct ArtifactsData {
+    artifacts: Vec<AuthlibInjectorVersion>,
+}
+
+/// authlib-injector 某个版本的文件校验值
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct AuthlibInjectorChecksums {
+    /// 文件的 SHA-256 摘要，十六进制小写
+    pub sha256: String,
+}
+
+/// authlib-injector 某个版本的详细信息，来自 `artifact/{构建编号}.json` 或 `artifact/latest.json`
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct AuthlibInjectorArtifact {
+    /// 构建编号
+    pub build_number: u32,
+    /// 版本号
     pub version: String,
+    /// 发布时间
+    #[serde(default)]
+    pub release_time: String,
+    /// 下载链接
     pub download_url: String,
+    /// 文件校验值
+    #[serde(default)]
+    pub checksums: AuthlibInjectorChecksums,
+}
+
+/// 检查版本号是否可以用作文件名，避免通过版本号写入到其它文件夹中
+fn check_version(version: &str) -> DynResult {
+    anyhow::ensure!(
+        !version.is_empty() && !version.contains(['/', '\\']) && !version.contains(".."),
+        "Authlib-Injector 版本号 {} 不合法",
+        version
+    );
+    Ok(())
+}
+
+/// 获取指定版本的 authlib-injector 在游戏目录中的保存路径
+///
+/// 不会检查版本号是否合法，版本号不应包含路径分隔符或 `..`
+pub fn authlib_injector_path(minecraft_path: impl AsRef<Path>, version: &str) -> PathBuf {
+    minecraft_path
+        .as_ref()
+        .join(AUTHLIB_INJECTOR_DIR)
+        .join(format!("authlib-injector-{version}.jar"))
+}
+
+fn version_key(version: &str) -> Vec<u64> {
+    version
+        .split(|c: char| !c.is_ascii_digit())
+        .filter_map(|x| x.parse().ok())
+        .collect()
+}
+
+/// 获取游戏目录中已经安装的所有 authlib-injector 版本，按版本从旧到新排序
+pub fn installed_authlib_injector_versions(minecraft_path: impl AsRef<Path>) -> Vec<String> {
+    let dir = minecraft_path.as_ref().join(AUTHLIB_INJECTOR_DIR);
+    let mut versions: Vec<String> = std::fs::read_dir(dir)
+        .map(|dir| {
+            dir.filter_map(|entry| entry.ok())
+                .filter(|entry| entry.path().is_file())
+                .filter_map(|entry| {
+                    let name = entry.file_name().to_string_lossy().to_string();
+                    name.strip_prefix("authlib-injector-")?
+                        .strip_suffix(".jar")
+                        .map(|x| x.to_owned())
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    versions.sort_by_cached_key(|x| version_key(x));
+    versions
+}
+
+/// 获取启动时应当使用的 authlib-injector 路径
+///
+/// 如果指定了版本，则使用该版本，如果该版本没有安装则返回 `None`；
+/// 否则使用已安装的最新版本，如果没有安装任何版本则尝试使用旧版本安装方式留下的 `authlib-injector.jar`。
+pub fn find_authlib_injector(
+    minecraft_path: impl AsRef<Path>,
+    version: Option<&str>,
+) -> Option<PathBuf> {
+    let minecraft_path = minecraft_path.as_ref();
+    if let Some(version) = version {
+        check_version(version).ok()?;
+        let path = authlib_injector_path(minecraft_path, version);
+        return path.is_file().then_some(path);
+    }
+    if let Some(latest) = installed_authlib_injector_versions(minecraft_path).last() {
+        return Some(authlib_injector_path(minecraft_path, latest));
+    }
+    let legacy = minecraft_path.join(LEGACY_AUTHLIB_INJECTOR_FILE);
+    legacy.is_file().then_some(legacy)
 }
 
 /// Authlib 第三方正版登录模块的下载特质
 ///
 /// 你可以通过引入本特质和 [`crate::download::Downloader`] 来下载并安装 Authlib Injector
 pub trait AuthlibDownloadExt: Sync {
+    /// 获取所有可用的 Authlib Injector 版本
+    async fn get_authlib_injector_versions(&self) -> DynResult<Vec<AuthlibInjectorVersion>>;
+    /// 获取指定版本的 Authlib Injector 详细信息，如果传入 `None` 则获取最新版本
+    async fn get_authlib_injector_artifact(
+        &self,
+        version: Option<&str>,
+    ) -> DynResult<AuthlibInjectorArtifact>;
     /// 下载最新版本的 Authlib Injector 并存放到指定路径，如果路径的文件夹不存在则会先创建它，如果文件已存在则会被覆盖
     async fn download_authlib_injector(&self, dest_path: &str) -> DynResult;
     /// 安装最新版本的 Authlib Injector
     async fn install_authlib_injector(&self) -> DynResult;
+    /// 安装指定版本的 Authlib Injector，如果传入 `None` 则安装最新版本，返回安装的版本号
+    ///
+    /// 如果该版本已经安装则不会重复下载，已经安装的其它版本会被保留
+    async fn install_authlib_injector_version(&self, version: Option<&str>) -> DynResult<String>;
+    /// 检查 Authlib Injector 是否有尚未安装的新版本，如果有则返回新版本的信息
+    async fn check_authlib_injector_update(&self) -> DynResult<Option<AuthlibInjectorArtifact>>;
+}
+
+impl<R: Reporter> Downloader<R> {
+    fn authlib_injector_api(&self) -> &'static str {
+        match self.source {
+            DownloadSource::BMCLAPI => "https://bmclapi2.bangbang93.com/mirrors/authlib-injector/",
+            _ => "https://authlib-injector.yushi.moe/",
+        }
+    }
+
+    async fn download_authlib_injector_artifact(
+        &self,
+        artifact: &AuthlibInjectorArtifact,
+        dest_path: &Path,
+    ) -> DynResult {
+        let data = crate::http::get(&artifact.download_url)
+            .recv_bytes()
+            .await
+            .map_err(|e| anyhow::anyhow!(e))?;
+        check_version(&artifact.version)?;
+        anyhow::ensure!(
+            !artifact.checksums.sha256.is_empty(),
+            "Authlib-Injector {} 没有提供 SHA-256 校验值，无法确认文件是否完整",
+            artifact.version
+        );
+        let sha256 = format!("{:x}", Sha256::digest(&data));
+        if !artifact.checksums.sha256.eq_ignore_ascii_case(&sha256) {
+            anyhow::bail!(
+                "Authlib-Injector {} 文件校验失败，期望 SHA-256 为 {}，实际为 {}",
+                artifact.version,
+                artifact.checksums.sha256,
+                sha256
+            );
+        }
+        if let Some(parent) = dest_path.parent() {
+            inner_future::fs::create_dir_all(parent).await?;
+        }
+        let mut temp_dest_path = dest_path.as_os_str().to_owned();
+        temp_dest_path.push(".tmp");
+        inner_future::fs::write(&temp_dest_path, data).await?;
+        inner_future::fs::rename(temp_dest_path, dest_path).await?;
+        Ok(())
+    }
 }
 
 impl<R: Reporter> AuthlibDownloadExt for Downloader<R> {
+    async fn get_authlib_injector_versions(&self) -> DynResult<Vec<AuthlibInjectorVersion>> {
+        let data: ArtifactsData =
+            crate::http::get(format!("{}artifacts.json", self.authlib_injector_api()))
+                .recv_json()
+                .await
+                .map_err(|e| anyhow::anyhow!(e))?;
+        Ok(data.artifacts)
+    }
+
+    async fn get_authlib_injector_artifact(
+        &self,
+        version: Option<&str>,
+    ) -> DynResult<AuthlibInjectorArtifact> {
+        let api = self.authlib_injector_api();
+        let uri = if let Some(version) = version {
+            let build_number = self
+                .get_authlib_injector_versions()
+                .await?
+                .into_iter()
+                .find(|x| x.version == version)
+                .ok_or_else(|| anyhow::anyhow!("找不到 Authlib-Injector 版本 {}", version))?
+                .build_number;
+            format!("{api}artifact/{build_number}.json")
+        } else {
+            format!("{api}artifact/latest.json")
+        };
+        crate::http::get(uri)
+            .recv_json()
+            .await
+            .map_err(|e| anyhow::anyhow!(e))
+    }
+
     async fn download_authlib_injector(&self, dest_path: &str) -> DynResult {
-        // https://authlib-injector.yushi.moe/
-        // /artifact/latest.json
-        // download_url
         let r = self.reporter.clone();
         r.add_max_progress(2.);
         r.set_message("正在获取 Authlib-Injector 版本元数据".into());
-        let latest_data: LatestData = crate::http::get(match self.source {
-            DownloadSource::BMCLAPI => {
-                "https://bmclapi2.bangbang93.com/mirrors/authlib-injector/artifact/latest.json"
-            }
-            _ => "https://authlib-injector.yushi.moe/artifact/latest.json",
-        })
-        .recv_json()
-        .await
-        .map_err(|e| anyhow::anyhow!(e))?;
+        let artifact = self.get_authlib_injector_artifact(None).await?;
         r.add_progress(1.);
-        r.set_message(format!("正在下载 Authlib-Injector {}", latest_data.version));
-        let download_url = latest_data.download_url;
-        let resp = crate::http::get(download_url)
-            .await
-            .map_err(|e| anyhow::anyhow!(e))?;
-        let temp_dest_path = format!("{dest_path}.tmp");
-        let f = inner_future::fs::OpenOptions::new()
-            .write(true)
-            .truncate(true)
-            .create(true)
-            .open(&temp_dest_path)
+        r.set_message(format!("正在下载 Authlib-Injector {}", artifact.version));
+        self.download_authlib_injector_artifact(&artifact, Path::new(dest_path))
             .await?;
-        inner_future::io::copy(resp, f).await?;
         r.add_progress(1.);
-        inner_future::fs::rename(temp_dest_path, dest_path).await?;
         Ok(())
     }
 
     async fn install_authlib_injector(&self) -> DynResult {
-        let dest_path = format!(
-            "{}{sep}authlib-injector.jar",
-            self.minecraft_path.as_str(),
-            sep = std::path::MAIN_SEPARATOR
-        );
-        let p = std::path::Path::new(&dest_path);
-        if !p.is_file() {
-            inner_future::fs::create_dir_all(p.parent().unwrap()).await?;
-            self.download_authlib_injector(&dest_path).await?;
-        }
+        self.install_authlib_injector_version(None).await?;
         Ok(())
     }
+
+    async fn install_authlib_injector_version(&self, version: Option<&str>) -> DynResult<String> {
+        if let Some(version) = version {
+            check_version(version)?;
+            if authlib_injector_path(&self.minecraft_path, version).is_file() {
+                return Ok(version.to_owned());
+            }
+        }
+        let r = self.reporter.clone();
+        r.add_max_progress(2.);
+        r.set_message("正在获取 Authlib-Injector 版本元数据".into());
+        let artifact = self.get_authlib_injector_artifact(version).await?;
+        r.add_progress(1.);
+        let dest_path = authlib_injector_path(&self.minecraft_path, &artifact.version);
+        if !dest_path.is_file() {
+            r.set_message(format!("正在下载 Authlib-Injector {}", artifact.version));
+            self.download_authlib_injector_artifact(&artifact, &dest_path)
+                .await?;
+        }
+        r.add_progress(1.);
+        Ok(artifact.version)
+    }
+
+    async fn check_authlib_injector_update(&self) -> DynResult<Option<AuthlibInjectorArtifact>> {
+        let artifact = self.get_authlib_injector_artifact(None).await?;
+        let installed = installed_authlib_injector_versions(&self.minecraft_path);
+        let is_newer = installed
+            .last()
+            .map(|x| version_key(&artifact.version) > version_key(x))
+            .unwrap_or(true);
+        Ok(is_newer.then_some(artifact))
+    }
+}
+
+#[test]
+fn authlib_injector_versions_test() {
+    let dir =
+        std::env::temp_dir().join(format!("scl-authlib-injector-test-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    assert_eq!(find_authlib_injector(&dir, None), None);
+
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join(LEGACY_AUTHLIB_INJECTOR_FILE), b"").unwrap();
+    assert_eq!(
+        find_authlib_injector(&dir, None),
+        Some(dir.join(LEGACY_AUTHLIB_INJECTOR_FILE))
+    );
+
+    for version in ["1.2.5", "1.10.0", "1.9.1"] {
+        let path = authlib_injector_path(&dir, version);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, b"").unwrap();
+    }
+    assert_eq!(
+        installed_authlib_injector_versions(&dir),
+        ["1.2.5", "1.9.1", "1.10.0"]
+    );
+    assert_eq!(
+        find_authlib_injector(&dir, None),
+        Some(authlib_injector_path(&dir, "1.10.0"))
+    );
+    assert_eq!(
+        find_authlib_injector(&dir, Some("1.9.1")),
+        Some(authlib_injector_path(&dir, "1.9.1"))
+    );
+    assert_eq!(find_authlib_injector(&dir, Some("1.0.0")), None);
+    assert_eq!(find_authlib_injector(&dir, Some("../1.9.1")), None);
+    assert!(check_version("1.2.5").is_ok());
+    for version in ["", "../evil", "1.0/../../x", "a\\b"] {
+        assert!(check_version(version).is_err());
+    }
+
+    std::fs::remove_dir_all(&dir).unwrap();
 }
@@ -1,40 +1,117 @@
 //! CurseForge 模组下载的结构和接口
 //!
-//! 在使用这个模块提供的功能前，请先设定好 `CURSEFORGE_API_KEY` 环境变量为你 CurseForge 的开发者令牌，否则服务将无法使用
+//! 在使用这个模块提供的功能前，请先通过 [`set_api_key`] 设置你 CurseForge 的开发者令牌，
+//! 或者在编译时或运行时设定好 `CURSEFORGE_API_KEY` 环境变量，否则服务将无法使用
 
 /*
-    基本链接：https://addons-ecs.forgesvc.net/api/v2/addon/
-    某个模组：https://addons-ecs.forgesvc.net/api/v2/addon/[MOD_ID]
-    模组详情：https://addons-ecs.forgesvc.net/api/v2/addon/[MOD_ID]/description
-    模组文件：https://addons-ecs.forgesvc.net/api/v2/addon/[MOD_ID]/files
-    搜索模组：https://addons-ecs.forgesvc.net/api/v2/addon/search
+    基本链接：https://api.curseforge.com/v1/
+    某个模组：https://api.curseforge.com/v1/mods/[MOD_ID]
+    模组文件：https://api.curseforge.com/v1/mods/[MOD_ID]/files
+    批量模组：https://api.curseforge.com/v1/mods （POST，{ "modIds": [...] }）
+    批量文件：https://api.curseforge.com/v1/mods/files （POST，{ "fileIds": [...] }）
+    搜索模组：https://api.curseforge.com/v1/mods/search
             请求字符串： gameId = 432
+                        classId
+                        categoryId
                         gameVersion
-                        sectionId = 6
                         searchFilter
-                        categoryID
+                        slug
+                        modLoaderType
                         index
-                        pageSize
-                        sort:
-                            FEATURED: 0
-                            POPULARITY: 1
-                            LAST_UPDATE: 2
-                            NAME: 3
-                            AUTHOR: 4
-                            TOTAL_DOWNLOADS: 5
+                        pageSize（最大为 50，且 index + pageSize 不能超过 10000）
+                        sortOrder: asc / desc
+                        sortField:
+                            FEATURED: 1
+                            POPULARITY: 2
+                            LAST_UPDATED: 3
+                            NAME: 4
+                            AUTHOR: 5
+                            TOTAL_DOWNLOADS: 6
 */
 
 use std::{
-    fmt::Write as _,
+    collections::VecDeque,
+    fmt::{Display, Write as _},
     ops::{Deref, DerefMut},
     path::PathBuf,
+    sync::RwLock,
 };
 
+use once_cell::sync::Lazy;
+use serde::de::DeserializeOwned;
+
 use crate::prelude::*;
 
-const API_KEY: Option<&str> = std::option_env!("CURSEFORGE_API_KEY");
 const BASE_URL: &str = "https://api.curseforge.com/v1/";
-const BASE_URL_SEARCH: &str = "https://api.curseforge.com/v1/mods/search?gameId=432&classId=6";
+const BASE_URL_SEARCH: &str = "https://api.curseforge.com/v1/mods/search?gameId=432";
+/// 搜索时每页的最大项目数量
+pub const MAX_PAGE_SIZE: u64 = 50;
+/// 搜索时可以获取到的最大项目位置
+pub const MAX_SEARCH_RESULTS: u64 = 10000;
+
+static API_KEY: Lazy<RwLock<Option<String>>> = Lazy::new(|| {
+    RwLock::new(
+        std::option_env!("CURSEFORGE_API_KEY")
+            .map(|x| x.to_owned())
+            .or_else(|| std::env::var("CURSEFORGE_API_KEY").ok())
+            .filter(|x| !x.is_empty()),
+    )
+});
+
+/// 设置请求 CurseForge API 时使用的开发者令牌，会覆盖 `CURSEFORGE_API_KEY` 环境变量的设置
+pub fn set_api_key(api_key: impl Into<String>) {
+    let api_key = api_key.into();
+    if let Ok(mut key) = API_KEY.write() {
+        *key = Some(api_key).filter(|x| !x.is_empty());
+    }
+}
+
+/// 是否已经设置了 CurseForge 的开发者令牌
+pub fn has_api_key() -> bool {
+    API_KEY.read().map(|x| x.is_some()).unwrap_or_default()
+}
+
+fn api_key() -> DynResult<String> {
+    API_KEY
+        .read()
+        .ok()
+        .and_then(|x| x.to_owned())
+        .ok_or_else(|| {
+            anyhow::anyhow!("未设置 CurseForge API 密钥，请先设置 CURSEFORGE_API_KEY 环境变量")
+        })
+}
+
+/// 发送请求并检查状态码，再将响应的 `data` 字段解析为指定的结构
+async fn send_request<T: DeserializeOwned>(request: surf::RequestBuilder) -> DynResult<T> {
+    let mut resp = request
+        .header("x-api-key", api_key()?)
+        .await
+        .map_err(|e| anyhow::anyhow!("无法连接到 CurseForge：{:?}", e))?;
+    match u16::from(resp.status()) {
+        200..=299 => {
+            let data: Response<T> = resp
+                .body_json()
+                .await
+                .map_err(|e| anyhow::anyhow!("无法解析 CurseForge 的响应：{:?}", e))?;
+            Ok(data.data)
+        }
+        403 => anyhow::bail!("CurseForge API 密钥无效或没有权限访问该接口"),
+        404 => anyhow::bail!("CurseForge 上找不到请求的资源"),
+        status => {
+            let body = resp.body_string().await.unwrap_or_default();
+            anyhow::bail!(
+                "CurseForge API 请求失败，状态码：{}，响应：{}",
+                status,
+                body
+            )
+        }
+    }
+}
+
+async fn get_data<T: DeserializeOwned>(uri: impl AsRef<str>) -> DynResult<T> {
+    tracing::trace!("Requesting {}", uri.as_ref());
+    send_request(crate::http::get(uri)).await
+}
 
 #[derive(Debug, Deserialize)]
 struct Response<T> {
@@ -74,6 +151,7 @@ pub struct ModAsset {
 
 /// 一个模组的信息
 #[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct ModInfo {
     /// 模组的 ID
     pub id: u64,
@@ -85,32 +163,243 @@ pub struct ModInfo {
     pub slug: String,
     /// 模组的 LOGO 图标
     pub logo: Option<ModAsset>,
+    /// 模组的类型 ID，参考 [`ClassId`]
+    #[serde(default)]
+    pub class_id: Option<u64>,
+    /// 模组的下载量
+    #[serde(default)]
+    pub download_count: f64,
+    /// 作者是否允许第三方下载模组文件
+    #[serde(default)]
+    pub allow_mod_distribution: Option<bool>,
+}
+
+/// 模组文件和依赖之间的关系
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(from = "u8")]
+pub enum RelationType {
+    /// 已经内嵌在文件中的库，无需下载
+    EmbeddedLibrary,
+    /// 可选的前置
+    OptionalDependency,
+    /// 必需的前置
+    RequiredDependency,
+    /// 配套的工具
+    Tool,
+    /// 不兼容的模组
+    Incompatible,
+    /// 已经包含在文件中的模组，无需下载
+    Include,
+    /// 未知的关系
+    Unknown(u8),
+}
+
+impl From<u8> for RelationType {
+    fn from(value: u8) -> Self {
+        match value {
+            1 => Self::EmbeddedLibrary,
+            2 => Self::OptionalDependency,
+            3 => Self::RequiredDependency,
+            4 => Self::Tool,
+            5 => Self::Incompatible,
+            6 => Self::Include,
+            other => Self::Unknown(other),
+        }
+    }
 }
 
 /// 模组的所需依赖
-///
-/// TODO：完善模组依赖下载功能
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Dependency {
-    // mod_id: i32,
-    // relation_type: u8,
+    /// 依赖的模组 ID
+    pub mod_id: u64,
+    /// 和依赖之间的关系
+    pub relation_type: RelationType,
 }
 
 /// 一个模组文件信息
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ModFile {
+    /// 模组文件的文件 ID
+    #[serde(default)]
+    pub id: u64,
+    /// 模组文件对应的模组 ID
+    #[serde(default)]
+    pub mod_id: u64,
+    /// 模组文件的显示名称
+    #[serde(default)]
+    pub display_name: String,
     /// 模组文件的文件名
     pub file_name: String,
-    /// 模组文件的下载链接
+    /// 模组文件的下载链接，作者禁止第三方下载时为空，参考 [`ModFile::is_distribution_allowed`]
+    #[serde(deserialize_with = "deserialize_null_default")]
+    #[serde(default)]
     pub download_url: String,
+    /// 模组文件的大小，单位为字节
+    #[serde(default)]
+    pub file_length: u64,
+    /// 模组文件的指纹，参考 [`crate::version::mod_identify::curseforge_fingerprint`]
+    #[serde(default)]
+    pub file_fingerprint: u32,
+    /// 模组文件的发布时间
+    #[serde(default)]
+    pub file_date: String,
+    /// 模组文件的摘要
+    #[serde(default)]
+    pub hashes: Vec<FileHash>,
     /// 模组的所需依赖
     pub dependencies: Vec<Dependency>,
     /// 模组支持的游戏版本
     pub game_versions: Vec<String>,
 }
 
+impl ModFile {
+    /// 作者是否允许第三方下载该文件，不允许时只能引导用户前往网页手动下载
+    pub fn is_distribution_allowed(&self) -> bool {
+        !self.download_url.is_empty()
+    }
+
+    /// 获取指定算法的文件摘要
+    pub fn hash(&self, algo: HashAlgo) -> Option<&str> {
+        self.hashes
+            .iter()
+            .find(|x| x.algo == algo as u8)
+            .map(|x| x.value.as_str())
+    }
+}
+
+/// 文件摘要的算法
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HashAlgo {
+    /// SHA-1
+    Sha1 = 1,
+    /// MD5
+    Md5 = 2,
+}
+
+/// 模组文件的一个摘要
+#[derive(Debug, Clone, Deserialize)]
+pub struct FileHash {
+    /// 摘要的十六进制值
+    pub value: String,
+    /// 摘要算法，`1` 为 SHA-1，`2` 为 MD5，参考 [`HashAlgo`]
+    pub algo: u8,
+}
+
+/// 模组文件所需的模组加载器，用于筛选模组文件
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ModLoaderType {
+    /// 任意模组加载器
+    #[default]
+    Any,
+    /// Forge
+    Forge,
+    /// Fabric
+    Fabric,
+    /// Quilt
+    Quilt,
+    /// NeoForge
+    NeoForge,
+}
+
+impl ModLoaderType {
+    fn to_query(self) -> u8 {
+        match self {
+            ModLoaderType::Any => 0,
+            ModLoaderType::Forge => 1,
+            ModLoaderType::Fabric => 4,
+            ModLoaderType::Quilt => 5,
+            ModLoaderType::NeoForge => 6,
+        }
+    }
+}
+
+/// 搜索的项目类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ClassId {
+    /// 模组
+    #[default]
+    Mods,
+    /// 资源包
+    ResourcePacks,
+    /// 光影包
+    Shaders,
+    /// 整合包
+    ModPacks,
+    /// 存档
+    Worlds,
+}
+
+impl ClassId {
+    /// 项目类型在 CurseForge 中的 ID
+    pub fn id(self) -> u64 {
+        match self {
+            ClassId::Mods => 6,
+            ClassId::ResourcePacks => 12,
+            ClassId::Shaders => 6552,
+            ClassId::ModPacks => 4471,
+            ClassId::Worlds => 17,
+        }
+    }
+
+    /// 通过 CurseForge 中的 ID 获取项目类型，无法识别时返回 `None`
+    pub fn from_id(id: u64) -> Option<Self> {
+        match id {
+            6 => Some(ClassId::Mods),
+            12 => Some(ClassId::ResourcePacks),
+            6552 => Some(ClassId::Shaders),
+            4471 => Some(ClassId::ModPacks),
+            17 => Some(ClassId::Worlds),
+            _ => None,
+        }
+    }
+}
+
+/// 一个通过指纹匹配到的模组文件
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FingerprintMatch {
+    /// 匹配到的文件对应的模组 ID
+    pub id: u64,
+    /// 匹配到的文件
+    pub file: ModFile,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct FingerprintMatchesResult {
+    #[serde(default)]
+    exact_matches: Vec<FingerprintMatch>,
+}
+
+#[derive(Debug, Serialize)]
+struct FingerprintsBody<'a> {
+    fingerprints: &'a [u32],
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ModIdsBody<'a> {
+    mod_ids: &'a [u64],
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct FileIdsBody<'a> {
+    file_ids: &'a [u64],
+}
+
+fn deserialize_null_default<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    T: Default + Deserialize<'de>,
+    D: Deserializer<'de>,
+{
+    let opt = Option::deserialize(deserializer)?;
+    Ok(opt.unwrap_or_default())
+}
+
 /// 使用搜索 API 时的排序方式
 #[derive(Debug, Clone, Copy, Default)]
 pub enum SearchSortMethod {
@@ -132,102 +421,361 @@ pub enum SearchSortMethod {
 impl SearchSortMethod {
     fn to_query(self) -> u8 {
         match self {
-            SearchSortMethod::Featured => 0,
-            SearchSortMethod::Populatity => 1,
-            SearchSortMethod::LastUpdate => 2,
-            SearchSortMethod::Name => 3,
-            SearchSortMethod::Author => 4,
-            SearchSortMethod::TotalDownloads => 5,
+            SearchSortMethod::Featured => 1,
+            SearchSortMethod::Populatity => 2,
+            SearchSortMethod::LastUpdate => 3,
+            SearchSortMethod::Name => 4,
+            SearchSortMethod::Author => 5,
+            SearchSortMethod::TotalDownloads => 6,
         }
     }
 }
 
+/// 搜索结果的排列顺序
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SortOrder {
+    /// 升序
+    Ascending,
+    /// 降序
+    #[default]
+    Descending,
+}
+
 /// 搜索参数，将其传入到 [`self::search_mods`] 方法以搜索模组
-#[derive(Default)]
+#[derive(Debug, Clone, Default)]
 pub struct SearchParams {
     /// 搜索支持指定游戏版本的模组
     pub game_version: String,
-    /// 当前的搜索页码
+    /// 当前的搜索页码，从 1 开始，传入 0 时视为第一页
     pub index: u64,
-    /// 当前搜索的每页项目数量
+    /// 当前搜索的每页项目数量，最大为 [`MAX_PAGE_SIZE`]，传入 0 时使用默认值 20
     pub page_size: u64,
-    /// 模组类型 ID
+    /// 搜索的项目类型
+    pub class_id: ClassId,
+    /// 项目分类 ID，为 0 时不做筛选
     pub category_id: u64,
+    /// 搜索支持指定模组加载器的模组
+    pub mod_loader_type: ModLoaderType,
     /// 搜索的关键字
     pub search_filter: String,
+    /// 按照 Slug 精确搜索，为空时不做筛选
+    pub slug: String,
     /// 搜索结果的排序方式
     pub sort: SearchSortMethod,
+    /// 搜索结果的排列顺序
+    pub sort_order: SortOrder,
 }
 
-/// 根据关键词从 Curseforge 搜索模组列表
-pub async fn search_mods(
-    SearchParams {
-        game_version,
-        index,
-        page_size,
-        category_id,
-        search_filter,
-        sort,
-    }: SearchParams,
-) -> DynResult<Vec<ModInfo>> {
-    let mut base_url = BASE_URL_SEARCH.to_string();
-    let _ = write!(&mut base_url, "&sort={}", sort.to_query());
-    if !search_filter.is_empty() {
+impl SearchParams {
+    /// 生成搜索链接，页码超出可以搜索的范围时返回错误
+    fn to_url(&self) -> DynResult<String> {
+        let page_size = match self.page_size {
+            0 => 20,
+            size if size > MAX_PAGE_SIZE => {
+                anyhow::bail!("CurseForge 搜索每页最多只能有 {} 个项目", MAX_PAGE_SIZE)
+            }
+            size => size,
+        };
+        let index = self.index.saturating_sub(1) * page_size;
+        if index + page_size > MAX_SEARCH_RESULTS {
+            anyhow::bail!(
+                "CurseForge 搜索最多只能获取前 {} 个项目",
+                MAX_SEARCH_RESULTS
+            );
+        }
+        let mut url = BASE_URL_SEARCH.to_string();
         let _ = write!(
-            &mut base_url,
-            "&searchFilter={}",
-            urlencoding::encode(&search_filter)
+            &mut url,
+            "&classId={}&sortField={}&sortOrder={}&index={index}&pageSize={page_size}",
+            self.class_id.id(),
+            self.sort.to_query(),
+            match self.sort_order {
+                SortOrder::Ascending => "asc",
+                SortOrder::Descending => "desc",
+            }
         );
+        if !self.search_filter.is_empty() {
+            let _ = write!(
+                &mut url,
+                "&searchFilter={}",
+                urlencoding::encode(&self.search_filter)
+            );
+        }
+        if !self.slug.is_empty() {
+            let _ = write!(&mut url, "&slug={}", urlencoding::encode(&self.slug));
+        }
+        if !self.game_version.is_empty() {
+            let _ = write!(
+                &mut url,
+                "&gameVersion={}",
+                urlencoding::encode(&self.game_version)
+            );
+        }
+        if self.mod_loader_type != ModLoaderType::Any {
+            let _ = write!(
+                &mut url,
+                "&modLoaderType={}",
+                self.mod_loader_type.to_query()
+            );
+        }
+        if self.category_id > 0 {
+            let _ = write!(&mut url, "&categoryId={}", self.category_id);
+        }
+        Ok(url)
     }
-    if !game_version.is_empty() {
-        let _ = write!(&mut base_url, "&gameVersion={game_version}");
-    }
-    if index > 0 {
-        let _ = write!(&mut base_url, "&index={index}");
-    }
-    if page_size > 0 && page_size <= 30 {
-        let _ = write!(&mut base_url, "&pageSize={page_size}");
-    } else {
-        let _ = write!(&mut base_url, "&pageSize={}", 20);
-    }
-    if category_id > 0 {
-        let _ = write!(&mut base_url, "&categoryID={category_id}");
+}
+
+/// 搜索结果的分页信息
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Pagination {
+    /// 本页第一个项目在所有结果中的位置
+    pub index: u64,
+    /// 请求的每页项目数量
+    pub page_size: u64,
+    /// 本页实际的项目数量
+    pub result_count: u64,
+    /// 所有结果的项目数量
+    pub total_count: u64,
+}
+
+impl Pagination {
+    /// 计算所有结果的总页数，受到 [`MAX_SEARCH_RESULTS`] 的限制
+    pub fn total_pages(&self) -> u64 {
+        if self.page_size == 0 {
+            0
+        } else {
+            self.total_count
+                .min(MAX_SEARCH_RESULTS)
+                .div_ceil(self.page_size)
+        }
     }
-    tracing::trace!("Searching by {base_url}");
-    let data: Response<Vec<ModInfo>> = crate::http::get(&base_url)
-        .header("x-api-key", API_KEY.unwrap_or_default())
+}
+
+/// 模组的搜索结果
+#[derive(Debug, Deserialize)]
+pub struct SearchResult {
+    /// 搜索命中的模组列表
+    pub data: Vec<ModInfo>,
+    /// 分页信息
+    #[serde(default)]
+    pub pagination: Pagination,
+}
+
+/// 根据关键词从 Curseforge 搜索模组列表
+pub async fn search_mods(params: SearchParams) -> DynResult<SearchResult> {
+    let url = params.to_url()?;
+    tracing::trace!("Searching by {url}");
+    let mut resp = crate::http::get(&url)
+        .header("x-api-key", api_key()?)
         .await
-        .map_err(|e| anyhow::anyhow!(e))?
-        .body_json()
+        .map_err(|e| anyhow::anyhow!("无法连接到 CurseForge：{:?}", e))?;
+    if !resp.status().is_success() {
+        anyhow::bail!("CurseForge 搜索失败，状态码：{}", resp.status());
+    }
+    resp.body_json()
         .await
-        .map_err(|e| anyhow::anyhow!(e))?;
-    Ok(data.data)
+        .map_err(|e| anyhow::anyhow!("无法解析 CurseForge 的响应：{:?}", e))
 }
 
 /// 通过模组在 Curseforge 的 ID 获取详情信息
 pub async fn get_mod_info(modid: u64) -> DynResult<ModInfo> {
-    let data: Response<ModInfo> = crate::http::get(&(format!("{BASE_URL}mods/{modid}")))
-        .header("x-api-key", API_KEY.unwrap_or_default())
-        .await
-        .map_err(|e| anyhow::anyhow!(e))?
-        .body_json()
-        .await
-        .map_err(|e| anyhow::anyhow!(e))?;
-    Ok(data.data)
+    get_data(format!("{BASE_URL}mods/{modid}")).await
 }
 
 /// 获取模组在 Curseforge 的 ID 获取可下载的模组文件列表
 pub async fn get_mod_files(modid: u64) -> DynResult<Vec<ModFile>> {
-    let data: Response<Vec<ModFile>> = crate::http::get(&format!("{BASE_URL}mods/{modid}/files"))
-        .header("x-api-key", API_KEY.unwrap_or_default())
-        .await
-        .map_err(|e| anyhow::anyhow!(e))?
-        .body_json()
-        .await
+    get_data(format!("{BASE_URL}mods/{modid}/files")).await
+}
+
+/// 获取模组在 Curseforge 的 ID 获取支持指定游戏版本和模组加载器的模组文件列表，按发布时间从新到旧排序
+///
+/// 游戏版本为空或模组加载器为 [`ModLoaderType::Any`] 时不做对应的筛选
+pub async fn get_mod_files_filtered(
+    modid: u64,
+    game_version: &str,
+    mod_loader_type: ModLoaderType,
+) -> DynResult<Vec<ModFile>> {
+    let mut url = format!("{BASE_URL}mods/{modid}/files?pageSize={MAX_PAGE_SIZE}");
+    if !game_version.is_empty() {
+        let _ = write!(
+            &mut url,
+            "&gameVersion={}",
+            urlencoding::encode(game_version)
+        );
+    }
+    if mod_loader_type != ModLoaderType::Any {
+        let _ = write!(&mut url, "&modLoaderType={}", mod_loader_type.to_query());
+    }
+    let mut files: Vec<ModFile> = get_data(url).await?;
+    files.sort_by(|a, b| b.file_date.cmp(&a.file_date));
+    Ok(files)
+}
+
+/// 获取指定的模组文件信息
+pub async fn get_mod_file(modid: u64, fileid: u64) -> DynResult<ModFile> {
+    get_data(format!("{BASE_URL}mods/{modid}/files/{fileid}")).await
+}
+
+/// 根据模组 ID 批量获取模组信息，找不到的模组不会出现在结果中
+pub async fn get_mods_info(mod_ids: &[u64]) -> DynResult<Vec<ModInfo>> {
+    if mod_ids.is_empty() {
+        return Ok(vec![]);
+    }
+    let request = crate::http::post(format!("{BASE_URL}mods"))
+        .body_json(&ModIdsBody { mod_ids })
+        .map_err(|e| anyhow::anyhow!(e))?;
+    send_request(request).await
+}
+
+/// 根据文件 ID 批量获取模组文件信息，找不到的文件不会出现在结果中
+pub async fn get_mod_files_by_ids(file_ids: &[u64]) -> DynResult<Vec<ModFile>> {
+    if file_ids.is_empty() {
+        return Ok(vec![]);
+    }
+    let request = crate::http::post(format!("{BASE_URL}mods/files"))
+        .body_json(&FileIdsBody { file_ids })
+        .map_err(|e| anyhow::anyhow!(e))?;
+    send_request(request).await
+}
+
+/// 获取模组文件的更新日志，为 HTML 格式
+pub async fn get_mod_file_changelog(modid: u64, fileid: u64) -> DynResult<String> {
+    get_data(format!("{BASE_URL}mods/{modid}/files/{fileid}/changelog")).await
+}
+
+/// 根据文件指纹批量获取文件所属的模组及文件信息，只返回完全匹配的结果
+pub async fn get_fingerprint_matches(fingerprints: &[u32]) -> DynResult<Vec<FingerprintMatch>> {
+    if fingerprints.is_empty() {
+        return Ok(vec![]);
+    }
+    let request = crate::http::post(format!("{BASE_URL}fingerprints/432"))
+        .body_json(&FingerprintsBody { fingerprints })
         .map_err(|e| anyhow::anyhow!(e))?;
-    Ok(data.data)
+    let data: FingerprintMatchesResult = send_request(request).await?;
+    Ok(data.exact_matches)
+}
+
+/// 一个模组文件及其依赖的下载计划，可以通过 [`resolve_download_plan`] 生成
+#[derive(Debug, Clone, Default)]
+pub struct DownloadPlan {
+    /// 需要下载的文件，包括传入的文件和解析出的依赖
+    pub files: Vec<ModFile>,
+    /// 没有加入计划的可选依赖
+    pub optional: Vec<Dependency>,
+    /// 无法找到兼容文件的必需依赖
+    pub unresolved: Vec<Dependency>,
+    /// 计划中的文件所声明的不兼容模组
+    pub incompatible: Vec<Dependency>,
+}
+
+impl DownloadPlan {
+    /// 计划中是否包含了该模组
+    pub fn contains_mod(&self, mod_id: u64) -> bool {
+        self.files.iter().any(|x| x.mod_id == mod_id)
+    }
+
+    /// 获取计划中已经包含的不兼容模组，这些模组和计划中的其它文件无法同时安装
+    pub fn conflicts(&self) -> Vec<&Dependency> {
+        self.incompatible
+            .iter()
+            .filter(|x| self.contains_mod(x.mod_id))
+            .collect()
+    }
+
+    /// 获取计划中作者禁止第三方下载的文件，这些文件需要引导用户手动下载
+    pub fn distribution_disabled(&self) -> Vec<&ModFile> {
+        self.files
+            .iter()
+            .filter(|x| !x.is_distribution_allowed())
+            .collect()
+    }
+}
+
+/// 递归解析模组文件的依赖，生成下载计划
+///
+/// 依赖会选择支持指定游戏版本和模组加载器的最新文件，
+/// 已经内嵌或包含的依赖以及配套工具会被忽略，可选依赖只有在 `include_optional` 为 `true` 时才会加入计划
+pub async fn resolve_download_plan(
+    files: Vec<ModFile>,
+    game_version: &str,
+    mod_loader_type: ModLoaderType,
+    include_optional: bool,
+) -> DynResult<DownloadPlan> {
+    let mut plan = DownloadPlan::default();
+    let mut queue: VecDeque<_> = files.into();
+    while let Some(file) = queue.pop_front() {
+        if plan.contains_mod(file.mod_id) {
+            continue;
+        }
+        for dep in file.dependencies.iter() {
+            match dep.relation_type {
+                RelationType::RequiredDependency => {}
+                RelationType::OptionalDependency if include_optional => {}
+                RelationType::OptionalDependency => {
+                    plan.optional.push(dep.to_owned());
+                    continue;
+                }
+                RelationType::Incompatible => {
+                    plan.incompatible.push(dep.to_owned());
+                    continue;
+                }
+                _ => continue,
+            }
+            if plan.contains_mod(dep.mod_id) || queue.iter().any(|x| x.mod_id == dep.mod_id) {
+                continue;
+            }
+            let resolved = get_mod_files_filtered(dep.mod_id, game_version, mod_loader_type)
+                .await?
+                .into_iter()
+                .next();
+            match resolved {
+                Some(dep_file) => queue.push_back(dep_file),
+                None if dep.relation_type == RelationType::RequiredDependency => {
+                    plan.unresolved.push(dep.to_owned())
+                }
+                None => plan.optional.push(dep.to_owned()),
+            }
+        }
+        plan.files.push(file);
+    }
+    Ok(plan)
+}
+
+/// 作者禁止了第三方下载模组文件时返回的错误，可以通过 [`anyhow::Error::downcast_ref`] 判断
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DistributionDisabledError {
+    /// 模组 ID
+    pub mod_id: u64,
+    /// 文件 ID
+    pub file_id: u64,
+    /// 文件名
+    pub file_name: String,
+}
+
+impl DistributionDisabledError {
+    /// 可供用户手动下载的网页链接
+    pub fn website_url(&self) -> String {
+        format!(
+            "https://www.curseforge.com/projects/{}/files/{}",
+            self.mod_id, self.file_id
+        )
+    }
 }
 
+impl Display for DistributionDisabledError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "模组文件 {} 的作者禁止了第三方下载，请前往 {} 手动下载",
+            self.file_name,
+            self.website_url()
+        )
+    }
+}
+
+impl std::error::Error for DistributionDisabledError {}
+
 /// 获取模组在 Curseforge 的 ID 获取模组的图标
 pub async fn get_mod_icon(mod_info: &ModInfo) -> DynResult<image::DynamicImage> {
     if let Some(logo) = &mod_info.logo {
@@ -253,23 +801,129 @@ pub async fn get_mod_icon_by_id(modid: u64) -> DynResult<image::DynamicImage> {
     get_mod_icon(&mod_info).await
 }
 
-/// 下载模组
-pub async fn download_mod(
-    _ctx: Option<impl Reporter>,
-    _name: &str,
-    url: &str,
-    dest: PathBuf,
-) -> DynResult {
-    let mut file = inner_future::fs::OpenOptions::new()
-        .create(true)
-        .truncate(true)
-        .write(true)
-        .open(format!("{}.tmp", dest.to_str().unwrap()))
-        .await?;
-    let res = crate::http::get(url)
+/// 下载模组文件，下载完成后会校验文件的 SHA-1 或 MD5 摘要
+///
+/// 如果文件没有提供任何摘要，则只能校验文件大小，并会输出警告
+///
+/// 如果作者禁止了第三方下载，则会返回 [`DistributionDisabledError`]
+pub async fn download_mod(ctx: Option<impl Reporter>, file: &ModFile, dest: PathBuf) -> DynResult {
+    if !file.is_distribution_allowed() {
+        return Err(DistributionDisabledError {
+            mod_id: file.mod_id,
+            file_id: file.id,
+            file_name: file.file_name.to_owned(),
+        }
+        .into());
+    }
+    let r = ctx.sub();
+    r.add_max_progress(1.);
+    r.set_message(format!("正在下载模组 {}", file.file_name));
+    let tmp_dest = format!("{}.tmp", dest.to_string_lossy());
+    let mut resp = crate::http::get(&file.download_url)
         .await
         .map_err(|e| anyhow::anyhow!(e))?;
-    inner_future::io::copy(res, &mut file).await?;
-    inner_future::fs::rename(format!("{}.tmp", dest.to_str().unwrap()), dest).await?;
+    if !resp.status().is_success() {
+        anyhow::bail!(
+            "下载模组文件 {} 失败，状态码：{}",
+            file.file_name,
+            resp.status()
+        );
+    }
+    let data = resp.body_bytes().await.map_err(|e| anyhow::anyhow!(e))?;
+    let (algo, expected, current) = if let Some(sha1) = file.hash(HashAlgo::Sha1) {
+        (
+            "SHA-1",
+            sha1.to_owned(),
+            sha1_smol::Sha1::from(&data).hexdigest(),
+        )
+    } else if let Some(md5) = file.hash(HashAlgo::Md5) {
+        ("MD5", md5.to_owned(), format!("{:x}", md5::compute(&data)))
+    } else {
+        tracing::warn!(
+            "模组文件 {} 没有提供 SHA-1 或 MD5 摘要，只能校验文件大小",
+            file.file_name
+        );
+        anyhow::ensure!(
+            file.file_length != 0,
+            "模组文件 {} 没有提供摘要和文件大小，无法校验文件",
+            file.file_name
+        );
+        (
+            "文件大小",
+            file.file_length.to_string(),
+            data.len().to_string(),
+        )
+    };
+    if !expected.eq_ignore_ascii_case(&current) {
+        anyhow::bail!(
+            "模组文件 {} 校验失败，期望 {} 为 {}，实际为 {}",
+            file.file_name,
+            algo,
+            expected,
+            current
+        );
+    }
+    if let Some(parent) = dest.parent() {
+        inner_future::fs::create_dir_all(parent).await?;
+    }
+    inner_future::fs::write(&tmp_dest, data).await?;
+    inner_future::fs::rename(&tmp_dest, dest).await?;
+    r.add_progress(1.);
     Ok(())
 }
+
+#[test]
+fn curseforge_search_test() {
+    let params = SearchParams {
+        index: 3,
+        page_size: 10,
+        class_id: ClassId::Shaders,
+        mod_loader_type: ModLoaderType::Fabric,
+        search_filter: "complementary shaders".into(),
+        sort: SearchSortMethod::TotalDownloads,
+        ..Default::default()
+    };
+    assert_eq!(
+        params.to_url().unwrap(),
+        "https://api.curseforge.com/v1/mods/search?gameId=432&classId=6552&sortField=6&sortOrder=desc&index=20&pageSize=10&searchFilter=complementary%20shaders&modLoaderType=4"
+    );
+    assert!(SearchParams {
+        page_size: 51,
+        ..Default::default()
+    }
+    .to_url()
+    .is_err());
+    assert!(SearchParams {
+        index: 501,
+        page_size: 20,
+        ..Default::default()
+    }
+    .to_url()
+    .is_err());
+
+    let file: ModFile = serde_json::from_str(
+        r#"{"id":4581234,"modId":238222,"displayName":"jei-1.20.1-forge-15.2.0.27.jar","fileName":"jei-1.20.1-forge-15.2.0.27.jar","downloadUrl":null,"fileLength":1200000,"hashes":[{"value":"abc","algo":2},{"value":"def","algo":1}],"dependencies":[{"modId":1,"relationType":3},{"modId":2,"relationType":5},{"modId":3,"relationType":9}],"gameVersions":["1.20.1","Forge"]}"#,
+    )
+    .unwrap();
+    assert!(!file.is_distribution_allowed());
+    assert_eq!(file.hash(HashAlgo::Sha1), Some("def"));
+    assert_eq!(
+        file.dependencies
+            .iter()
+            .map(|x| x.relation_type)
+            .collect::<Vec<_>>(),
+        [
+            RelationType::RequiredDependency,
+            RelationType::Incompatible,
+            RelationType::Unknown(9)
+        ]
+    );
+
+    let pagination = Pagination {
+        index: 0,
+        page_size: 50,
+        result_count: 50,
+        total_count: 123456,
+    };
+    assert_eq!(pagination.total_pages(), 200);
+}
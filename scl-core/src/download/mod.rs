@@ -18,6 +18,7 @@ use anyhow::Context;
 pub use authlib::AuthlibDownloadExt;
 pub use fabric::FabricDownloadExt;
 pub use forge::ForgeDownloadExt;
+pub use modrinth::ModrinthDownloadExt;
 pub use neoforge::NeoForgeDownloadExt;
 pub use optifine::OptifineDownloadExt;
 pub use quiltmc::QuiltMCDownloadExt;
@@ -146,6 +147,17 @@ impl<R> Downloader<R> {
         self.set_minecraft_path(dot_minecraft_path);
         self
     }
+
+    /// 获取指定版本的模组文件夹路径，会根据 [`Downloader::game_independent`] 决定是否使用版本独立的文件夹
+    pub(crate) fn mods_path(&self, version_name: &str) -> std::path::PathBuf {
+        if self.game_independent {
+            Path::new(&self.minecraft_version_path)
+                .join(version_name)
+                .join("mods")
+        } else {
+            Path::new(&self.minecraft_path).join("mods")
+        }
+    }
 }
 
 impl<R: Reporter> Clone for Downloader<R> {
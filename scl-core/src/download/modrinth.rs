@@ -1,7 +1,15 @@
 //! Modrinth 的模组检索和下载
 
+use std::{
+    collections::HashMap,
+    fmt::Write as _,
+    path::{Path, PathBuf},
+};
+
 use image::DynamicImage;
+use sha2::{Digest, Sha512};
 
+use super::Downloader;
 use crate::prelude::*;
 
 /// 一个模组搜索结果的信息
@@ -9,7 +17,7 @@ use crate::prelude::*;
 pub struct ModResult {
     /// 这个不是真正的模组 ID，而是服务器中记录的数字 ID
     #[serde(deserialize_with = "deserialize_null_default")]
-    #[serde(default)]
+    #[serde(default, alias = "id")]
     pub project_id: String,
     /// 用于短链接的模组名，大部分应该都是模组 ID
     #[serde(deserialize_with = "deserialize_null_default")]
@@ -21,10 +29,100 @@ pub struct ModResult {
     pub title: String,
     /// 模组的简介
     pub description: String,
+    /// 项目的类型
+    #[serde(default)]
+    pub project_type: ProjectType,
+    /// 作者的用户名
+    #[serde(default)]
+    pub author: String,
+    /// 项目的分类，包括所支持的模组加载器
+    #[serde(default)]
+    pub categories: Vec<String>,
+    /// 项目支持的游戏版本
+    #[serde(default)]
+    pub versions: Vec<String>,
+    /// 项目的下载量
+    #[serde(default)]
+    pub downloads: u64,
+    /// 项目的关注数
+    #[serde(default)]
+    pub follows: u64,
+    /// 项目在客户端的需求情况
+    #[serde(default)]
+    pub client_side: SideSupport,
+    /// 项目在服务端的需求情况
+    #[serde(default)]
+    pub server_side: SideSupport,
+    /// 项目的最后更新时间
+    #[serde(default)]
+    pub date_modified: String,
+}
+
+/// Modrinth 上的项目类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ProjectType {
+    /// 模组
+    #[default]
+    Mod,
+    /// 资源包
+    ResourcePack,
+    /// 光影包
+    Shader,
+    /// 整合包
+    ModPack,
+    /// 数据包，在 Modrinth 中是以 `datapack` 为模组加载器的模组
+    DataPack,
+    /// 服务端插件
+    Plugin,
+}
+
+impl ProjectType {
+    /// 搜索该类型的项目时使用的分面条件
+    fn facets(self) -> Vec<Vec<String>> {
+        match self {
+            ProjectType::Mod => vec![vec!["project_type:mod".into()]],
+            ProjectType::ResourcePack => vec![vec!["project_type:resourcepack".into()]],
+            ProjectType::Shader => vec![vec!["project_type:shader".into()]],
+            ProjectType::ModPack => vec![vec!["project_type:modpack".into()]],
+            ProjectType::DataPack => vec![
+                vec!["project_type:mod".into()],
+                vec!["categories:datapack".into()],
+            ],
+            ProjectType::Plugin => vec![vec!["project_type:plugin".into()]],
+        }
+    }
+}
+
+/// 项目在客户端或服务端的需求情况
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SideSupport {
+    /// 必须安装
+    Required,
+    /// 可选安装
+    Optional,
+    /// 不支持安装
+    Unsupported,
+    /// 未知
+    #[default]
+    #[serde(other)]
+    Unknown,
+}
+
+impl SideSupport {
+    fn as_str(self) -> &'static str {
+        match self {
+            SideSupport::Required => "required",
+            SideSupport::Optional => "optional",
+            SideSupport::Unsupported => "unsupported",
+            SideSupport::Unknown => "unknown",
+        }
+    }
 }
 
 /// 一个模组文件信息
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize)]
 pub struct ModFile {
     /// 此模组文件的下载链接
     pub url: String,
@@ -32,17 +130,85 @@ pub struct ModFile {
     pub filename: String,
     /// 是否是主要的推荐下载项目
     pub primary: bool,
+    /// 此模组文件的摘要，键为算法名称，例如 `sha1` 和 `sha512`
+    #[serde(default)]
+    pub hashes: HashMap<String, String>,
+    /// 此模组文件的大小，单位为字节
+    #[serde(default)]
+    pub size: u64,
+}
+
+/// 模组版本的依赖类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DependencyType {
+    /// 必需的前置
+    Required,
+    /// 可选的前置
+    Optional,
+    /// 不兼容的项目
+    Incompatible,
+    /// 已经内嵌在文件中的项目，无需下载
+    Embedded,
+}
+
+/// 模组版本的一个依赖
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct VersionDependency {
+    /// 依赖的具体版本 ID，为空时代表任意版本
+    #[serde(default)]
+    pub version_id: Option<String>,
+    /// 依赖的项目 ID
+    #[serde(default)]
+    pub project_id: Option<String>,
+    /// 依赖的文件名，只有依赖不在 Modrinth 上时才会出现
+    #[serde(default)]
+    pub file_name: Option<String>,
+    /// 依赖的类型
+    pub dependency_type: DependencyType,
 }
 
 /// 一个模组文件的信息
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize)]
 pub struct ModVersion {
+    /// 此版本的 ID
+    #[serde(default)]
+    pub id: String,
+    /// 此版本所属模组的 ID
+    #[serde(default)]
+    pub project_id: String,
+    /// 此版本的名称
+    #[serde(default)]
+    pub name: String,
+    /// 此版本的版本号
+    #[serde(default)]
+    pub version_number: String,
+    /// 此版本的更新日志，为 Markdown 格式
+    #[serde(deserialize_with = "deserialize_null_default")]
+    #[serde(default)]
+    pub changelog: String,
+    /// 此版本的发布时间
+    #[serde(default)]
+    pub date_published: String,
     /// 文件列表
     pub files: Vec<ModFile>,
     /// 模组文件支持的游戏版本
     pub game_versions: Vec<String>,
     /// 模组文件所需的模组加载器，通常是 `Forge` 或者 `Fabric`
     pub loaders: Vec<String>,
+    /// 此版本的依赖
+    #[serde(default)]
+    pub dependencies: Vec<VersionDependency>,
+}
+
+impl ModVersion {
+    /// 获取此版本的主要文件，如果没有标记主要文件则使用第一个文件
+    pub fn primary_file(&self) -> Option<&ModFile> {
+        self.files
+            .iter()
+            .find(|x| x.primary)
+            .or_else(|| self.files.first())
+    }
 }
 
 fn deserialize_null_default<'de, D, T>(deserializer: D) -> Result<T, D::Error>
@@ -59,17 +225,144 @@ where
 pub struct ModSearchResult {
     /// 搜索命中的模组列表
     pub hits: Vec<ModResult>,
+    /// 本页第一个项目在所有结果中的位置
+    #[serde(default)]
+    pub offset: u64,
+    /// 本页的最大项目数量
+    #[serde(default)]
+    pub limit: u64,
+    /// 所有结果的项目数量
+    #[serde(default)]
+    pub total_hits: u64,
+}
+
+impl ModSearchResult {
+    /// 按照本页的项目数量计算所有结果的总页数
+    pub fn total_pages(&self) -> u64 {
+        if self.limit == 0 {
+            0
+        } else {
+            self.total_hits.div_ceil(self.limit)
+        }
+    }
+}
+
+/// 使用搜索 API 时的排序方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SearchSortMethod {
+    /// 按相关度排序
+    #[default]
+    Relevance,
+    /// 按下载量排序
+    Downloads,
+    /// 按关注数排序
+    Follows,
+    /// 按发布时间排序
+    Newest,
+    /// 按最后更新时间排序
+    Updated,
+}
+
+impl SearchSortMethod {
+    fn to_query(self) -> &'static str {
+        match self {
+            SearchSortMethod::Relevance => "relevance",
+            SearchSortMethod::Downloads => "downloads",
+            SearchSortMethod::Follows => "follows",
+            SearchSortMethod::Newest => "newest",
+            SearchSortMethod::Updated => "updated",
+        }
+    }
 }
 
 /// 搜索参数，将其传入到 [`self::search_mods`] 方法以搜索模组
-#[derive(Default)]
+///
+/// 同一个列表中的条件满足其一即可，不同列表之间的条件需要同时满足，空列表代表不做筛选
+#[derive(Debug, Clone, Default)]
 pub struct SearchParams {
     /// 搜索关键词
     pub search_filter: String,
-    /// 搜索结果的页码
+    /// 搜索结果的页码，从 1 开始，传入 0 时视为第一页
     pub index: u64,
-    /// 搜索结果的单页项目数量，最大为 100
+    /// 搜索结果的单页项目数量，最大为 100，传入 0 时使用默认值 10
     pub page_size: u64,
+    /// 搜索的项目类型，为 `None` 时搜索所有类型
+    pub project_type: Option<ProjectType>,
+    /// 项目的分类，例如 `technology`、`optimization`
+    pub categories: Vec<String>,
+    /// 项目支持的游戏版本，例如 `1.20.1`
+    pub game_versions: Vec<String>,
+    /// 项目支持的模组加载器，例如 `fabric`、`forge`
+    pub loaders: Vec<String>,
+    /// 项目在客户端的需求情况
+    pub client_side: Vec<SideSupport>,
+    /// 项目在服务端的需求情况
+    pub server_side: Vec<SideSupport>,
+    /// 搜索结果的排序方式
+    pub sort: SearchSortMethod,
+}
+
+impl SearchParams {
+    /// 生成搜索时使用的分面条件
+    fn facets(&self) -> Vec<Vec<String>> {
+        let mut facets = self
+            .project_type
+            .map(ProjectType::facets)
+            .unwrap_or_default();
+        let mut push = |key: &str, values: Vec<&str>| {
+            if !values.is_empty() {
+                facets.push(values.into_iter().map(|x| format!("{key}:{x}")).collect());
+            }
+        };
+        push(
+            "categories",
+            self.categories.iter().map(|x| x.as_str()).collect(),
+        );
+        push(
+            "versions",
+            self.game_versions.iter().map(|x| x.as_str()).collect(),
+        );
+        push(
+            "categories",
+            self.loaders.iter().map(|x| x.as_str()).collect(),
+        );
+        push(
+            "client_side",
+            self.client_side.iter().map(|x| x.as_str()).collect(),
+        );
+        push(
+            "server_side",
+            self.server_side.iter().map(|x| x.as_str()).collect(),
+        );
+        facets
+    }
+
+    /// 生成搜索链接的查询字符串
+    fn to_query(&self) -> String {
+        let page_size = match self.page_size {
+            0 => 10,
+            size => size.min(100),
+        };
+        let mut query = format!(
+            "offset={}&limit={}&index={}",
+            self.index.saturating_sub(1) * page_size,
+            page_size,
+            self.sort.to_query()
+        );
+        if !self.search_filter.is_empty() {
+            let _ = write!(
+                &mut query,
+                "&query={}",
+                urlencoding::encode(&self.search_filter)
+            );
+        }
+        let facets = self.facets();
+        if !facets.is_empty() {
+            let facets = serde_json::to_string(&facets).unwrap_or_default();
+            let _ = write!(&mut query, "&facets={}", urlencoding::encode(&facets));
+        }
+        query
+    }
 }
 
 /// 根据模组 ID 获取可以下载的模组文件
@@ -80,6 +373,321 @@ pub async fn get_mod_files(modid: &str) -> DynResult<Vec<ModVersion>> {
     .await
 }
 
+fn version_list_query(loaders: &[String], game_versions: &[String]) -> String {
+    let mut query = vec![];
+    if !loaders.is_empty() {
+        let loaders = serde_json::to_string(loaders).unwrap_or_default();
+        query.push(format!("loaders={}", urlencoding::encode(&loaders)));
+    }
+    if !game_versions.is_empty() {
+        let game_versions = serde_json::to_string(game_versions).unwrap_or_default();
+        query.push(format!(
+            "game_versions={}",
+            urlencoding::encode(&game_versions)
+        ));
+    }
+    if query.is_empty() {
+        String::new()
+    } else {
+        format!("?{}", query.join("&"))
+    }
+}
+
+/// 根据模组 ID 获取支持指定模组加载器和游戏版本的模组文件，按发布时间从新到旧排序
+///
+/// 传入空数组则不做对应的筛选
+pub async fn get_mod_files_filtered(
+    modid: &str,
+    loaders: &[String],
+    game_versions: &[String],
+) -> DynResult<Vec<ModVersion>> {
+    crate::http::retry_get_json(format!(
+        "https://api.modrinth.com/v2/project/{modid}/version{}",
+        version_list_query(loaders, game_versions)
+    ))
+    .await
+}
+
+/// 根据版本 ID 获取模组版本信息
+pub async fn get_version(version_id: &str) -> DynResult<ModVersion> {
+    crate::http::retry_get_json(format!("https://api.modrinth.com/v2/version/{version_id}")).await
+}
+
+/// 一个模组及其依赖的下载计划，可以通过 [`resolve_download_plan`] 生成
+#[derive(Debug, Clone, Default)]
+pub struct DownloadPlan {
+    /// 需要下载的版本，包括传入的版本和解析出的依赖
+    pub versions: Vec<ModVersion>,
+    /// 没有加入计划的可选依赖
+    pub optional: Vec<VersionDependency>,
+    /// 无法找到兼容版本的必需依赖
+    pub unresolved: Vec<VersionDependency>,
+    /// 计划中的版本所声明的不兼容项目
+    pub incompatible: Vec<VersionDependency>,
+}
+
+impl DownloadPlan {
+    /// 计划中是否包含了该项目
+    pub fn contains_project(&self, project_id: &str) -> bool {
+        self.versions.iter().any(|x| x.project_id == project_id)
+    }
+
+    /// 获取计划中已经包含的不兼容项目，这些项目和计划中的其它版本无法同时安装
+    pub fn conflicts(&self) -> Vec<&VersionDependency> {
+        self.incompatible
+            .iter()
+            .filter(|dep| {
+                dep.project_id
+                    .as_deref()
+                    .map(|x| self.contains_project(x))
+                    .unwrap_or_default()
+                    || dep
+                        .version_id
+                        .as_deref()
+                        .map(|x| self.versions.iter().any(|v| v.id == x))
+                        .unwrap_or_default()
+            })
+            .collect()
+    }
+}
+
+/// 递归解析模组版本的依赖，生成下载计划
+///
+/// 没有指定具体版本的依赖会选择支持指定模组加载器和游戏版本的最新版本，
+/// 已经内嵌的依赖会被忽略，可选依赖只有在 `include_optional` 为 `true` 时才会加入计划
+pub async fn resolve_download_plan(
+    versions: Vec<ModVersion>,
+    loaders: &[String],
+    game_versions: &[String],
+    include_optional: bool,
+) -> DynResult<DownloadPlan> {
+    let mut plan = DownloadPlan::default();
+    let mut queue: std::collections::VecDeque<_> = versions.into();
+    while let Some(version) = queue.pop_front() {
+        if plan.contains_project(&version.project_id) {
+            continue;
+        }
+        for dep in version.dependencies.iter() {
+            match dep.dependency_type {
+                DependencyType::Embedded => continue,
+                DependencyType::Incompatible => {
+                    plan.incompatible.push(dep.to_owned());
+                    continue;
+                }
+                DependencyType::Optional if !include_optional => {
+                    plan.optional.push(dep.to_owned());
+                    continue;
+                }
+                _ => {}
+            }
+            let in_plan = |project_id: &str| {
+                plan.contains_project(project_id)
+                    || queue.iter().any(|x| x.project_id == project_id)
+            };
+            let resolved = if let Some(version_id) = &dep.version_id {
+                let dep_version = get_version(version_id).await?;
+                if in_plan(&dep_version.project_id) {
+                    continue;
+                }
+                Some(dep_version)
+            } else if let Some(project_id) = &dep.project_id {
+                if in_plan(project_id) {
+                    continue;
+                }
+                get_mod_files_filtered(project_id, loaders, game_versions)
+                    .await?
+                    .into_iter()
+                    .next()
+            } else {
+                None
+            };
+            match resolved {
+                Some(dep_version) if !in_plan(&dep_version.project_id) => {
+                    queue.push_back(dep_version)
+                }
+                Some(_) => {}
+                None if dep.dependency_type == DependencyType::Required => {
+                    plan.unresolved.push(dep.to_owned())
+                }
+                None => plan.optional.push(dep.to_owned()),
+            }
+        }
+        plan.versions.push(version);
+    }
+    Ok(plan)
+}
+
+/// 计算文件的摘要，十六进制小写
+async fn file_hash(path: &Path, algo: HashAlgorithm) -> DynResult<String> {
+    let data = inner_future::fs::read(path).await?;
+    Ok(inner_future::unblock(move || match algo {
+        HashAlgorithm::Sha1 => sha1_smol::Sha1::from(data).hexdigest(),
+        HashAlgorithm::Sha512 => format!("{:x}", Sha512::digest(data)),
+    })
+    .await)
+}
+
+/// Modrinth 模组的下载特质
+///
+/// 你可以通过引入本特质和 [`crate::download::Downloader`] 来下载 Modrinth 上的模组到指定版本的模组文件夹中
+pub trait ModrinthDownloadExt: Sync {
+    /// 下载一个模组版本的主要文件到指定版本的模组文件夹中，返回文件的保存路径
+    ///
+    /// 下载后会校验文件的 SHA-512 摘要，没有 SHA-512 摘要时使用 SHA-1 摘要，两者都没有时会返回错误；
+    /// 已存在且摘要一致的文件不会重复下载
+    async fn download_modrinth_version(
+        &self,
+        version_name: &str,
+        version: &ModVersion,
+    ) -> DynResult<PathBuf>;
+    /// 下载计划中的所有模组到指定版本的模组文件夹中，返回所有文件的保存路径
+    ///
+    /// 会受到下载并发量的限制
+    async fn download_modrinth_plan(
+        &self,
+        version_name: &str,
+        plan: &DownloadPlan,
+    ) -> DynResult<Vec<PathBuf>>;
+}
+
+impl<R: Reporter> ModrinthDownloadExt for Downloader<R> {
+    async fn download_modrinth_version(
+        &self,
+        version_name: &str,
+        version: &ModVersion,
+    ) -> DynResult<PathBuf> {
+        let file = version
+            .primary_file()
+            .ok_or_else(|| anyhow::anyhow!("模组版本 {} 没有可下载的文件", version.name))?;
+        crate::utils::check_file_name(&file.filename)?;
+        let dest_path = self.mods_path(version_name).join(&file.filename);
+        let (algo, expected) = [HashAlgorithm::Sha512, HashAlgorithm::Sha1]
+            .into_iter()
+            .find_map(|algo| Some((algo, file.hashes.get(algo.as_str())?.to_owned())))
+            .ok_or_else(|| {
+                anyhow::anyhow!("模组文件 {} 没有提供 SHA-512 或 SHA-1 摘要", file.filename)
+            })?;
+        let l = self.parallel_lock.acquire().await;
+        let r = self.reporter.sub();
+        r.add_max_progress(1.);
+        r.set_message(format!("正在下载模组 {}", file.filename));
+        if dest_path.is_file()
+            && file_hash(&dest_path, algo)
+                .await?
+                .eq_ignore_ascii_case(&expected)
+        {
+            r.add_progress(1.);
+            return Ok(dest_path);
+        }
+        inner_future::fs::create_dir_all(dest_path.parent().unwrap()).await?;
+        crate::http::download(&[&file.url], &dest_path.to_string_lossy(), file.size as _).await?;
+        let current = file_hash(&dest_path, algo).await?;
+        if !current.eq_ignore_ascii_case(&expected) {
+            let _ = inner_future::fs::remove_file(&dest_path).await;
+            anyhow::bail!(
+                "模组文件 {} 校验失败，期望 {} 为 {}，实际为 {}",
+                file.filename,
+                algo.as_str().to_uppercase(),
+                expected,
+                current
+            );
+        }
+        r.add_progress(1.);
+        drop(l);
+        Ok(dest_path)
+    }
+
+    async fn download_modrinth_plan(
+        &self,
+        version_name: &str,
+        plan: &DownloadPlan,
+    ) -> DynResult<Vec<PathBuf>> {
+        futures::future::try_join_all(
+            plan.versions
+                .iter()
+                .map(|version| self.download_modrinth_version(version_name, version)),
+        )
+        .await
+    }
+}
+
+/// 通过摘要查询文件时使用的摘要算法
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum HashAlgorithm {
+    /// SHA-1
+    #[default]
+    Sha1,
+    /// SHA-512
+    Sha512,
+}
+
+impl HashAlgorithm {
+    fn as_str(self) -> &'static str {
+        match self {
+            HashAlgorithm::Sha1 => "sha1",
+            HashAlgorithm::Sha512 => "sha512",
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct VersionFilesBody<'a> {
+    hashes: &'a [String],
+    algorithm: &'static str,
+}
+
+/// 根据文件摘要批量获取文件所属的模组版本，返回的表的键为传入的摘要，找不到的文件不会出现在结果中
+pub async fn get_versions_by_hashes(
+    hashes: &[String],
+    algorithm: HashAlgorithm,
+) -> DynResult<HashMap<String, ModVersion>> {
+    if hashes.is_empty() {
+        return Ok(HashMap::new());
+    }
+    crate::http::post("https://api.modrinth.com/v2/version_files")
+        .body_json(&VersionFilesBody {
+            hashes,
+            algorithm: algorithm.as_str(),
+        })
+        .map_err(|e| anyhow::anyhow!(e))?
+        .recv_json()
+        .await
+        .map_err(|e| anyhow::anyhow!(e))
+}
+
+#[derive(Debug, Serialize)]
+struct VersionFilesUpdateBody<'a> {
+    hashes: &'a [String],
+    algorithm: &'static str,
+    loaders: &'a [String],
+    game_versions: &'a [String],
+}
+
+/// 根据文件摘要批量获取文件所属模组的最新版本，返回的表的键为传入的摘要
+///
+/// 可以指定模组加载器和游戏版本来筛选版本，传入空数组则不做筛选；如果文件已经是最新版本，则返回的是文件自身的版本
+pub async fn get_latest_versions_by_hashes(
+    hashes: &[String],
+    algorithm: HashAlgorithm,
+    loaders: &[String],
+    game_versions: &[String],
+) -> DynResult<HashMap<String, ModVersion>> {
+    if hashes.is_empty() {
+        return Ok(HashMap::new());
+    }
+    crate::http::post("https://api.modrinth.com/v2/version_files/update")
+        .body_json(&VersionFilesUpdateBody {
+            hashes,
+            algorithm: algorithm.as_str(),
+            loaders,
+            game_versions,
+        })
+        .map_err(|e| anyhow::anyhow!(e))?
+        .recv_json()
+        .await
+        .map_err(|e| anyhow::anyhow!(e))
+}
+
 /// 根据模组 ID 获取模组信息
 pub async fn get_mod_info(modid: &str) -> DynResult<ModResult> {
     crate::http::retry_get_json(format!("https://api.modrinth.com/v2/project/{modid}")).await
@@ -145,28 +753,86 @@ pub async fn get_mod_icon_by_url(url: &str) -> DynResult<DynamicImage> {
 }
 
 /// 根据搜索参数搜索模组
-pub async fn search_mods(
-    SearchParams {
-        search_filter,
-        index,
-        page_size,
-    }: SearchParams,
-) -> DynResult<Vec<ModResult>> {
-    let search_filter = urlencoding::encode(&search_filter);
-    let r: ModSearchResult = crate::http::get(format!(
-        "https://api.modrinth.com/v2/search?offset={}&limit={}&query={}",
-        (index - 1) * page_size,
-        page_size,
-        search_filter
+pub async fn search_mods(params: SearchParams) -> DynResult<ModSearchResult> {
+    let mut r: ModSearchResult = crate::http::get(format!(
+        "https://api.modrinth.com/v2/search?{}",
+        params.to_query()
     ))
     .recv_json()
     .await
     .map_err(|e| anyhow::anyhow!(e))?;
-    Ok(r.hits
-        .into_iter()
-        .map(|mut a| {
-            a.project_id = a.project_id.trim_start_matches("local-").to_owned();
-            a
-        })
-        .collect())
+    for hit in r.hits.iter_mut() {
+        hit.project_id = hit.project_id.trim_start_matches("local-").to_owned();
+    }
+    Ok(r)
+}
+
+#[test]
+fn search_params_test() {
+    let params = SearchParams {
+        search_filter: "sodium extra".into(),
+        project_type: Some(ProjectType::DataPack),
+        game_versions: vec!["1.20.1".into(), "1.20".into()],
+        loaders: vec!["fabric".into()],
+        client_side: vec![SideSupport::Required],
+        sort: SearchSortMethod::Downloads,
+        ..Default::default()
+    };
+    assert_eq!(
+        params.facets(),
+        [
+            vec!["project_type:mod"],
+            vec!["categories:datapack"],
+            vec!["versions:1.20.1", "versions:1.20"],
+            vec!["categories:fabric"],
+            vec!["client_side:required"],
+        ]
+    );
+    let query = params.to_query();
+    assert!(query.starts_with("offset=0&limit=10&index=downloads&query=sodium%20extra&facets="));
+
+    let params = SearchParams {
+        index: 3,
+        page_size: 20,
+        ..Default::default()
+    };
+    assert_eq!(params.to_query(), "offset=40&limit=20&index=relevance");
+
+    let result: ModSearchResult = serde_json::from_str(
+        r#"{"hits":[{"project_id":"AANobbMI","slug":"sodium","icon_url":null,"title":"Sodium","description":"","project_type":"mod","client_side":"required","server_side":"unsupported","downloads":100}],"offset":0,"limit":10,"total_hits":25}"#,
+    )
+    .unwrap();
+    assert_eq!(result.total_pages(), 3);
+    assert_eq!(result.hits[0].server_side, SideSupport::Unsupported);
+}
+
+#[test]
+fn download_plan_test() {
+    assert_eq!(version_list_query(&[], &[]), "");
+    assert_eq!(
+        version_list_query(&["fabric".into()], &["1.20.1".into()]),
+        "?loaders=%5B%22fabric%22%5D&game_versions=%5B%221.20.1%22%5D"
+    );
+
+    let version: ModVersion = serde_json::from_str(
+        r#"{"id":"b4hTi3mo","project_id":"YL57xq9U","name":"Iris 1.6.4","version_number":"1.6.4+1.20.1","changelog":null,"files":[{"url":"https://cdn.modrinth.com/a.jar","filename":"iris-sources.jar","primary":false,"hashes":{}},{"url":"https://cdn.modrinth.com/b.jar","filename":"iris-1.6.4.jar","primary":true,"hashes":{"sha512":"00"},"size":2}],"game_versions":["1.20.1"],"loaders":["fabric"],"dependencies":[{"version_id":null,"project_id":"AANobbMI","file_name":null,"dependency_type":"required"},{"project_id":"optifabric","dependency_type":"incompatible"}]}"#,
+    )
+    .unwrap();
+    assert_eq!(version.primary_file().unwrap().filename, "iris-1.6.4.jar");
+    assert_eq!(
+        version.dependencies[0].project_id.as_deref(),
+        Some("AANobbMI")
+    );
+    assert_eq!(
+        version.dependencies[1].dependency_type,
+        DependencyType::Incompatible
+    );
+
+    let plan = DownloadPlan {
+        incompatible: version.dependencies[1..].to_vec(),
+        versions: vec![version],
+        ..Default::default()
+    };
+    assert!(plan.conflicts().is_empty());
+    assert!(plan.contains_project("YL57xq9U"));
 }
@@ -107,16 +107,7 @@ impl<R: Reporter> OptifineDownloadExt for Downloader<R> {
         if as_mod {
             let mod_file_name =
                 format!("Optifine-{vanilla_version}-{optifine_type}-{optifine_patch}.jar");
-            let mod_path = if self.game_independent {
-                std::path::Path::new(&self.minecraft_version_path)
-                    .join(version_name)
-                    .join("mods")
-                    .join(mod_file_name)
-            } else {
-                std::path::Path::new(&self.minecraft_path)
-                    .join("mods")
-                    .join(mod_file_name)
-            };
+            let mod_path = self.mods_path(version_name).join(mod_file_name);
             inner_future::fs::create_dir_all(mod_path.parent().unwrap()).await?;
             self.download_optifine(
                 vanilla_version,
@@ -239,6 +239,16 @@ pub fn post(uri: impl AsRef<str>) -> RequestBuilder {
     GLOBAL_CLIENT.post(uri)
 }
 
+/// 生成简单的 PUT 请求
+pub fn put(uri: impl AsRef<str>) -> RequestBuilder {
+    GLOBAL_CLIENT.put(uri)
+}
+
+/// 生成简单的 DELETE 请求
+pub fn delete(uri: impl AsRef<str>) -> RequestBuilder {
+    GLOBAL_CLIENT.delete(uri)
+}
+
 /// 针对 Mojang 验证 API 的响应结构
 #[derive(Debug, Clone)]
 pub enum RequestResult<T> {
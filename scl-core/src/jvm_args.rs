@@ -0,0 +1,550 @@
+//! JVM 参数的启动前检查和常用的垃圾回收器参数预设
+//!
+//! 用户填写的 JVM 参数会被原样传递给 Java，一旦出现拼写错误或者
+//! 当前 Java 版本不支持的参数，游戏会在启动后立刻崩溃且很难排查原因，
+//! 所以在组合启动参数前可以使用 [`validate_jvm_args`] 提前检查一遍。
+use std::fmt::Display;
+
+use crate::{java::JavaRuntime, prelude::*};
+
+/// 一个 JVM 参数预设，可以在 [`crate::client::ClientConfig::jvm_args_preset`]
+/// 或 [`crate::version::structs::SCLLaunchConfig::jvm_args_preset`] 中指定，
+/// 由 [`crate::client::Client::new`] 展开成实际的参数
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum JvmArgsPreset {
+    /// 针对客户端调整过的 G1 垃圾回收器参数，适合大部分情况
+    #[serde(rename = "g1")]
+    G1Tuned,
+    /// 来自 Aikar 的 G1 垃圾回收器参数，原本用于服务端，对大型整合包也有不错的效果
+    ///
+    /// 会将初始内存设置为与最大内存相同
+    #[serde(rename = "aikar")]
+    Aikar,
+    /// 使用 ZGC 垃圾回收器，需要 Java 17 及以上
+    ///
+    /// 在 Java 21 和 22 上会同时启用分代模式
+    #[serde(rename = "zgc")]
+    ZGC,
+    /// 使用 Shenandoah 垃圾回收器，需要 Java 17 及以上
+    ///
+    /// 注意部分发行版（例如 Oracle JDK）并不包含该垃圾回收器
+    #[serde(rename = "shenandoah")]
+    Shenandoah,
+}
+
+impl JvmArgsPreset {
+    /// 该预设所需要的最低 Java 主版本号
+    pub fn required_java_version(&self) -> u8 {
+        match self {
+            Self::G1Tuned | Self::Aikar => 8,
+            Self::ZGC | Self::Shenandoah => 17,
+        }
+    }
+
+    /// 根据 Java 主版本号和最大内存（单位 MB）展开成 JVM 参数
+    ///
+    /// 如果 Java 版本低于 [`JvmArgsPreset::required_java_version`] 则返回错误，
+    /// 主版本号为 `0`（即未知版本）时不做检查
+    pub fn expand(&self, java_main_version: u8, max_mem: u32) -> DynResult<Vec<String>> {
+        if java_main_version != 0 && java_main_version < self.required_java_version() {
+            anyhow::bail!(
+                "JVM 参数预设 {} 需要 Java {} 及以上，当前为 Java {}",
+                self,
+                self.required_java_version(),
+                java_main_version
+            );
+        }
+        let args: Vec<String> = match self {
+            Self::G1Tuned => [
+                "-XX:+UseG1GC",
+                "-XX:+UnlockExperimentalVMOptions",
+                "-XX:G1NewSizePercent=20",
+                "-XX:G1ReservePercent=20",
+                "-XX:MaxGCPauseMillis=50",
+                "-XX:G1HeapRegionSize=16M",
+                "-XX:-OmitStackTraceInFastThrow",
+            ]
+            .iter()
+            .map(|x| x.to_string())
+            .collect(),
+            Self::Aikar => {
+                // https://docs.papermc.io/paper/aikars-flags
+                let large_heap = max_mem >= 12 * 1024;
+                vec![
+                    format!("-Xms{max_mem}m"),
+                    "-XX:+UseG1GC".into(),
+                    "-XX:+ParallelRefProcEnabled".into(),
+                    "-XX:MaxGCPauseMillis=200".into(),
+                    "-XX:+UnlockExperimentalVMOptions".into(),
+                    "-XX:+DisableExplicitGC".into(),
+                    "-XX:+AlwaysPreTouch".into(),
+                    format!("-XX:G1NewSizePercent={}", if large_heap { 40 } else { 30 }),
+                    format!(
+                        "-XX:G1MaxNewSizePercent={}",
+                        if large_heap { 50 } else { 40 }
+                    ),
+                    format!(
+                        "-XX:G1HeapRegionSize={}",
+                        if large_heap { "16M" } else { "8M" }
+                    ),
+                    format!("-XX:G1ReservePercent={}", if large_heap { 15 } else { 20 }),
+                    "-XX:G1HeapWastePercent=5".into(),
+                    "-XX:G1MixedGCCountTarget=4".into(),
+                    format!(
+                        "-XX:InitiatingHeapOccupancyPercent={}",
+                        if large_heap { 20 } else { 15 }
+                    ),
+                    "-XX:G1MixedGCLiveThresholdPercent=90".into(),
+                    "-XX:G1RSetUpdatingPauseTimePercent=5".into(),
+                    "-XX:SurvivorRatio=32".into(),
+                    "-XX:+PerfDisableSharedMem".into(),
+                    "-XX:MaxTenuringThreshold=1".into(),
+                ]
+            }
+            Self::ZGC => {
+                let mut args = vec!["-XX:+UseZGC".to_string()];
+                // Java 23 开始分代模式成为默认行为，Java 24 移除了该参数
+                if (21..23).contains(&java_main_version) {
+                    args.push("-XX:+ZGenerational".into());
+                }
+                args
+            }
+            Self::Shenandoah => vec!["-XX:+UseShenandoahGC".into()],
+        };
+        Ok(args)
+    }
+}
+
+impl Display for JvmArgsPreset {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::G1Tuned => write!(f, "G1（优化）"),
+            Self::Aikar => write!(f, "Aikar"),
+            Self::ZGC => write!(f, "ZGC"),
+            Self::Shenandoah => write!(f, "Shenandoah"),
+        }
+    }
+}
+
+/// 检查出的问题的严重程度
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum JvmArgSeverity {
+    /// 参数可能无效或被忽略，但不会阻止游戏启动
+    Warning,
+    /// 参数会导致 Java 虚拟机无法启动
+    Error,
+}
+
+/// 一个 JVM 参数检查出的问题
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JvmArgIssue {
+    /// 出现问题的参数
+    pub arg: String,
+    /// 问题的严重程度
+    pub severity: JvmArgSeverity,
+    /// 问题的描述
+    pub message: String,
+}
+
+impl JvmArgIssue {
+    fn warning(arg: &str, message: impl Into<String>) -> Self {
+        Self {
+            arg: arg.to_owned(),
+            severity: JvmArgSeverity::Warning,
+            message: message.into(),
+        }
+    }
+
+    fn error(arg: &str, message: impl Into<String>) -> Self {
+        Self {
+            arg: arg.to_owned(),
+            severity: JvmArgSeverity::Error,
+            message: message.into(),
+        }
+    }
+
+    /// 该问题是否会导致 Java 虚拟机无法启动
+    pub fn is_error(&self) -> bool {
+        self.severity == JvmArgSeverity::Error
+    }
+}
+
+impl Display for JvmArgIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}：{}", self.arg, self.message)
+    }
+}
+
+/// 检查 JVM 参数时需要的运行环境信息
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct JvmArgsTarget {
+    /// Java 主版本号，为 `0` 时跳过所有和版本相关的检查
+    pub java_main_version: u8,
+    /// Java 是否为 64 位
+    pub java_64bit: bool,
+    /// 启动时会传入的最大内存，单位 MB
+    pub max_mem: u32,
+}
+
+impl JvmArgsTarget {
+    /// 根据 Java 运行时和最大内存创建检查目标
+    pub fn new(java_runtime: &JavaRuntime, max_mem: u32) -> Self {
+        Self {
+            java_main_version: java_runtime.main_version(),
+            java_64bit: java_runtime.is_64bit(),
+            max_mem,
+        }
+    }
+}
+
+/// 一个 `-XX` 参数在不同 Java 版本中的支持情况，版本号为 `0` 代表不适用
+struct FlagRule {
+    name: &'static str,
+    /// 从哪个版本开始可用
+    since: u8,
+    /// 在哪些版本之前需要 `-XX:+UnlockExperimentalVMOptions`
+    experimental_before: u8,
+    /// 从哪个版本开始被忽略（会输出警告但仍可启动）
+    obsolete_in: u8,
+    /// 从哪个版本开始不被识别（无法启动）
+    expired_in: u8,
+    /// 部分发行版会将其向下移植到更早的版本上，此时低于 `since` 只作警告
+    backported: bool,
+}
+
+const fn rule(name: &'static str, since: u8, obsolete_in: u8, expired_in: u8) -> FlagRule {
+    FlagRule {
+        name,
+        since,
+        experimental_before: 0,
+        obsolete_in,
+        expired_in,
+        backported: false,
+    }
+}
+
+const XX_FLAG_RULES: &[FlagRule] = &[
+    FlagRule {
+        experimental_before: 15,
+        ..rule("UseZGC", 11, 0, 0)
+    },
+    rule("ZGenerational", 21, 24, 0),
+    FlagRule {
+        experimental_before: 15,
+        backported: true,
+        ..rule("UseShenandoahGC", 12, 0, 0)
+    },
+    FlagRule {
+        experimental_before: u8::MAX,
+        ..rule("UseEpsilonGC", 11, 0, 0)
+    },
+    rule("UseConcMarkSweepGC", 0, 14, 0),
+    rule("CMSIncrementalMode", 0, 9, 0),
+    rule("CMSClassUnloadingEnabled", 0, 14, 0),
+    rule("UseParNewGC", 0, 10, 0),
+    rule("UseParallelOldGC", 0, 15, 16),
+    rule("AggressiveOpts", 0, 12, 0),
+    rule("PermSize", 0, 8, 17),
+    rule("MaxPermSize", 0, 8, 17),
+    rule("UseSplitVerifier", 0, 8, 0),
+];
+
+/// 需要 `-XX:+UnlockExperimentalVMOptions` 的 G1 参数
+const EXPERIMENTAL_XX_FLAGS: &[&str] = &[
+    "G1NewSizePercent",
+    "G1MaxNewSizePercent",
+    "G1MixedGCLiveThresholdPercent",
+];
+
+/// 互相冲突的垃圾回收器开关
+const GC_FLAGS: &[&str] = &[
+    "UseSerialGC",
+    "UseParallelGC",
+    "UseParallelOldGC",
+    "UseConcMarkSweepGC",
+    "UseG1GC",
+    "UseZGC",
+    "UseShenandoahGC",
+    "UseEpsilonGC",
+];
+
+/// 获取垃圾回收器开关对应的回收器，`UseParallelOldGC` 是 `UseParallelGC` 的老年代回收器，视为同一个
+fn gc_collector(flag: &str) -> &str {
+    if flag == "UseParallelOldGC" {
+        "UseParallelGC"
+    } else {
+        flag
+    }
+}
+
+/// 需要在下一个参数中携带值的选项
+const OPTIONS_WITH_VALUE: &[&str] = &[
+    "-cp",
+    "-classpath",
+    "--class-path",
+    "-p",
+    "--module-path",
+    "--add-opens",
+    "--add-exports",
+    "--add-reads",
+    "--add-modules",
+    "--patch-module",
+];
+
+/// 仅在 Java 9 及以上可用的模块系统参数前缀
+const MODULE_OPTIONS: &[&str] = &[
+    "--add-opens",
+    "--add-exports",
+    "--add-reads",
+    "--add-modules",
+    "--patch-module",
+    "--module-path",
+    "--illegal-access",
+    "--enable-preview",
+    "--enable-native-access",
+];
+
+/// 解析形如 `512m`、`4G` 的内存大小，返回以 MB 为单位的数值
+pub(crate) fn parse_memory_size(value: &str) -> Option<u64> {
+    let (num, unit) = match value.char_indices().last()? {
+        (i, c) if c.is_ascii_alphabetic() => (&value[..i], c.to_ascii_lowercase()),
+        _ => (value, 'b'),
+    };
+    let num: u64 = num.parse().ok()?;
+    match unit {
+        'b' => Some(num / 1024 / 1024),
+        'k' => Some(num / 1024),
+        'm' => Some(num),
+        'g' => Some(num * 1024),
+        't' => Some(num * 1024 * 1024),
+        _ => None,
+    }
+}
+
+/// 检查 JVM 参数是否适用于指定的 Java 版本和内存设置
+///
+/// 返回检查出的所有问题，其中严重程度为 [`JvmArgSeverity::Error`] 的问题会导致
+/// Java 虚拟机无法启动，[`crate::client::Client::new`] 遇到这类问题时会直接返回错误。
+///
+/// 注意最大内存会由启动器在这些参数之后以 `-Xmx` 的形式传入，
+/// 所以参数中的 `-Xmx` 会被覆盖。
+pub fn validate_jvm_args(args: &[impl AsRef<str>], target: &JvmArgsTarget) -> Vec<JvmArgIssue> {
+    let mut issues = vec![];
+    let version = target.java_main_version;
+    let mut unlocked_experimental = false;
+    let mut enabled_gc: Vec<&str> = vec![];
+    let mut skip_next = false;
+
+    if target.max_mem == 0 {
+        issues.push(JvmArgIssue::error("-Xmx0m", "最大内存不能为 0"));
+    } else if !target.java_64bit && version != 0 && target.max_mem > 1536 {
+        issues.push(JvmArgIssue::error(
+            &format!("-Xmx{}m", target.max_mem),
+            "32 位 Java 最多只能分配约 1.5GB 的内存，请使用 64 位 Java 或降低最大内存",
+        ));
+    }
+
+    for arg in args.iter().map(|x| x.as_ref()) {
+        if skip_next {
+            skip_next = false;
+            continue;
+        }
+        if arg.is_empty() {
+            continue;
+        }
+        if !arg.starts_with('-') {
+            issues.push(JvmArgIssue::error(
+                arg,
+                "不是以 - 开头的参数会被 Java 当作主类名，请检查是否有拼写错误或多余的空格",
+            ));
+            continue;
+        }
+        if OPTIONS_WITH_VALUE.contains(&arg) {
+            skip_next = true;
+        }
+
+        if let Some(option) = MODULE_OPTIONS.iter().find(|x| arg.starts_with(*x)) {
+            if version != 0 && version < 9 {
+                issues.push(JvmArgIssue::error(
+                    arg,
+                    format!("{option} 需要 Java 9 及以上，当前为 Java {version}"),
+                ));
+            }
+        } else if let Some(flag) = arg.strip_prefix("-XX:") {
+            let (name, enabled) = if let Some(name) = flag.strip_prefix('+') {
+                (name, true)
+            } else if let Some(name) = flag.strip_prefix('-') {
+                (name, false)
+            } else if let Some((name, _)) = flag.split_once('=') {
+                (name, true)
+            } else {
+                issues.push(JvmArgIssue::error(
+                    arg,
+                    "-XX 参数格式应为 -XX:+名称、-XX:-名称 或 -XX:名称=值",
+                ));
+                continue;
+            };
+            if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+                issues.push(JvmArgIssue::error(arg, "无法识别的 -XX 参数名称"));
+                continue;
+            }
+            if name == "UnlockExperimentalVMOptions" {
+                unlocked_experimental = enabled;
+            }
+            if enabled
+                && GC_FLAGS.contains(&name)
+                && !enabled_gc
+                    .iter()
+                    .any(|x| gc_collector(x) == gc_collector(name))
+            {
+                enabled_gc.push(name);
+            }
+            if EXPERIMENTAL_XX_FLAGS.contains(&name) && !unlocked_experimental {
+                issues.push(JvmArgIssue::error(
+                    arg,
+                    "该参数为实验性参数，需要在其之前添加 -XX:+UnlockExperimentalVMOptions",
+                ));
+            }
+            if let Some(rule) = XX_FLAG_RULES.iter().find(|x| x.name == name) {
+                if version == 0 {
+                    continue;
+                }
+                if version < rule.since {
+                    let message = format!(
+                        "{name} 需要 Java {} 及以上，当前为 Java {version}",
+                        rule.since
+                    );
+                    if rule.backported {
+                        issues.push(JvmArgIssue::warning(
+                            arg,
+                            message + "，除非当前发行版自行移植了该功能",
+                        ));
+                    } else {
+                        issues.push(JvmArgIssue::error(arg, message));
+                    }
+                } else if rule.expired_in != 0 && version >= rule.expired_in {
+                    issues.push(JvmArgIssue::error(
+                        arg,
+                        format!("{name} 已在 Java {} 中移除", rule.expired_in),
+                    ));
+                } else if rule.obsolete_in != 0 && version >= rule.obsolete_in {
+                    issues.push(JvmArgIssue::warning(
+                        arg,
+                        format!("{name} 从 Java {} 开始不再生效，会被忽略", rule.obsolete_in),
+                    ));
+                } else if enabled && version < rule.experimental_before && !unlocked_experimental {
+                    issues.push(JvmArgIssue::error(
+                        arg,
+                        format!(
+                            "{name} 在 Java {version} 中为实验性功能，需要在其之前添加 -XX:+UnlockExperimentalVMOptions"
+                        ),
+                    ));
+                }
+            }
+        } else if arg == "-Xincgc" || arg == "-Xnoincgc" {
+            if version >= 9 {
+                issues.push(JvmArgIssue::error(arg, "该参数已在 Java 9 中移除"));
+            }
+        } else if let Some(value) = arg.strip_prefix("-Xmx") {
+            if parse_memory_size(value).is_none() {
+                issues.push(JvmArgIssue::error(arg, "无法识别的内存大小"));
+            } else {
+                issues.push(JvmArgIssue::warning(
+                    arg,
+                    format!(
+                        "最大内存会被启动器设置的 {}MB 覆盖，请在内存设置中修改",
+                        target.max_mem
+                    ),
+                ));
+            }
+        } else if let Some(value) = arg.strip_prefix("-Xms") {
+            match parse_memory_size(value) {
+                Some(size) if target.max_mem != 0 && size > target.max_mem as u64 => issues.push(
+                    JvmArgIssue::error(arg, format!("初始内存大于最大内存 {}MB", target.max_mem)),
+                ),
+                Some(_) => {}
+                None => issues.push(JvmArgIssue::error(arg, "无法识别的内存大小")),
+            }
+        } else if let Some(value) = arg
+            .strip_prefix("-Xmn")
+            .or_else(|| arg.strip_prefix("-Xss"))
+        {
+            if parse_memory_size(value).is_none() {
+                issues.push(JvmArgIssue::error(arg, "无法识别的内存大小"));
+            }
+        }
+    }
+
+    if enabled_gc.len() > 1 {
+        issues.push(JvmArgIssue::error(
+            &enabled_gc
+                .iter()
+                .map(|x| format!("-XX:+{x}"))
+                .collect::<Vec<_>>()
+                .join(" "),
+            "同时启用了多个垃圾回收器，Java 会拒绝启动",
+        ));
+    }
+
+    issues
+}
+
+#[test]
+fn validate_jvm_args_test() {
+    let java8 = JvmArgsTarget {
+        java_main_version: 8,
+        java_64bit: true,
+        max_mem: 4096,
+    };
+    let java17 = JvmArgsTarget {
+        java_main_version: 17,
+        ..java8
+    };
+
+    let issues = validate_jvm_args(&["-XX:+UseZGC"], &java8);
+    assert!(issues.iter().any(|x| x.is_error()));
+    assert!(validate_jvm_args(&["-XX:+UseZGC"], &java17).is_empty());
+
+    assert!(validate_jvm_args(&["-Xincgc"], &java17)[0].is_error());
+    assert!(validate_jvm_args(&["-Xincgc"], &java8).is_empty());
+
+    let issues = validate_jvm_args(&["-XX:+UseG1GC", "-XX:+UseZGC"], &java17);
+    assert_eq!(issues.len(), 1);
+    assert!(issues[0].is_error());
+
+    assert!(validate_jvm_args(&["-XX:+UseParallelGC", "-XX:+UseParallelOldGC"], &java8).is_empty());
+    assert!(
+        validate_jvm_args(&["-XX:+UseParallelOldGC", "-XX:+UseG1GC"], &java8)
+            .iter()
+            .any(|x| x.is_error())
+    );
+    assert!(validate_jvm_args(&["-XX:+UseParallelOldGC"], &java17)[0].is_error());
+
+    let issues = validate_jvm_args(&["-Xms8G"], &java17);
+    assert!(issues[0].is_error());
+    assert!(validate_jvm_args(&["-Xms1024m", "-Xss4M"], &java17).is_empty());
+
+    assert!(validate_jvm_args(&["-XX:G1NewSizePercent=20"], &java17)[0].is_error());
+    assert!(validate_jvm_args(&["XX:+UseG1GC"], &java17)[0].is_error());
+    assert!(
+        validate_jvm_args(&["--add-opens", "java.base/java.lang=ALL-UNNAMED"], &java17).is_empty()
+    );
+    assert!(
+        validate_jvm_args(&["--add-opens", "java.base/java.lang=ALL-UNNAMED"], &java8)[0]
+            .is_error()
+    );
+
+    for preset in [
+        JvmArgsPreset::G1Tuned,
+        JvmArgsPreset::Aikar,
+        JvmArgsPreset::ZGC,
+        JvmArgsPreset::Shenandoah,
+    ] {
+        let args = preset.expand(17, 4096).unwrap();
+        assert!(
+            validate_jvm_args(args.as_slice(), &java17).is_empty(),
+            "{preset}"
+        );
+    }
+    assert!(JvmArgsPreset::ZGC.expand(8, 4096).is_err());
+}
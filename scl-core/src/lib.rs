@@ -17,9 +17,15 @@
    - 下载 Fabric 模组安装器
    - 下载 Optifine 模组
    - 自定义启动参数
-   - 正版登录（Mojang, Microsoft（你需要自行获取到回调链接））
+   - 正版登录（Mojang, Microsoft（设备码或浏览器授权码登录））
+   - 多账户存储，令牌加密保存
+   - 外置登录服务器管理，支持拖放添加服务器
    - 多下载源（BMCLAPI MCBBS MC）
    - Curseforge 模组检索/下载
+   - Modrinth/CurseForge 整合包导入/导出
+   - 从 MultiMC/Prism、HMCL、PCL 和官方启动器导入实例
+   - 模组、资源包、光影包和数据包管理（启用/禁用、回收站、pack.mcmeta 兼容性检查）
+   - 模组配置方案，可在启动时切换启用的模组组合
 
    ## 部分引用的 JAR 的原仓库
 
@@ -37,6 +43,8 @@ pub mod client;
 pub mod download;
 pub mod http;
 pub mod java;
+pub mod jvm_args;
+pub mod modpack;
 pub mod password;
 pub mod progress;
 pub mod semver;
@@ -0,0 +1,367 @@
+//! CurseForge 整合包（包含 `manifest.json` 的压缩包）的导入
+//!
+//! 使用前需要先设置 CurseForge 的 API 密钥，参考 [`crate::download::curseforge::set_api_key`]
+
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use super::{extract_overrides, safe_join, ModpackLoader};
+use crate::{
+    download::{
+        curseforge::{self, ClassId, DistributionDisabledError, ModFile},
+        Downloader,
+    },
+    prelude::*,
+};
+
+/// 整合包清单文件在压缩包中的路径
+pub const MANIFEST_FILE_NAME: &str = "manifest.json";
+
+/// 清单中的一个模组加载器
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ManifestModLoader {
+    /// 模组加载器的 ID，例如 `forge-47.2.0`、`neoforge-20.4.80` 或 `fabric-0.15.7`
+    pub id: String,
+    /// 是否为主要的模组加载器
+    #[serde(default)]
+    pub primary: bool,
+}
+
+/// 清单中的游戏信息
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ManifestMinecraft {
+    /// 游戏版本
+    pub version: String,
+    /// 整合包使用的模组加载器
+    #[serde(default)]
+    pub mod_loaders: Vec<ManifestModLoader>,
+}
+
+/// 清单中需要下载的一个文件
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ManifestFile {
+    /// 文件所属项目的 ID
+    #[serde(rename = "projectID")]
+    pub project_id: u64,
+    /// 文件的 ID
+    #[serde(rename = "fileID")]
+    pub file_id: u64,
+    /// 是否必须安装，可选的文件不会被下载
+    #[serde(default = "default_required")]
+    pub required: bool,
+}
+
+fn default_required() -> bool {
+    true
+}
+
+fn default_overrides() -> String {
+    "overrides".into()
+}
+
+/// 整合包的清单文件 `manifest.json`
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CurseForgeManifest {
+    /// 游戏信息
+    pub minecraft: ManifestMinecraft,
+    /// 清单类型，目前只有 `minecraftModpack`
+    pub manifest_type: String,
+    /// 清单格式版本，目前只有 `1`
+    pub manifest_version: u32,
+    /// 整合包的名称
+    #[serde(default)]
+    pub name: String,
+    /// 整合包自身的版本号
+    #[serde(default)]
+    pub version: String,
+    /// 整合包的作者
+    #[serde(default)]
+    pub author: String,
+    /// 整合包中需要下载的文件
+    #[serde(default)]
+    pub files: Vec<ManifestFile>,
+    /// 覆盖文件夹在压缩包中的路径
+    #[serde(default = "default_overrides")]
+    pub overrides: String,
+}
+
+impl CurseForgeManifest {
+    /// 整合包所需的模组加载器，优先使用标记为主要的模组加载器
+    pub fn loader(&self) -> DynResult<Option<ModpackLoader>> {
+        let Some(loader) = self
+            .minecraft
+            .mod_loaders
+            .iter()
+            .find(|x| x.primary)
+            .or_else(|| self.minecraft.mod_loaders.first())
+        else {
+            return Ok(None);
+        };
+        let (name, version) = loader
+            .id
+            .split_once('-')
+            .ok_or_else(|| anyhow::anyhow!("无法识别的模组加载器 {}", loader.id))?;
+        // 1.20.1 的 NeoForge 整合包会在版本号前带上游戏版本号，例如 neoforge-1.20.1-47.1.84
+        // 这类版本可以识别，但安装时会由 ModpackLoader::check_installable 返回不支持的错误
+        let version = version
+            .strip_prefix(&format!("{}-", self.minecraft.version))
+            .unwrap_or(version)
+            .to_owned();
+        Ok(Some(match name {
+            "forge" => ModpackLoader::Forge(version),
+            "neoforge" => ModpackLoader::NeoForge(version),
+            "fabric" => ModpackLoader::Fabric(version),
+            "quilt" => ModpackLoader::Quilt(version),
+            _ => anyhow::bail!("不支持的模组加载器 {}", loader.id),
+        }))
+    }
+
+    /// 需要下载的文件
+    pub fn required_files(&self) -> impl Iterator<Item = &ManifestFile> {
+        self.files.iter().filter(|x| x.required)
+    }
+
+    fn check(&self) -> DynResult {
+        if self.manifest_type != "minecraftModpack" {
+            anyhow::bail!("不支持的整合包类型 {}", self.manifest_type);
+        }
+        if self.manifest_version != 1 {
+            anyhow::bail!("不支持的整合包清单版本 {}", self.manifest_version);
+        }
+        Ok(())
+    }
+}
+
+/// 读取整合包中的清单文件
+pub async fn read_curseforge_manifest(
+    modpack_path: impl AsRef<Path>,
+) -> DynResult<CurseForgeManifest> {
+    let modpack_path = modpack_path.as_ref().to_owned();
+    inner_future::unblock(move || {
+        let mut archive = zip::ZipArchive::new(std::fs::File::open(modpack_path)?)?;
+        let manifest: CurseForgeManifest =
+            serde_json::from_reader(archive.by_name(MANIFEST_FILE_NAME)?)?;
+        manifest.check()?;
+        Ok(manifest)
+    })
+    .await
+}
+
+/// 根据项目类型获取文件应当放置的文件夹，获取不到项目类型时视为模组
+///
+/// 存档、整合包等无法直接放入游戏文件夹的类型会返回 `None`
+fn class_folder(class_id: Option<u64>) -> Option<&'static str> {
+    match class_id.map(ClassId::from_id) {
+        None | Some(Some(ClassId::Mods)) => Some("mods"),
+        Some(Some(ClassId::ResourcePacks)) => Some("resourcepacks"),
+        Some(Some(ClassId::Shaders)) => Some("shaderpacks"),
+        Some(Some(ClassId::ModPacks | ClassId::Worlds) | None) => None,
+    }
+}
+
+/// 一个作者禁止了第三方下载，需要用户手动下载的文件
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ManualDownload {
+    /// 文件的信息，可以通过 [`DistributionDisabledError::website_url`] 获取手动下载的网页链接
+    pub info: DistributionDisabledError,
+    /// 文件下载后应当放置的路径
+    pub dest_path: PathBuf,
+}
+
+/// CurseForge 整合包的导入结果
+#[derive(Debug, Clone)]
+pub struct CurseForgeImportResult {
+    /// 整合包的清单
+    pub manifest: CurseForgeManifest,
+    /// 需要用户手动下载的文件，导入时会跳过这些文件
+    pub manual_downloads: Vec<ManualDownload>,
+    /// 项目类型不受支持（例如存档）而被跳过的文件
+    pub unsupported_files: Vec<ManifestFile>,
+}
+
+/// CurseForge 整合包的导入
+pub trait CurseForgeImportExt: Sync {
+    /// 将整合包导入为一个新的版本
+    ///
+    /// 会先批量获取所有文件的下载信息，再安装整合包所需的原版和模组加载器，下载所有文件并应用覆盖文件夹，
+    /// 最后为该版本开启版本独立。作者禁止了第三方下载的文件和不支持的项目类型的文件会被跳过并在结果中返回。
+    ///
+    /// 如果版本已存在则会返回错误，导入失败时会删除已创建的版本文件夹
+    async fn import_curseforge_modpack(
+        &self,
+        modpack_path: impl AsRef<Path>,
+        version_name: &str,
+    ) -> DynResult<CurseForgeImportResult>;
+}
+
+impl<R: Reporter> CurseForgeImportExt for Downloader<R> {
+    async fn import_curseforge_modpack(
+        &self,
+        modpack_path: impl AsRef<Path>,
+        version_name: &str,
+    ) -> DynResult<CurseForgeImportResult> {
+        let modpack_path = modpack_path.as_ref().to_owned();
+        let manifest = read_curseforge_manifest(&modpack_path).await?;
+        let loader = manifest.loader()?;
+        if let Some(loader) = &loader {
+            loader.check_installable(&manifest.minecraft.version)?;
+        }
+        let version_dir = self.version_dir(version_name);
+        if version_dir.exists() {
+            anyhow::bail!("版本 {} 已存在", version_name);
+        }
+
+        let r = self.reporter.fork();
+        r.set_message(format!("正在解析整合包 {} 的文件", manifest.name));
+        let entries: Vec<_> = manifest.required_files().collect();
+        let file_ids: Vec<_> = entries.iter().map(|x| x.file_id).collect();
+        let mut mod_ids: Vec<_> = entries.iter().map(|x| x.project_id).collect();
+        mod_ids.sort_unstable();
+        mod_ids.dedup();
+        let (files, mods) = futures::try_join!(
+            curseforge::get_mod_files_by_ids(&file_ids),
+            curseforge::get_mods_info(&mod_ids)
+        )?;
+
+        let mut downloads: Vec<(ModFile, PathBuf)> = Vec::with_capacity(entries.len());
+        let mut manual_downloads = vec![];
+        let mut unsupported_files = vec![];
+        for entry in entries {
+            let file = files
+                .iter()
+                .find(|x| x.id == entry.file_id)
+                .ok_or_else(|| {
+                    anyhow::anyhow!(
+                        "CurseForge 上找不到整合包文件 {}（项目 {}）",
+                        entry.file_id,
+                        entry.project_id
+                    )
+                })?;
+            let class_id = mods
+                .iter()
+                .find(|x| x.id == entry.project_id)
+                .and_then(|x| x.class_id);
+            let Some(folder) = class_folder(class_id) else {
+                tracing::warn!(
+                    "跳过不支持的整合包文件 {}（项目 {}，类型 {:?}）",
+                    file.file_name,
+                    entry.project_id,
+                    class_id
+                );
+                unsupported_files.push(entry.to_owned());
+                continue;
+            };
+            let dest_path = safe_join(&version_dir, &format!("{}/{}", folder, file.file_name))
+                .ok_or_else(|| anyhow::anyhow!("整合包文件名 {} 不安全", file.file_name))?;
+            if file.is_distribution_allowed() {
+                downloads.push((file.to_owned(), dest_path));
+            } else {
+                manual_downloads.push(ManualDownload {
+                    info: DistributionDisabledError {
+                        mod_id: entry.project_id,
+                        file_id: entry.file_id,
+                        file_name: file.file_name.to_owned(),
+                    },
+                    dest_path,
+                });
+            }
+        }
+
+        let result = async {
+            self.install_modpack_game(version_name, &manifest.minecraft.version, loader.as_ref())
+                .await?;
+
+            r.set_message("正在下载整合包文件".into());
+            futures::future::try_join_all(downloads.iter().map(|(file, dest_path)| async move {
+                let l = self.parallel_lock.acquire().await;
+                curseforge::download_mod(self.reporter.to_owned(), file, dest_path.to_owned())
+                    .await?;
+                drop(l);
+                DynResult::Ok(())
+            }))
+            .await?;
+
+            r.set_message("正在应用整合包覆盖文件".into());
+            let overrides = manifest.overrides.to_owned();
+            let version_dir = version_dir.to_owned();
+            inner_future::unblock(move || -> DynResult {
+                let mut archive = zip::ZipArchive::new(std::fs::File::open(modpack_path)?)?;
+                extract_overrides(&mut archive, &overrides, &version_dir)?;
+                Ok(())
+            })
+            .await?;
+
+            self.enable_game_independent(version_name).await
+        }
+        .await;
+        self.cleanup_failed_import(version_name, result).await?;
+
+        for manual in &manual_downloads {
+            tracing::warn!("{}", manual.info);
+        }
+        Ok(CurseForgeImportResult {
+            manifest,
+            manual_downloads,
+            unsupported_files,
+        })
+    }
+}
+
+#[test]
+fn curseforge_manifest_test() {
+    let mut manifest: CurseForgeManifest = serde_json::from_str(
+        r#"{
+            "minecraft": {
+                "version": "1.20.1",
+                "modLoaders": [
+                    { "id": "fabric-0.15.7", "primary": false },
+                    { "id": "forge-47.2.0", "primary": true }
+                ]
+            },
+            "manifestType": "minecraftModpack",
+            "manifestVersion": 1,
+            "name": "Test Pack",
+            "version": "1.0.0",
+            "author": "SCL",
+            "files": [
+                { "projectID": 238222, "fileID": 4712866, "required": true },
+                { "projectID": 394468, "fileID": 4681877, "required": false },
+                { "projectID": 306612, "fileID": 4596743 }
+            ]
+        }"#,
+    )
+    .unwrap();
+    manifest.check().unwrap();
+    assert_eq!(manifest.overrides, "overrides");
+    assert_eq!(
+        manifest.loader().unwrap(),
+        Some(ModpackLoader::Forge("47.2.0".into()))
+    );
+    let required: Vec<_> = manifest.required_files().map(|x| x.file_id).collect();
+    assert_eq!(required, [4712866, 4596743]);
+
+    manifest.minecraft.mod_loaders = vec![ManifestModLoader {
+        id: "neoforge-1.20.1-47.1.84".into(),
+        primary: true,
+    }];
+    let loader = manifest.loader().unwrap().unwrap();
+    assert_eq!(loader, ModpackLoader::NeoForge("47.1.84".into()));
+    assert!(loader.check_installable("1.20.1").is_err());
+    assert!(ModpackLoader::NeoForge("20.4.80".into())
+        .check_installable("1.20.4")
+        .is_ok());
+    manifest.minecraft.mod_loaders[0].id = "liteloader-1.12.2".into();
+    assert!(manifest.loader().is_err());
+    manifest.minecraft.mod_loaders.clear();
+    assert_eq!(manifest.loader().unwrap(), None);
+
+    assert_eq!(class_folder(Some(6)), Some("mods"));
+    assert_eq!(class_folder(Some(12)), Some("resourcepacks"));
+    assert_eq!(class_folder(Some(6552)), Some("shaderpacks"));
+    assert_eq!(class_folder(Some(17)), None);
+    assert_eq!(class_folder(Some(6945)), None);
+    assert_eq!(class_folder(None), Some("mods"));
+}
@@ -0,0 +1,405 @@
+//! 将版本导出为 Modrinth 或 CurseForge 整合包
+//!
+//! 模组、资源包和光影包会先通过文件摘要在对应平台上识别，能识别的文件只会在清单中引用，
+//! 其余被选中的文件会被打包进覆盖文件夹中。无法连接到平台（或者没有设置 CurseForge API 密钥）时，
+//! 所有文件都会被打包进覆盖文件夹中，导出依然可以完成。
+
+use std::{
+    collections::HashMap,
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use zip::{write::FileOptions, ZipWriter};
+
+use super::{
+    curseforge::{CurseForgeManifest, ManifestFile, ManifestMinecraft, ManifestModLoader},
+    mrpack::{MrpackFile, MrpackIndex},
+    ModpackLoader,
+};
+use crate::{
+    download::{curseforge, modrinth},
+    prelude::*,
+    version::{mod_identify::ModHashes, structs::VersionInfo},
+};
+
+/// 默认导出的文件
+pub const DEFAULT_INCLUDE: &[&str] = &[
+    "mods/*.jar",
+    "config",
+    "defaultconfigs",
+    "kubejs",
+    "resourcepacks/*.zip",
+    "shaderpacks/*.zip",
+    "options.txt",
+];
+
+/// 会尝试在平台上识别的文件夹，其中的文件识别成功后只会在清单中引用
+const IDENTIFIABLE_DIRS: &[&str] = &["mods", "resourcepacks", "shaderpacks"];
+
+/// 导出的整合包格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ModpackFormat {
+    /// Modrinth 整合包（`.mrpack`）
+    #[default]
+    Modrinth,
+    /// CurseForge 整合包（包含 `manifest.json` 的 `.zip`）
+    CurseForge,
+}
+
+/// 导出整合包时的选项
+#[derive(Debug, Clone)]
+pub struct ExportOptions {
+    /// 导出的整合包格式
+    pub format: ModpackFormat,
+    /// 整合包的名称
+    pub name: String,
+    /// 整合包自身的版本号
+    pub version: String,
+    /// 整合包的作者，仅 CurseForge 整合包使用
+    pub author: String,
+    /// 整合包的简介，仅 Modrinth 整合包使用
+    pub summary: Option<String>,
+    /// 需要导出的文件，为相对于游戏目录的路径通配符，参考 [`glob_match`]
+    pub include: Vec<String>,
+    /// 需要排除的文件，规则同 [`ExportOptions::include`]，优先级高于它
+    pub exclude: Vec<String>,
+}
+
+impl Default for ExportOptions {
+    fn default() -> Self {
+        Self {
+            format: ModpackFormat::default(),
+            name: String::new(),
+            version: "1.0.0".into(),
+            author: String::new(),
+            summary: None,
+            include: DEFAULT_INCLUDE.iter().map(|x| x.to_string()).collect(),
+            exclude: vec![],
+        }
+    }
+}
+
+/// 整合包的导出结果
+#[derive(Debug, Clone, Default)]
+pub struct ExportResult {
+    /// 在平台上识别成功，只在清单中引用的文件，为相对于游戏目录的路径
+    pub referenced: Vec<String>,
+    /// 被打包进覆盖文件夹中的文件，为相对于游戏目录的路径
+    pub overrides: Vec<String>,
+    /// 是否因为无法连接到平台而跳过了文件识别，此时所有文件都会被打包进覆盖文件夹中
+    pub identification_skipped: bool,
+}
+
+/// 判断一个以 `/` 分隔的相对路径是否匹配通配符
+///
+/// `*` 匹配一段路径中的任意字符，`?` 匹配一段路径中的任意一个字符，`**` 匹配任意段路径。
+/// 如果通配符匹配了路径的某个上级文件夹，也视为匹配，例如 `config` 会匹配 `config/sodium.json`
+pub fn glob_match(pattern: &str, path: &str) -> bool {
+    fn match_segment(pattern: &[char], text: &[char]) -> bool {
+        match pattern.split_first() {
+            None => text.is_empty(),
+            Some(('*', rest)) => (0..=text.len()).any(|i| match_segment(rest, &text[i..])),
+            Some(('?', rest)) => !text.is_empty() && match_segment(rest, &text[1..]),
+            Some((c, rest)) => text.first() == Some(c) && match_segment(rest, &text[1..]),
+        }
+    }
+    fn match_segments(pattern: &[&str], path: &[&str]) -> bool {
+        match pattern.split_first() {
+            None => path.is_empty(),
+            Some((&"**", rest)) => (0..=path.len()).any(|i| match_segments(rest, &path[i..])),
+            Some((segment, rest)) => {
+                let Some((first, path_rest)) = path.split_first() else {
+                    return false;
+                };
+                let segment: Vec<char> = segment.chars().collect();
+                let first: Vec<char> = first.chars().collect();
+                match_segment(&segment, &first) && match_segments(rest, path_rest)
+            }
+        }
+    }
+    let pattern: Vec<&str> = pattern.split('/').filter(|x| !x.is_empty()).collect();
+    let path: Vec<&str> = path.split('/').filter(|x| !x.is_empty()).collect();
+    (1..=path.len()).any(|i| match_segments(&pattern, &path[..i]))
+}
+
+/// 递归列出文件夹下的所有文件，返回以 `/` 分隔的相对路径，并按路径排序
+fn list_files(base: &Path) -> DynResult<Vec<String>> {
+    fn walk(base: &Path, dir: &Path, result: &mut Vec<String>) -> DynResult {
+        for entry in std::fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                walk(base, &path, result)?;
+            } else if let Ok(relative) = path.strip_prefix(base) {
+                let relative: Vec<_> = relative
+                    .components()
+                    .map(|x| x.as_os_str().to_string_lossy())
+                    .collect();
+                result.push(relative.join("/"));
+            }
+        }
+        Ok(())
+    }
+    let mut result = vec![];
+    if base.is_dir() {
+        walk(base, base, &mut result)?;
+    }
+    result.sort();
+    Ok(result)
+}
+
+fn mrpack_dependencies(
+    minecraft_version: &str,
+    loader: Option<&ModpackLoader>,
+) -> HashMap<String, String> {
+    let mut dependencies = HashMap::new();
+    dependencies.insert("minecraft".into(), minecraft_version.to_owned());
+    if let Some(loader) = loader {
+        let id = match loader {
+            ModpackLoader::Fabric(_) => "fabric-loader",
+            ModpackLoader::Quilt(_) => "quilt-loader",
+            ModpackLoader::Forge(_) => "forge",
+            ModpackLoader::NeoForge(_) => "neoforge",
+        };
+        dependencies.insert(id.into(), loader.version().to_owned());
+    }
+    dependencies
+}
+
+fn curseforge_mod_loader(loader: &ModpackLoader) -> ManifestModLoader {
+    let name = match loader {
+        ModpackLoader::Fabric(_) => "fabric",
+        ModpackLoader::Quilt(_) => "quilt",
+        ModpackLoader::Forge(_) => "forge",
+        ModpackLoader::NeoForge(_) => "neoforge",
+    };
+    ManifestModLoader {
+        id: format!("{}-{}", name, loader.version()),
+        primary: true,
+    }
+}
+
+/// 将版本导出为整合包，保存到指定路径
+///
+/// 游戏版本和模组加载器版本会从版本元数据中读取，游戏目录会根据版本是否开启版本独立决定，
+/// 参考 [`VersionInfo::version_path`]
+pub async fn export_modpack(
+    version: &VersionInfo,
+    dest: impl AsRef<Path>,
+    options: &ExportOptions,
+) -> DynResult<ExportResult> {
+    let meta = version
+        .meta
+        .as_ref()
+        .ok_or_else(|| anyhow::anyhow!("版本 {} 的元数据尚未加载", version.version))?;
+    let loader = ModpackLoader::from_version_meta(meta);
+    let minecraft_version = version.minecraft_version.to_string();
+    let game_dir = version.version_path();
+    let dest = dest.as_ref().to_owned();
+
+    let files: Vec<String> = {
+        let game_dir = game_dir.to_owned();
+        inner_future::unblock(move || list_files(&game_dir)).await?
+    }
+    .into_iter()
+    .filter(|x| {
+        options.include.iter().any(|p| glob_match(p, x))
+            && !options.exclude.iter().any(|p| glob_match(p, x))
+    })
+    .filter(|x| game_dir.join(x) != dest)
+    .collect();
+
+    let mut candidates = vec![];
+    for file in &files {
+        if IDENTIFIABLE_DIRS
+            .iter()
+            .any(|dir| file.split_once('/').map(|x| x.0) == Some(*dir))
+        {
+            candidates.push((
+                file.to_owned(),
+                ModHashes::from_path(game_dir.join(file)).await?,
+            ));
+        }
+    }
+
+    let mut result = ExportResult::default();
+    let manifest = match options.format {
+        ModpackFormat::Modrinth => {
+            let sha512: Vec<_> = candidates.iter().map(|x| x.1.sha512.clone()).collect();
+            let versions =
+                match modrinth::get_versions_by_hashes(&sha512, modrinth::HashAlgorithm::Sha512)
+                    .await
+                {
+                    Ok(versions) => versions,
+                    Err(err) => {
+                        tracing::warn!(
+                            "无法从 Modrinth 识别文件，所有文件都将打包进覆盖文件夹：{:?}",
+                            err
+                        );
+                        result.identification_skipped = true;
+                        Default::default()
+                    }
+                };
+            let mut index_files = vec![];
+            for (path, hashes) in &candidates {
+                let Some(file) = versions.get(&hashes.sha512).and_then(|v| {
+                    v.files
+                        .iter()
+                        .find(|x| x.hashes.get("sha512") == Some(&hashes.sha512))
+                }) else {
+                    continue;
+                };
+                index_files.push(MrpackFile {
+                    path: path.to_owned(),
+                    hashes: HashMap::from([
+                        ("sha1".into(), hashes.sha1.to_owned()),
+                        ("sha512".into(), hashes.sha512.to_owned()),
+                    ]),
+                    env: None,
+                    downloads: vec![file.url.to_owned()],
+                    file_size: file.size,
+                });
+                result.referenced.push(path.to_owned());
+            }
+            serde_json::to_vec_pretty(&MrpackIndex {
+                format_version: 1,
+                game: "minecraft".into(),
+                version_id: options.version.to_owned(),
+                name: options.name.to_owned(),
+                summary: options.summary.to_owned(),
+                files: index_files,
+                dependencies: mrpack_dependencies(&minecraft_version, loader.as_ref()),
+            })?
+        }
+        ModpackFormat::CurseForge => {
+            let fingerprints: Vec<_> = candidates
+                .iter()
+                .map(|x| x.1.curseforge_fingerprint)
+                .collect();
+            let matches = match curseforge::get_fingerprint_matches(&fingerprints).await {
+                Ok(matches) => matches,
+                Err(err) => {
+                    tracing::warn!(
+                        "无法从 CurseForge 识别文件，所有文件都将打包进覆盖文件夹：{:?}",
+                        err
+                    );
+                    result.identification_skipped = true;
+                    vec![]
+                }
+            };
+            let mut manifest_files = vec![];
+            for (path, hashes) in &candidates {
+                let Some(matched) = matches
+                    .iter()
+                    .find(|x| x.file.file_fingerprint == hashes.curseforge_fingerprint)
+                else {
+                    continue;
+                };
+                manifest_files.push(ManifestFile {
+                    project_id: matched.id,
+                    file_id: matched.file.id,
+                    required: true,
+                });
+                result.referenced.push(path.to_owned());
+            }
+            serde_json::to_vec_pretty(&CurseForgeManifest {
+                minecraft: ManifestMinecraft {
+                    version: minecraft_version,
+                    mod_loaders: loader.iter().map(curseforge_mod_loader).collect(),
+                },
+                manifest_type: "minecraftModpack".into(),
+                manifest_version: 1,
+                name: options.name.to_owned(),
+                version: options.version.to_owned(),
+                author: options.author.to_owned(),
+                files: manifest_files,
+                overrides: "overrides".into(),
+            })?
+        }
+    };
+    result.overrides = files
+        .into_iter()
+        .filter(|x| !result.referenced.contains(x))
+        .collect();
+
+    let manifest_name = match options.format {
+        ModpackFormat::Modrinth => super::mrpack::INDEX_FILE_NAME,
+        ModpackFormat::CurseForge => super::curseforge::MANIFEST_FILE_NAME,
+    };
+    let overrides = result.overrides.to_owned();
+    inner_future::unblock(move || -> DynResult {
+        if let Some(parent) = dest.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let mut zip = ZipWriter::new(std::fs::File::create(&dest)?);
+        let options = FileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+        zip.start_file(manifest_name, options)?;
+        zip.write_all(&manifest)?;
+        for file in overrides {
+            zip.start_file(format!("overrides/{file}"), options)?;
+            let mut source = std::fs::File::open(game_dir.join(&file))?;
+            std::io::copy(&mut source, &mut zip)?;
+        }
+        zip.finish()?;
+        Ok(())
+    })
+    .await?;
+    Ok(result)
+}
+
+/// 获取整合包导出后默认的文件名，Modrinth 整合包为 `.mrpack`，CurseForge 整合包为 `.zip`
+pub fn default_file_name(options: &ExportOptions) -> PathBuf {
+    let extension = match options.format {
+        ModpackFormat::Modrinth => "mrpack",
+        ModpackFormat::CurseForge => "zip",
+    };
+    PathBuf::from(format!(
+        "{}-{}.{}",
+        options.name, options.version, extension
+    ))
+}
+
+#[test]
+fn export_options_test() {
+    assert!(glob_match("mods/*.jar", "mods/sodium-fabric-0.5.3.jar"));
+    assert!(!glob_match("mods/*.jar", "mods/sodium.jar.disabled"));
+    assert!(!glob_match("mods/*.jar", "mods/nested/sodium.jar"));
+    assert!(glob_match("config", "config/sodium-options.json"));
+    assert!(glob_match("config/**", "config/a/b/c.toml"));
+    assert!(glob_match("**/*.toml", "config/a/b/c.toml"));
+    assert!(glob_match("options?.txt", "options1.txt"));
+    assert!(!glob_match("options.txt", "optionsof.txt"));
+    assert!(!glob_match("config", "configs/a.json"));
+
+    assert_eq!(
+        mrpack_dependencies("1.20.1", Some(&ModpackLoader::Fabric("0.15.7".into()))),
+        HashMap::from([
+            ("minecraft".into(), "1.20.1".into()),
+            ("fabric-loader".into(), "0.15.7".into())
+        ])
+    );
+    assert_eq!(
+        curseforge_mod_loader(&ModpackLoader::NeoForge("20.4.80".into())).id,
+        "neoforge-20.4.80"
+    );
+    assert_eq!(
+        default_file_name(&ExportOptions {
+            name: "Test".into(),
+            ..Default::default()
+        }),
+        PathBuf::from("Test-1.0.0.mrpack")
+    );
+
+    let meta: crate::version::structs::VersionMeta = serde_json::from_str(
+        r#"{
+            "arguments": { "game": ["--launchTarget", "forgeclient", "--fml.forgeVersion", "47.2.0"] },
+            "libraries": [{ "name": "net.minecraftforge:fmlloader:1.20.1-47.2.0" }],
+            "mainClass": "cpw.mods.bootstraplauncher.BootstrapLauncher"
+        }"#,
+    )
+    .unwrap();
+    assert_eq!(
+        ModpackLoader::from_version_meta(&meta),
+        Some(ModpackLoader::Forge("47.2.0".into()))
+    );
+}
@@ -0,0 +1,152 @@
+//! 读取 HMCL 的版本设置
+//!
+//! HMCL 会将每个版本的独立设置保存在版本文件夹中的 `hmclversion.cfg` 里，格式为 JSON
+
+use std::path::Path;
+
+use super::{apply_jvm_args, apply_wrapper, read_version_source, InstanceImport};
+use crate::prelude::*;
+
+/// 将 `hmclversion.cfg` 中的设置映射到实例信息中
+fn map_settings(
+    minecraft_dir: &Path,
+    version_name: &str,
+    settings: &serde_json::Value,
+    result: &mut InstanceImport,
+) {
+    let str_value = |key: &str| settings[key].as_str().filter(|x| !x.is_empty());
+    let version_dir = minecraft_dir.join("versions").join(version_name);
+    // 0 为公共游戏目录，1 为版本文件夹，2 为自定义目录
+    result.game_dir = Some(match settings["gameDirType"].as_u64() {
+        Some(1) => version_dir,
+        Some(2) => str_value("gameDir")
+            .map(Into::into)
+            .unwrap_or_else(|| minecraft_dir.to_owned()),
+        _ => minecraft_dir.to_owned(),
+    });
+    if settings["usesGlobal"].as_bool() == Some(true) {
+        result
+            .unmapped
+            .push("该版本使用了 HMCL 的全局游戏设置，这些设置不会被导入".into());
+        return;
+    }
+
+    let config = &mut result.launch_config;
+    if let Some(args) = str_value("javaArgs") {
+        apply_jvm_args(config, args);
+    }
+    if let Some(args) = str_value("minecraftArgs") {
+        config.game_args = args.to_owned();
+    }
+    if settings["autoMemory"].as_bool() != Some(true) {
+        if let Some(max_mem) = settings["maxMemory"].as_u64() {
+            config.max_mem = Some(max_mem as _);
+        }
+    }
+    if let Some(min_mem) = settings["minMemory"].as_u64() {
+        apply_jvm_args(config, &format!("-Xms{min_mem}m"));
+    }
+    if settings["java"].as_str() == Some("Custom") {
+        if let Some(java_dir) = str_value("javaDir") {
+            config.java_path = java_dir.to_owned();
+        }
+    }
+    if let Some(wrapper) = str_value("wrapper") {
+        apply_wrapper(config, wrapper);
+    }
+    if let Some(command) = str_value("precalledCommand") {
+        result.unmapped.push(format!("启动前执行的命令 {command}"));
+    }
+    if let Some(server) = str_value("serverIp") {
+        result
+            .unmapped
+            .push(format!("启动后自动进入服务器 {server}"));
+    }
+    if settings["fullscreen"].as_bool() == Some(true) {
+        result.unmapped.push("全屏启动".into());
+    } else if let (Some(width), Some(height)) =
+        (settings["width"].as_u64(), settings["height"].as_u64())
+    {
+        result.unmapped.push(format!("窗口大小 {width}x{height}"));
+    }
+}
+
+/// 读取 HMCL 游戏文件夹中指定版本的设置，`minecraft_dir` 为 `.minecraft` 文件夹
+///
+/// 游戏版本和模组加载器会从版本元数据中读取，版本没有独立设置时只会导入版本本身
+pub async fn read_hmcl_version(
+    minecraft_dir: impl AsRef<Path>,
+    version_name: &str,
+) -> DynResult<InstanceImport> {
+    let minecraft_dir = minecraft_dir.as_ref();
+    let (minecraft_version, loader) =
+        read_version_source(&minecraft_dir.join("versions"), version_name).await?;
+    let mut result = InstanceImport {
+        name: version_name.to_owned(),
+        minecraft_version,
+        loader,
+        game_dir: Some(minecraft_dir.to_owned()),
+        ..Default::default()
+    };
+    let settings_path = minecraft_dir
+        .join("versions")
+        .join(version_name)
+        .join("hmclversion.cfg");
+    if settings_path.is_file() {
+        let data = inner_future::fs::read_to_string(settings_path).await?;
+        let settings: serde_json::Value =
+            serde_json::from_str(data.trim_start_matches('\u{feff}'))?;
+        map_settings(minecraft_dir, version_name, &settings, &mut result);
+    }
+    Ok(result)
+}
+
+#[test]
+fn hmcl_version_test() {
+    let settings = serde_json::json!({
+        "usesGlobal": false,
+        "javaArgs": "-XX:+UseZGC -Xmx4096m",
+        "minecraftArgs": "--demo",
+        "autoMemory": false,
+        "maxMemory": 6144,
+        "java": "Custom",
+        "javaDir": "C:\\Java\\bin\\javaw.exe",
+        "gameDirType": 1,
+        "wrapper": "",
+        "precalledCommand": "",
+        "width": 854,
+        "height": 480,
+        "fullscreen": false,
+        "serverIp": "mc.example.com"
+    });
+    let mut result = InstanceImport::default();
+    map_settings(
+        Path::new(".minecraft"),
+        "1.20.1-Forge",
+        &settings,
+        &mut result,
+    );
+    assert_eq!(
+        result.game_dir,
+        Some(Path::new(".minecraft/versions/1.20.1-Forge").to_owned())
+    );
+    assert_eq!(result.launch_config.jvm_args, "-XX:+UseZGC");
+    assert_eq!(result.launch_config.max_mem, Some(6144));
+    assert_eq!(result.launch_config.game_args, "--demo");
+    assert_eq!(result.launch_config.java_path, "C:\\Java\\bin\\javaw.exe");
+    assert_eq!(
+        result.unmapped,
+        ["启动后自动进入服务器 mc.example.com", "窗口大小 854x480"]
+    );
+
+    let mut result = InstanceImport::default();
+    map_settings(
+        Path::new(".minecraft"),
+        "1.20.1",
+        &serde_json::json!({ "usesGlobal": true, "maxMemory": 1024 }),
+        &mut result,
+    );
+    assert_eq!(result.game_dir, Some(Path::new(".minecraft").to_owned()));
+    assert_eq!(result.launch_config.max_mem, None);
+    assert_eq!(result.unmapped.len(), 1);
+}
@@ -0,0 +1,310 @@
+//! 从其它启动器导入游戏实例
+//!
+//! 各个启动器的读取函数只负责将实例的设置映射为 [`InstanceImport`]，
+//! 再通过 [`InstanceImportExt::import_instance`] 安装或复用所需的版本并复制游戏文件。
+
+pub mod hmcl;
+pub mod multimc;
+pub mod official;
+pub mod pcl;
+
+use std::path::{Path, PathBuf};
+
+use super::ModpackLoader;
+use crate::{
+    download::Downloader,
+    prelude::*,
+    version::structs::{SCLLaunchConfig, VersionInfo, VersionMeta},
+};
+
+/// 复制游戏目录时会跳过的文件夹，这些文件夹由启动器管理
+const SKIPPED_DIRS: &[&str] = &[
+    "versions",
+    "libraries",
+    "assets",
+    "logs",
+    "crash-reports",
+    "runtime",
+    "webcache",
+    "webcache2",
+    "PCL",
+];
+
+/// 复制游戏目录时会跳过的文件，这些文件是各个启动器自己的配置文件
+const SKIPPED_FILES: &[&str] = &["hmclversion.cfg", "clientId.txt", "treatment_tags.json"];
+
+/// 判断游戏目录顶层的文件或文件夹是否应当跳过
+///
+/// 除了 [`SKIPPED_DIRS`] 和 [`SKIPPED_FILES`] 以外，还会跳过原生库文件夹、官方启动器的 `launcher_*` 文件，
+/// 以及游戏目录为版本文件夹时（例如开启了版本隔离的 HMCL 和 PCL 版本）其中的版本 JAR 和元数据文件
+fn is_skipped(game_dir: &Path, name: &str, is_dir: bool) -> bool {
+    if is_dir {
+        return SKIPPED_DIRS.contains(&name)
+            || name.starts_with("natives")
+            || name.ends_with("-natives");
+    }
+    if SKIPPED_FILES.contains(&name) || name.starts_with("launcher_") {
+        return true;
+    }
+    let version_name = game_dir
+        .file_name()
+        .and_then(|x| x.to_str())
+        .unwrap_or_default();
+    !version_name.is_empty()
+        && (name == format!("{version_name}.jar") || name == format!("{version_name}.json"))
+}
+
+/// 从其它启动器读取到的实例信息
+#[derive(Debug, Clone, Default)]
+pub struct InstanceImport {
+    /// 实例的名称，可以作为导入后的版本名称
+    pub name: String,
+    /// 实例的游戏版本
+    pub minecraft_version: String,
+    /// 实例使用的模组加载器
+    pub loader: Option<ModpackLoader>,
+    /// 实例的游戏目录，导入时会将其中的文件复制到版本文件夹中，为 `None` 时不复制文件
+    pub game_dir: Option<PathBuf>,
+    /// 映射后的启动设置
+    pub launch_config: SCLLaunchConfig,
+    /// 无法映射的设置，每项为一句说明
+    pub unmapped: Vec<String>,
+}
+
+/// 实例的导入结果
+#[derive(Debug, Clone, Default)]
+pub struct InstanceImportResult {
+    /// 是否复用了已存在的同名版本，否则为新安装的版本
+    pub reused_version: bool,
+    /// 复制的游戏文件数量
+    pub copied_files: usize,
+    /// 无法映射的设置，参考 [`InstanceImport::unmapped`]
+    pub unmapped: Vec<String>,
+}
+
+/// 将 JVM 参数中的 `-Xmx` 提取为最大内存，其余参数作为额外的 JVM 参数追加到启动设置中
+pub(crate) fn apply_jvm_args(config: &mut SCLLaunchConfig, args: &str) {
+    let Ok(args) = shell_words::split(args) else {
+        config.jvm_args = args.trim().to_owned();
+        return;
+    };
+    let mut rest = vec![];
+    for arg in args {
+        match arg
+            .strip_prefix("-Xmx")
+            .and_then(crate::jvm_args::parse_memory_size)
+        {
+            Some(size) => config.max_mem = Some(size as _),
+            None => rest.push(arg),
+        }
+    }
+    if !config.jvm_args.is_empty() {
+        rest.insert(0, config.jvm_args.to_owned());
+    }
+    config.jvm_args = rest.join(" ");
+}
+
+/// 将包装器命令拆分为包装器路径和参数
+pub(crate) fn apply_wrapper(config: &mut SCLLaunchConfig, command: &str) {
+    let mut parts = shell_words::split(command)
+        .unwrap_or_else(|_| command.split_whitespace().map(str::to_owned).collect())
+        .into_iter();
+    if let Some(path) = parts.next() {
+        config.wrapper_path = path;
+        config.wrapper_args = shell_words::join(parts);
+    }
+}
+
+/// 读取其它启动器版本文件夹中的版本元数据，返回游戏版本和模组加载器
+///
+/// 游戏版本会依次尝试 `inheritsFrom`、`clientVersion` 和 HMCL 的 `patches` 字段，都没有时视为和版本名称相同
+pub(crate) async fn read_version_source(
+    versions_dir: &Path,
+    version_name: &str,
+) -> DynResult<(String, Option<ModpackLoader>)> {
+    let meta_path = versions_dir
+        .join(version_name)
+        .join(format!("{version_name}.json"));
+    let data = inner_future::fs::read_to_string(&meta_path).await?;
+    let data = data.trim_start_matches('\u{feff}');
+    let meta: VersionMeta = serde_json::from_str(data)?;
+    let raw: serde_json::Value = serde_json::from_str(data)?;
+    let patch_version = raw["patches"].as_array().and_then(|patches| {
+        patches
+            .iter()
+            .find(|x| x["id"] == "game")
+            .and_then(|x| x["version"].as_str())
+    });
+    let minecraft_version = [meta.inherits_from.as_str(), meta.client_version.as_str()]
+        .into_iter()
+        .find(|x| !x.is_empty())
+        .or(patch_version)
+        .unwrap_or(version_name)
+        .to_owned();
+    Ok((minecraft_version, ModpackLoader::from_version_meta(&meta)))
+}
+
+/// 递归复制游戏目录，跳过由启动器管理的文件，参考 [`is_skipped`]，返回复制的文件数量
+fn copy_game_dir(from: &Path, to: &Path, top_level: bool) -> DynResult<usize> {
+    // 游戏目录和版本文件夹相同时（例如导入同一个游戏文件夹中的 PCL 版本）不需要复制
+    if top_level
+        && matches!(
+            (std::fs::canonicalize(from), std::fs::canonicalize(to)),
+            (Ok(from), Ok(to)) if from == to
+        )
+    {
+        return Ok(0);
+    }
+    let mut count = 0;
+    std::fs::create_dir_all(to)?;
+    for entry in std::fs::read_dir(from)? {
+        let entry = entry?;
+        let path = entry.path();
+        let name = entry.file_name();
+        if top_level && is_skipped(from, &name.to_string_lossy(), path.is_dir()) {
+            continue;
+        }
+        if path.is_dir() {
+            count += copy_game_dir(&path, &to.join(&name), false)?;
+        } else {
+            std::fs::copy(&path, to.join(&name))?;
+            count += 1;
+        }
+    }
+    Ok(count)
+}
+
+/// 从其它启动器导入实例
+pub trait InstanceImportExt: Sync {
+    /// 将实例导入为指定名称的版本
+    ///
+    /// 如果同名版本已存在，且游戏版本和模组加载器都和实例一致，则会复用该版本，否则返回错误；
+    /// 版本不存在时会安装所需的原版和模组加载器，导入失败时会删除新建的版本文件夹。
+    ///
+    /// 实例的游戏文件会被复制到版本文件夹中，并为该版本开启版本独立
+    async fn import_instance(
+        &self,
+        instance: &InstanceImport,
+        version_name: &str,
+    ) -> DynResult<InstanceImportResult>;
+}
+
+impl<R: Reporter> InstanceImportExt for Downloader<R> {
+    async fn import_instance(
+        &self,
+        instance: &InstanceImport,
+        version_name: &str,
+    ) -> DynResult<InstanceImportResult> {
+        let version_dir = self.version_dir(version_name);
+        let mut version_info = VersionInfo {
+            version_base: self.minecraft_version_path.to_owned(),
+            version: version_name.to_owned(),
+            ..Default::default()
+        };
+        let reused_version = version_dir.exists();
+        if reused_version {
+            version_info.load().await?;
+            let (minecraft_version, loader) =
+                read_version_source(Path::new(&self.minecraft_version_path), version_name).await?;
+            if minecraft_version != instance.minecraft_version || loader != instance.loader {
+                anyhow::bail!(
+                    "版本 {} 已存在，但其游戏版本或模组加载器和实例 {} 不一致",
+                    version_name,
+                    instance.name
+                );
+            }
+        }
+
+        let r = self.reporter.fork();
+        r.set_message(format!("正在导入实例 {}", instance.name));
+        let result = async {
+            if !reused_version {
+                self.install_modpack_game(
+                    version_name,
+                    &instance.minecraft_version,
+                    instance.loader.as_ref(),
+                )
+                .await?;
+                version_info.load().await?;
+            }
+
+            let copied_files = match &instance.game_dir {
+                Some(game_dir) if game_dir.is_dir() => {
+                    r.set_message("正在复制实例的游戏文件".into());
+                    let (from, to) = (game_dir.to_owned(), version_dir.to_owned());
+                    inner_future::unblock(move || copy_game_dir(&from, &to, true)).await?
+                }
+                _ => 0,
+            };
+
+            let mut config = instance.launch_config.to_owned();
+            config.game_independent = true;
+            version_info.scl_launch_config = Some(config);
+            version_info.save().await?;
+            Ok(copied_files)
+        }
+        .await;
+        let copied_files = if reused_version {
+            result?
+        } else {
+            self.cleanup_failed_import(version_name, result).await?
+        };
+
+        Ok(InstanceImportResult {
+            reused_version,
+            copied_files,
+            unmapped: instance.unmapped.to_owned(),
+        })
+    }
+}
+
+#[test]
+fn copy_game_dir_test() {
+    let dir = std::env::temp_dir().join(format!("scl-instance-copy-test-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let game_dir = dir.join(".minecraft").join("versions").join("Pack");
+    for path in [
+        "mods/sodium.jar",
+        "saves/World/level.dat",
+        "config/sodium.json",
+        "options.txt",
+        "Pack.jar",
+        "Pack.json",
+        "hmclversion.cfg",
+        "launcher_profiles.json",
+        "launcher_accounts.json",
+        "PCL/Setup.ini",
+        "Pack-natives/lwjgl.dll",
+        "natives-windows-x86_64/lwjgl.dll",
+        "runtime/java-runtime-gamma/bin/java",
+        "webcache2/Cache/data_0",
+        "logs/latest.log",
+    ] {
+        let path = game_dir.join(path);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, b"").unwrap();
+    }
+    let dest = dir.join("dest");
+    assert_eq!(copy_game_dir(&game_dir, &dest, true).unwrap(), 4);
+    for path in [
+        "config/sodium.json",
+        "mods/sodium.jar",
+        "options.txt",
+        "saves/World/level.dat",
+    ] {
+        assert!(dest.join(path).is_file());
+    }
+    for path in [
+        "Pack.jar",
+        "PCL",
+        "Pack-natives",
+        "runtime",
+        "launcher_profiles.json",
+    ] {
+        assert!(!dest.join(path).exists());
+    }
+    assert_eq!(copy_game_dir(&dest, &dest, true).unwrap(), 0);
+    assert!(dest.join("options.txt").is_file());
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
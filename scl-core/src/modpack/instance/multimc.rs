@@ -0,0 +1,205 @@
+//! 读取 MultiMC 和 Prism Launcher 的实例
+//!
+//! 实例文件夹中的 `instance.cfg` 保存了实例设置，`mmc-pack.json` 保存了实例使用的组件及其版本
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use serde::Deserialize;
+
+use super::{apply_jvm_args, apply_wrapper, InstanceImport};
+use crate::{modpack::ModpackLoader, prelude::*};
+
+/// 实例使用的一个组件
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PackComponent {
+    /// 组件的 ID，例如 `net.minecraft` 或 `net.fabricmc.fabric-loader`
+    pub uid: String,
+    /// 组件的版本
+    #[serde(default)]
+    pub version: String,
+    /// 组件的显示名称
+    #[serde(default)]
+    pub cached_name: String,
+}
+
+/// 实例的组件列表文件 `mmc-pack.json`
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MmcPack {
+    /// 实例使用的组件
+    #[serde(default)]
+    pub components: Vec<PackComponent>,
+}
+
+/// 会被忽略的组件，这些组件会随游戏或模组加载器一起安装
+const IGNORED_COMPONENTS: &[&str] = &[
+    "org.lwjgl",
+    "org.lwjgl3",
+    "net.fabricmc.intermediary",
+    "org.quiltmc.hashed",
+];
+
+/// 解析 `instance.cfg`，其格式为带有可选节的 `键=值` 形式
+fn parse_instance_cfg(data: &str) -> HashMap<String, String> {
+    data.lines()
+        .map(str::trim)
+        .filter(|x| !x.is_empty() && !x.starts_with('[') && !x.starts_with('#'))
+        .filter_map(|x| x.split_once('='))
+        .map(|(k, v)| {
+            let v = v.trim();
+            let v = v
+                .strip_prefix('"')
+                .and_then(|x| x.strip_suffix('"'))
+                .unwrap_or(v);
+            (k.trim().to_owned(), v.to_owned())
+        })
+        .collect()
+}
+
+fn map_instance(
+    instance_dir: &Path,
+    cfg: &HashMap<String, String>,
+    pack: &MmcPack,
+) -> InstanceImport {
+    let mut result = InstanceImport {
+        name: cfg.get("name").cloned().unwrap_or_else(|| {
+            instance_dir
+                .file_name()
+                .map(|x| x.to_string_lossy().into_owned())
+                .unwrap_or_default()
+        }),
+        ..Default::default()
+    };
+
+    for component in &pack.components {
+        let version = component.version.to_owned();
+        match component.uid.as_str() {
+            "net.minecraft" => result.minecraft_version = version,
+            "net.minecraftforge" => result.loader = Some(ModpackLoader::Forge(version)),
+            // 1.20.1 的 NeoForge 组件版本号形如 47.1.84，可以复用已有的版本，但无法重新安装
+            "net.neoforged" => result.loader = Some(ModpackLoader::NeoForge(version)),
+            "net.fabricmc.fabric-loader" => result.loader = Some(ModpackLoader::Fabric(version)),
+            "org.quiltmc.quilt-loader" => result.loader = Some(ModpackLoader::Quilt(version)),
+            uid if IGNORED_COMPONENTS.contains(&uid) => {}
+            uid => result.unmapped.push(format!(
+                "组件 {} {}",
+                if component.cached_name.is_empty() {
+                    uid
+                } else {
+                    &component.cached_name
+                },
+                component.version
+            )),
+        }
+    }
+
+    // 对应的覆盖开关为 false 时，设置值只是全局设置的副本，不需要导入
+    let get = |override_key: &str, key: &str| {
+        cfg.get(key)
+            .filter(|x| !x.is_empty())
+            .filter(|_| cfg.get(override_key).map(String::as_str) != Some("false"))
+    };
+    let config = &mut result.launch_config;
+    if let Some(args) = get("OverrideJavaArgs", "JvmArgs") {
+        apply_jvm_args(config, args);
+    }
+    if let Some(max_mem) = get("OverrideMemory", "MaxMemAlloc").and_then(|x| x.parse().ok()) {
+        config.max_mem = Some(max_mem);
+    }
+    if let Some(min_mem) = get("OverrideMemory", "MinMemAlloc") {
+        apply_jvm_args(config, &format!("-Xms{min_mem}m"));
+    }
+    if let Some(java_path) = get("OverrideJavaLocation", "JavaPath") {
+        config.java_path = java_path.to_owned();
+    }
+    if let Some(wrapper) = get("OverrideCommands", "WrapperCommand") {
+        apply_wrapper(config, wrapper);
+    }
+    for (key, description) in [
+        ("PreLaunchCommand", "启动前执行的命令"),
+        ("PostExitCommand", "退出后执行的命令"),
+    ] {
+        if let Some(command) = get("OverrideCommands", key) {
+            result.unmapped.push(format!("{description} {command}"));
+        }
+    }
+    if let (Some(width), Some(height)) = (
+        get("OverrideWindow", "MinecraftWinWidth"),
+        get("OverrideWindow", "MinecraftWinHeight"),
+    ) {
+        result.unmapped.push(format!("窗口大小 {width}x{height}"));
+    }
+    if let Some(server) = get("JoinServerOnLaunch", "JoinServerOnLaunchAddress") {
+        result
+            .unmapped
+            .push(format!("启动后自动进入服务器 {server}"));
+    }
+
+    result.game_dir = [".minecraft", "minecraft"]
+        .iter()
+        .map(|x| instance_dir.join(x))
+        .find(|x| x.is_dir());
+    result
+}
+
+/// 读取 MultiMC 或 Prism Launcher 的实例文件夹
+pub async fn read_multimc_instance(instance_dir: impl AsRef<Path>) -> DynResult<InstanceImport> {
+    let instance_dir: PathBuf = instance_dir.as_ref().to_owned();
+    let cfg = inner_future::fs::read_to_string(instance_dir.join("instance.cfg")).await?;
+    let pack = inner_future::fs::read_to_string(instance_dir.join("mmc-pack.json")).await?;
+    let pack: MmcPack = serde_json::from_str(&pack)?;
+    let result = map_instance(&instance_dir, &parse_instance_cfg(&cfg), &pack);
+    if result.minecraft_version.is_empty() {
+        anyhow::bail!("实例 {} 没有指定游戏版本", result.name);
+    }
+    Ok(result)
+}
+
+#[test]
+fn multimc_instance_test() {
+    let cfg = parse_instance_cfg(
+        r#"[General]
+InstanceType=OneSix
+name="All The Mods"
+OverrideJavaArgs=true
+JvmArgs=-XX:+UseG1GC -Xmx6G
+OverrideMemory=true
+MaxMemAlloc=8192
+MinMemAlloc=2048
+OverrideJavaLocation=false
+JavaPath=/usr/bin/java
+OverrideCommands=true
+WrapperCommand=gamemoderun --verbose
+PreLaunchCommand=echo start
+"#,
+    );
+    let pack: MmcPack = serde_json::from_str(
+        r#"{
+            "components": [
+                { "uid": "org.lwjgl3", "version": "3.3.1" },
+                { "uid": "net.minecraft", "version": "1.20.1" },
+                { "uid": "net.minecraftforge", "version": "47.2.0" },
+                { "uid": "com.example.custom", "version": "1.0", "cachedName": "Custom Patch" }
+            ],
+            "formatVersion": 1
+        }"#,
+    )
+    .unwrap();
+    let result = map_instance(Path::new("instances/atm"), &cfg, &pack);
+    assert_eq!(result.name, "All The Mods");
+    assert_eq!(result.minecraft_version, "1.20.1");
+    assert_eq!(result.loader, Some(ModpackLoader::Forge("47.2.0".into())));
+    assert_eq!(result.launch_config.max_mem, Some(8192));
+    assert_eq!(result.launch_config.jvm_args, "-XX:+UseG1GC -Xms2048m");
+    assert_eq!(result.launch_config.java_path, "");
+    assert_eq!(result.launch_config.wrapper_path, "gamemoderun");
+    assert_eq!(result.launch_config.wrapper_args, "--verbose");
+    assert_eq!(
+        result.unmapped,
+        ["组件 Custom Patch 1.0", "启动前执行的命令 echo start"]
+    );
+}
@@ -0,0 +1,186 @@
+//! 读取官方启动器的启动配置
+//!
+//! 官方启动器会将所有启动配置保存在 `.minecraft/launcher_profiles.json` 中
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use serde::Deserialize;
+
+use super::{apply_jvm_args, read_version_source, InstanceImport};
+use crate::{modpack::ModpackLoader, prelude::*};
+
+/// 启动配置的窗口分辨率
+#[derive(Debug, Clone, Deserialize)]
+pub struct ProfileResolution {
+    /// 窗口宽度
+    pub width: u32,
+    /// 窗口高度
+    pub height: u32,
+}
+
+/// 官方启动器的一个启动配置
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LauncherProfile {
+    /// 启动配置的名称
+    #[serde(default)]
+    pub name: String,
+    /// 启动配置的类型，`custom` 为自定义配置，`latest-release` 和 `latest-snapshot` 为始终使用最新版本的配置
+    #[serde(default, rename = "type")]
+    pub profile_type: String,
+    /// 启动配置使用的版本名称
+    #[serde(default)]
+    pub last_version_id: String,
+    /// 启动配置的游戏目录，为空时使用 `.minecraft` 文件夹
+    #[serde(default)]
+    pub game_dir: Option<PathBuf>,
+    /// 额外的 JVM 参数
+    #[serde(default)]
+    pub java_args: Option<String>,
+    /// Java 运行时路径
+    #[serde(default)]
+    pub java_dir: Option<String>,
+    /// 窗口分辨率
+    #[serde(default)]
+    pub resolution: Option<ProfileResolution>,
+}
+
+#[derive(Debug, Deserialize)]
+struct LauncherProfiles {
+    #[serde(default)]
+    profiles: HashMap<String, LauncherProfile>,
+}
+
+/// 在没有版本元数据时，根据官方启动器和各个安装器的命名规则从版本名称推断游戏版本和模组加载器
+///
+/// 例如 `1.20.1-forge-47.2.0`、`fabric-loader-0.15.7-1.20.1`、`quilt-loader-0.23.1-1.20.1`
+fn guess_version_id(version_id: &str) -> (String, Option<ModpackLoader>) {
+    if let Some((game, forge)) = version_id.split_once("-forge-") {
+        return (
+            game.to_owned(),
+            Some(ModpackLoader::Forge(forge.to_owned())),
+        );
+    }
+    for (prefix, loader) in [
+        (
+            "fabric-loader-",
+            ModpackLoader::Fabric as fn(String) -> ModpackLoader,
+        ),
+        ("quilt-loader-", ModpackLoader::Quilt),
+    ] {
+        if let Some((loader_version, game)) = version_id
+            .strip_prefix(prefix)
+            .and_then(|x| x.split_once('-'))
+        {
+            return (game.to_owned(), Some(loader(loader_version.to_owned())));
+        }
+    }
+    (version_id.to_owned(), None)
+}
+
+async fn map_profile(minecraft_dir: &Path, profile: &LauncherProfile) -> InstanceImport {
+    let versions_dir = minecraft_dir.join("versions");
+    let (minecraft_version, loader) =
+        match read_version_source(&versions_dir, &profile.last_version_id).await {
+            Ok(source) => source,
+            Err(_) => guess_version_id(&profile.last_version_id),
+        };
+    let mut result = InstanceImport {
+        name: if profile.name.is_empty() {
+            profile.last_version_id.to_owned()
+        } else {
+            profile.name.to_owned()
+        },
+        minecraft_version,
+        loader,
+        game_dir: Some(
+            profile
+                .game_dir
+                .to_owned()
+                .unwrap_or_else(|| minecraft_dir.to_owned()),
+        ),
+        ..Default::default()
+    };
+    if let Some(args) = &profile.java_args {
+        apply_jvm_args(&mut result.launch_config, args);
+    }
+    if let Some(java_dir) = &profile.java_dir {
+        result.launch_config.java_path = java_dir.to_owned();
+    }
+    if let Some(resolution) = &profile.resolution {
+        result.unmapped.push(format!(
+            "窗口大小 {}x{}",
+            resolution.width, resolution.height
+        ));
+    }
+    result
+}
+
+/// 读取官方启动器的所有启动配置，`minecraft_dir` 为 `.minecraft` 文件夹
+///
+/// 始终使用最新版本的启动配置没有固定的游戏版本，会被忽略
+pub async fn read_official_profiles(
+    minecraft_dir: impl AsRef<Path>,
+) -> DynResult<Vec<InstanceImport>> {
+    let minecraft_dir = minecraft_dir.as_ref();
+    let data =
+        inner_future::fs::read_to_string(minecraft_dir.join("launcher_profiles.json")).await?;
+    let profiles: LauncherProfiles = serde_json::from_str(data.trim_start_matches('\u{feff}'))?;
+    let mut profiles: Vec<_> = profiles
+        .profiles
+        .into_values()
+        .filter(|x| !x.last_version_id.is_empty() && !x.profile_type.starts_with("latest-"))
+        .collect();
+    profiles.sort_by(|a, b| a.name.cmp(&b.name));
+    let mut result = Vec::with_capacity(profiles.len());
+    for profile in &profiles {
+        result.push(map_profile(minecraft_dir, profile).await);
+    }
+    Ok(result)
+}
+
+#[test]
+fn official_profiles_test() {
+    assert_eq!(
+        guess_version_id("1.20.1-forge-47.2.0"),
+        ("1.20.1".into(), Some(ModpackLoader::Forge("47.2.0".into())))
+    );
+    assert_eq!(
+        guess_version_id("fabric-loader-0.15.7-1.20.1"),
+        (
+            "1.20.1".into(),
+            Some(ModpackLoader::Fabric("0.15.7".into()))
+        )
+    );
+    assert_eq!(guess_version_id("1.20.4"), ("1.20.4".into(), None));
+
+    let profiles: LauncherProfiles = serde_json::from_str(
+        r#"{
+            "profiles": {
+                "a1b2": {
+                    "name": "Fabric",
+                    "type": "custom",
+                    "lastVersionId": "fabric-loader-0.15.7-1.20.1",
+                    "gameDir": "/home/steve/fabric",
+                    "javaArgs": "-Xmx4G -XX:+UseG1GC",
+                    "resolution": { "width": 1280, "height": 720 }
+                },
+                "c3d4": { "type": "latest-release", "lastVersionId": "latest-release" }
+            },
+            "version": 3
+        }"#,
+    )
+    .unwrap();
+    let profile = &profiles.profiles["a1b2"];
+    let result = inner_future::block_on(map_profile(Path::new("/nonexistent/.minecraft"), profile));
+    assert_eq!(result.name, "Fabric");
+    assert_eq!(result.minecraft_version, "1.20.1");
+    assert_eq!(result.loader, Some(ModpackLoader::Fabric("0.15.7".into())));
+    assert_eq!(result.game_dir, Some(PathBuf::from("/home/steve/fabric")));
+    assert_eq!(result.launch_config.max_mem, Some(4096));
+    assert_eq!(result.launch_config.jvm_args, "-XX:+UseG1GC");
+    assert_eq!(result.unmapped, ["窗口大小 1280x720"]);
+}
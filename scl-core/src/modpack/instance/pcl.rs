@@ -0,0 +1,121 @@
+//! 读取 PCL 的版本设置
+//!
+//! PCL 会将每个版本的独立设置保存在版本文件夹中的 `PCL/Setup.ini` 里，格式为每行一个 `键:值`
+
+use std::{collections::HashMap, path::Path};
+
+use super::{apply_jvm_args, read_version_source, InstanceImport};
+use crate::prelude::*;
+
+/// 版本文件夹中存在这些文件夹时，视为该版本开启了版本隔离
+const ISOLATION_MARKERS: &[&str] = &["mods", "saves", "config", "resourcepacks"];
+
+fn parse_setup_ini(data: &str) -> HashMap<String, String> {
+    data.lines()
+        .filter_map(|x| x.split_once(':'))
+        .map(|(k, v)| (k.trim().to_owned(), v.trim().to_owned()))
+        .collect()
+}
+
+/// 将 PCL 内存滑块的值换算为以 MB 为单位的内存大小
+///
+/// 换算方式参考 PCL2 的版本设置页面，滑块的步长会随着数值增大而增大
+fn ram_slider_to_mb(value: f64) -> usize {
+    let gb = if value <= 12. {
+        value * 0.1
+    } else if value <= 25. {
+        (value - 12.) * 0.5 + 1.2
+    } else if value <= 33. {
+        (value - 25.) + 7.7
+    } else {
+        (value - 33.) * 2. + 15.7
+    };
+    (gb * 1024.).round() as usize
+}
+
+fn map_settings(setup: &HashMap<String, String>, result: &mut InstanceImport) {
+    let get = |key: &str| setup.get(key).map(String::as_str).filter(|x| !x.is_empty());
+    let config = &mut result.launch_config;
+    if let Some(args) = get("VersionAdvanceJvm") {
+        apply_jvm_args(config, args);
+    }
+    if let Some(args) = get("VersionAdvanceGame") {
+        config.game_args = args.to_owned();
+    }
+    if let Some(title) = get("VersionArgumentTitle") {
+        config.window_title = title.to_owned();
+    }
+    // 0 为自动配置，1 为自定义，其余为跟随全局设置
+    if get("VersionRamType") == Some("1") {
+        if let Some(value) = get("VersionRamCustom").and_then(|x| x.parse().ok()) {
+            config.max_mem = Some(ram_slider_to_mb(value));
+        }
+    }
+    if let Some(java) = get("VersionArgumentJavaSelect") {
+        result.unmapped.push(format!("指定的 Java {java}"));
+    }
+    if let Some(server) = get("VersionServerEnter") {
+        result
+            .unmapped
+            .push(format!("启动后自动进入服务器 {server}"));
+    }
+}
+
+/// 读取 PCL 游戏文件夹中指定版本的设置，`minecraft_dir` 为 `.minecraft` 文件夹
+///
+/// 游戏版本和模组加载器会从版本元数据中读取，版本文件夹中存在模组、存档等文件夹时视为开启了版本隔离
+pub async fn read_pcl_version(
+    minecraft_dir: impl AsRef<Path>,
+    version_name: &str,
+) -> DynResult<InstanceImport> {
+    let minecraft_dir = minecraft_dir.as_ref();
+    let version_dir = minecraft_dir.join("versions").join(version_name);
+    let (minecraft_version, loader) =
+        read_version_source(&minecraft_dir.join("versions"), version_name).await?;
+    let isolated = ISOLATION_MARKERS
+        .iter()
+        .any(|x| version_dir.join(x).is_dir());
+    let mut result = InstanceImport {
+        name: version_name.to_owned(),
+        minecraft_version,
+        loader,
+        game_dir: Some(if isolated {
+            version_dir.to_owned()
+        } else {
+            minecraft_dir.to_owned()
+        }),
+        ..Default::default()
+    };
+    let setup_path = version_dir.join("PCL").join("Setup.ini");
+    if setup_path.is_file() {
+        let data = inner_future::fs::read_to_string(setup_path).await?;
+        map_settings(&parse_setup_ini(&data), &mut result);
+    }
+    Ok(result)
+}
+
+#[test]
+fn pcl_version_test() {
+    assert_eq!(ram_slider_to_mb(10.), 1024);
+    assert_eq!(ram_slider_to_mb(20.), 5325);
+    assert_eq!(ram_slider_to_mb(33.), 16077);
+
+    let setup = parse_setup_ini(
+        "VersionAdvanceJvm:-XX:+UseG1GC -Xmx2G\r\n\
+         VersionAdvanceGame:\r\n\
+         VersionArgumentTitle:My Pack\r\n\
+         VersionRamType:1\r\n\
+         VersionRamCustom:20\r\n\
+         VersionServerEnter:mc.example.com:25565\r\n",
+    );
+    let mut result = InstanceImport::default();
+    map_settings(&setup, &mut result);
+    assert_eq!(result.launch_config.jvm_args, "-XX:+UseG1GC");
+    assert_eq!(result.launch_config.game_args, "");
+    assert_eq!(result.launch_config.window_title, "My Pack");
+    assert_eq!(result.launch_config.max_mem, Some(5325));
+    assert_eq!(
+        result.unmapped,
+        ["启动后自动进入服务器 mc.example.com:25565"]
+    );
+}
@@ -0,0 +1,255 @@
+//! 整合包的导入和导出
+//!
+//! 导入时会先安装整合包所需的原版和模组加载器，再下载整合包内的文件并复制覆盖文件，
+//! 导入的版本总是开启版本独立，整合包内的文件都会被放置在版本文件夹中。
+
+pub mod curseforge;
+pub mod export;
+pub mod instance;
+pub mod mrpack;
+
+use std::{
+    io::Read,
+    path::{Component, Path, PathBuf},
+};
+
+use crate::{
+    download::{Downloader, GameDownload, VanillaDownloadExt},
+    prelude::*,
+    version::structs::{Argument, VersionInfo, VersionMeta},
+};
+
+/// 整合包所需的模组加载器及其版本
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ModpackLoader {
+    /// Fabric，版本为 Fabric Loader 的版本号
+    Fabric(String),
+    /// Quilt，版本为 Quilt Loader 的版本号
+    Quilt(String),
+    /// Forge，版本为不带游戏版本号前缀的 Forge 版本号，例如 `47.2.0`
+    Forge(String),
+    /// NeoForge，版本为 NeoForge 的版本号，例如 `20.4.80`
+    ///
+    /// 1.20.1 的 NeoForge 以 `net.neoforged:forge` 发布，版本号形如 `47.1.84`，可以识别但无法安装
+    NeoForge(String),
+}
+
+impl ModpackLoader {
+    /// 模组加载器的版本号
+    pub fn version(&self) -> &str {
+        match self {
+            ModpackLoader::Fabric(v)
+            | ModpackLoader::Quilt(v)
+            | ModpackLoader::Forge(v)
+            | ModpackLoader::NeoForge(v) => v,
+        }
+    }
+
+    /// 检查是否可以为指定的游戏版本安装该模组加载器
+    ///
+    /// 1.20.1 的 NeoForge 不在 NeoForge 安装器使用的 `net.neoforged:neoforge` 下发布，暂不支持安装
+    pub fn check_installable(&self, minecraft_version: &str) -> DynResult {
+        if let ModpackLoader::NeoForge(version) = self {
+            anyhow::ensure!(
+                minecraft_version != "1.20.1",
+                "暂不支持安装 1.20.1 的 NeoForge {}，请改用对应版本的 Forge",
+                version
+            );
+        }
+        Ok(())
+    }
+
+    /// 从版本元数据中获取模组加载器及其版本，优先读取 Forge 和 NeoForge 的启动参数，再读取依赖库的名称
+    pub fn from_version_meta(meta: &VersionMeta) -> Option<Self> {
+        if let Some(arguments) = &meta.arguments {
+            let args: Vec<&str> = arguments
+                .game
+                .iter()
+                .filter_map(|x| match x {
+                    Argument::Common(x) => Some(x.as_str()),
+                    Argument::Specify(_) => None,
+                })
+                .collect();
+            for pair in args.windows(2) {
+                match pair[0] {
+                    "--fml.forgeVersion" => return Some(ModpackLoader::Forge(pair[1].to_owned())),
+                    "--fml.neoForgeVersion" => {
+                        return Some(ModpackLoader::NeoForge(pair[1].to_owned()))
+                    }
+                    _ => {}
+                }
+            }
+        }
+        // Forge 的依赖库版本号带有游戏版本号前缀，例如 1.20.1-47.2.0
+        let strip_game_version = |version: &str| {
+            version
+                .split_once('-')
+                .map(|(_, x)| x)
+                .unwrap_or(version)
+                .to_owned()
+        };
+        meta.libraries.iter().find_map(|lib| {
+            let mut parts = lib.name.split(':');
+            let (group, artifact, version) = (parts.next()?, parts.next()?, parts.next()?);
+            match (group, artifact) {
+                ("net.fabricmc", "fabric-loader") => {
+                    Some(ModpackLoader::Fabric(version.to_owned()))
+                }
+                ("org.quiltmc", "quilt-loader") => Some(ModpackLoader::Quilt(version.to_owned())),
+                ("net.minecraftforge", "forge" | "fmlloader") => {
+                    Some(ModpackLoader::Forge(strip_game_version(version)))
+                }
+                ("net.neoforged", "neoforge") => Some(ModpackLoader::NeoForge(version.to_owned())),
+                ("net.neoforged", "forge") => {
+                    Some(ModpackLoader::NeoForge(strip_game_version(version)))
+                }
+                _ => None,
+            }
+        })
+    }
+}
+
+/// 将整合包内的相对路径拼接到指定的文件夹上，如果路径是绝对路径或者会跳出该文件夹则返回 `None`
+pub(crate) fn safe_join(base: &Path, relative: &str) -> Option<PathBuf> {
+    let relative = Path::new(relative);
+    let mut result = base.to_path_buf();
+    let mut depth = 0usize;
+    for component in relative.components() {
+        match component {
+            Component::Normal(part) => {
+                result.push(part);
+                depth += 1;
+            }
+            Component::CurDir => {}
+            Component::ParentDir if depth > 0 => {
+                result.pop();
+                depth -= 1;
+            }
+            _ => return None,
+        }
+    }
+    (depth > 0).then_some(result)
+}
+
+/// 将压缩包中指定文件夹内的所有文件解压到目标文件夹中，返回解压的文件数量
+///
+/// 会覆盖已存在的文件，会跳出目标文件夹的路径将被忽略
+pub(crate) fn extract_overrides(
+    archive: &mut zip::ZipArchive<impl Read + std::io::Seek>,
+    prefix: &str,
+    dest: &Path,
+) -> DynResult<usize> {
+    let prefix = format!("{}/", prefix.trim_end_matches('/'));
+    let mut count = 0;
+    for i in 0..archive.len() {
+        let mut entry = archive.by_index(i)?;
+        let Some(relative) = entry.name().strip_prefix(&prefix) else {
+            continue;
+        };
+        if relative.is_empty() || entry.is_dir() {
+            continue;
+        }
+        let Some(path) = safe_join(dest, relative) else {
+            tracing::warn!("忽略不安全的覆盖文件路径 {}", entry.name());
+            continue;
+        };
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let mut file = std::fs::File::create(&path)?;
+        std::io::copy(&mut entry, &mut file)?;
+        count += 1;
+    }
+    Ok(count)
+}
+
+impl<R: Reporter> Downloader<R> {
+    /// 获取指定版本的版本文件夹路径，导入的整合包总是开启版本独立，所以这也是游戏的运行目录
+    pub(crate) fn version_dir(&self, version_name: &str) -> PathBuf {
+        Path::new(&self.minecraft_version_path).join(version_name)
+    }
+
+    /// 安装整合包所需的原版和模组加载器
+    pub(crate) async fn install_modpack_game(
+        &self,
+        version_name: &str,
+        minecraft_version: &str,
+        loader: Option<&ModpackLoader>,
+    ) -> DynResult {
+        if self.version_dir(version_name).exists() {
+            anyhow::bail!("版本 {} 已存在", version_name);
+        }
+        if let Some(loader) = loader {
+            loader.check_installable(minecraft_version)?;
+        }
+        let vanilla = self
+            .get_avaliable_vanilla_versions()
+            .await?
+            .versions
+            .into_iter()
+            .find(|x| x.id == minecraft_version)
+            .ok_or_else(|| anyhow::anyhow!("找不到游戏版本 {}", minecraft_version))?;
+        let version = |matcher: fn(&ModpackLoader) -> bool| {
+            loader
+                .filter(|x| matcher(x))
+                .map(|x| x.version())
+                .unwrap_or_default()
+        };
+        self.download_game(
+            version_name,
+            vanilla,
+            version(|x| matches!(x, ModpackLoader::Fabric(_))),
+            version(|x| matches!(x, ModpackLoader::Quilt(_))),
+            version(|x| matches!(x, ModpackLoader::Forge(_))),
+            version(|x| matches!(x, ModpackLoader::NeoForge(_))),
+            "",
+        )
+        .await
+    }
+
+    /// 为导入的整合包版本开启版本独立
+    pub(crate) async fn enable_game_independent(&self, version_name: &str) -> DynResult {
+        let mut version_info = VersionInfo {
+            version_base: self.minecraft_version_path.to_owned(),
+            version: version_name.to_owned(),
+            ..Default::default()
+        };
+        version_info.load().await?;
+        let mut config = version_info.scl_launch_config.take().unwrap_or_default();
+        config.game_independent = true;
+        version_info.scl_launch_config = Some(config);
+        version_info.save().await
+    }
+
+    /// 整合包导入失败时删除已经创建的版本文件夹
+    pub(crate) async fn cleanup_failed_import<T>(
+        &self,
+        version_name: &str,
+        result: DynResult<T>,
+    ) -> DynResult<T> {
+        if result.is_err() {
+            let version_dir = self.version_dir(version_name);
+            if version_dir.is_dir() {
+                let _ = inner_future::fs::remove_dir_all(version_dir).await;
+            }
+        }
+        result
+    }
+}
+
+#[test]
+fn safe_join_test() {
+    let base = Path::new("versions/pack");
+    assert_eq!(
+        safe_join(base, "mods/sodium.jar"),
+        Some(base.join("mods").join("sodium.jar"))
+    );
+    assert_eq!(
+        safe_join(base, "config/../options.txt"),
+        Some(base.join("options.txt"))
+    );
+    assert_eq!(safe_join(base, "../other/mods/a.jar"), None);
+    assert_eq!(safe_join(base, "mods/../../a.jar"), None);
+    assert_eq!(safe_join(base, "/etc/passwd"), None);
+    assert_eq!(safe_join(base, ""), None);
+}
@@ -0,0 +1,329 @@
+//! Modrinth 整合包（`.mrpack`）的导入
+//!
+//! 格式参考：<https://support.modrinth.com/en/articles/8802351-modrinth-modpack-format-mrpack>
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha512};
+
+use super::{extract_overrides, safe_join, ModpackLoader};
+use crate::{download::Downloader, prelude::*};
+
+/// 整合包索引文件在压缩包中的路径
+pub const INDEX_FILE_NAME: &str = "modrinth.index.json";
+/// 通用覆盖文件夹在压缩包中的路径
+pub const OVERRIDES_DIR: &str = "overrides";
+/// 仅客户端使用的覆盖文件夹在压缩包中的路径，会在通用覆盖文件夹之后应用
+pub const CLIENT_OVERRIDES_DIR: &str = "client-overrides";
+
+/// 文件在客户端或服务端上的需求程度
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EnvSupport {
+    /// 必须安装
+    #[default]
+    Required,
+    /// 可选安装
+    Optional,
+    /// 不应安装
+    Unsupported,
+}
+
+/// 文件在各个环境上的需求程度
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct MrpackEnv {
+    /// 在客户端上的需求程度
+    pub client: EnvSupport,
+    /// 在服务端上的需求程度
+    pub server: EnvSupport,
+}
+
+/// 整合包中需要下载的一个文件
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MrpackFile {
+    /// 文件相对于游戏目录的路径
+    pub path: String,
+    /// 文件的摘要，键为算法名称，例如 `sha1` 和 `sha512`
+    pub hashes: HashMap<String, String>,
+    /// 文件在各个环境上的需求程度，没有该字段时视为全部必须安装
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub env: Option<MrpackEnv>,
+    /// 文件的下载链接，会按顺序尝试
+    pub downloads: Vec<String>,
+    /// 文件的大小，单位为字节
+    pub file_size: u64,
+}
+
+impl MrpackFile {
+    /// 该文件是否需要在客户端上安装
+    pub fn is_client_file(&self) -> bool {
+        self.env
+            .as_ref()
+            .map(|x| x.client != EnvSupport::Unsupported)
+            .unwrap_or(true)
+    }
+}
+
+/// 整合包的索引文件 `modrinth.index.json`
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MrpackIndex {
+    /// 索引格式版本，目前只有 `1`
+    pub format_version: u32,
+    /// 整合包所属的游戏，目前只有 `minecraft`
+    pub game: String,
+    /// 整合包自身的版本号
+    pub version_id: String,
+    /// 整合包的名称
+    pub name: String,
+    /// 整合包的简介
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub summary: Option<String>,
+    /// 整合包中需要下载的文件
+    #[serde(default)]
+    pub files: Vec<MrpackFile>,
+    /// 整合包依赖的游戏和模组加载器，键为 `minecraft`、`forge`、`neoforge`、`fabric-loader` 或 `quilt-loader`
+    pub dependencies: HashMap<String, String>,
+}
+
+impl MrpackIndex {
+    /// 整合包所需的游戏版本
+    pub fn minecraft_version(&self) -> DynResult<&str> {
+        self.dependencies
+            .get("minecraft")
+            .map(String::as_str)
+            .ok_or_else(|| anyhow::anyhow!("整合包没有指定游戏版本"))
+    }
+
+    /// 整合包所需的模组加载器，遇到无法识别的依赖时返回错误
+    pub fn loader(&self) -> DynResult<Option<ModpackLoader>> {
+        let mut loader = None;
+        for (id, version) in &self.dependencies {
+            let current = match id.as_str() {
+                "minecraft" => continue,
+                "fabric-loader" => ModpackLoader::Fabric(version.to_owned()),
+                "quilt-loader" => ModpackLoader::Quilt(version.to_owned()),
+                "forge" => ModpackLoader::Forge(version.to_owned()),
+                "neoforge" => ModpackLoader::NeoForge(version.to_owned()),
+                _ => anyhow::bail!("不支持整合包依赖 {} {}", id, version),
+            };
+            if loader.is_some() {
+                anyhow::bail!("整合包同时依赖了多个模组加载器");
+            }
+            loader = Some(current);
+        }
+        Ok(loader)
+    }
+
+    /// 需要在客户端上安装的文件
+    pub fn client_files(&self) -> impl Iterator<Item = &MrpackFile> {
+        self.files.iter().filter(|x| x.is_client_file())
+    }
+
+    fn check(&self) -> DynResult {
+        if self.format_version != 1 {
+            anyhow::bail!("不支持的整合包格式版本 {}", self.format_version);
+        }
+        if self.game != "minecraft" {
+            anyhow::bail!("不支持的整合包游戏 {}", self.game);
+        }
+        Ok(())
+    }
+}
+
+/// 读取整合包中的索引文件
+pub async fn read_mrpack_index(mrpack_path: impl AsRef<Path>) -> DynResult<MrpackIndex> {
+    let mrpack_path = mrpack_path.as_ref().to_owned();
+    inner_future::unblock(move || {
+        let mut archive = zip::ZipArchive::new(std::fs::File::open(mrpack_path)?)?;
+        let index: MrpackIndex = serde_json::from_reader(archive.by_name(INDEX_FILE_NAME)?)?;
+        index.check()?;
+        Ok(index)
+    })
+    .await
+}
+
+/// 校验文件数据是否和整合包中记录的摘要一致，优先使用 SHA-512，其次使用 SHA-1
+fn verify_hashes(file: &MrpackFile, data: &[u8]) -> DynResult {
+    let (algorithm, expected, current) = if let Some(sha512) = file.hashes.get("sha512") {
+        ("SHA-512", sha512, format!("{:x}", Sha512::digest(data)))
+    } else if let Some(sha1) = file.hashes.get("sha1") {
+        ("SHA-1", sha1, sha1_smol::Sha1::from(data).hexdigest())
+    } else {
+        anyhow::bail!("整合包文件 {} 没有提供摘要", file.path);
+    };
+    if !current.eq_ignore_ascii_case(expected) {
+        anyhow::bail!(
+            "整合包文件 {} 校验失败，期望 {} 为 {}，实际为 {}",
+            file.path,
+            algorithm,
+            expected,
+            current
+        );
+    }
+    Ok(())
+}
+
+/// Modrinth 整合包的导入
+pub trait MrpackImportExt: Sync {
+    /// 将整合包导入为一个新的版本，返回整合包的索引信息
+    ///
+    /// 会安装整合包所需的原版和模组加载器，下载并校验所有客户端需要的文件，
+    /// 然后依次应用 `overrides` 和 `client-overrides` 文件夹，最后为该版本开启版本独立。
+    ///
+    /// 如果版本已存在则会返回错误，导入失败时会删除已创建的版本文件夹
+    async fn import_mrpack(
+        &self,
+        mrpack_path: impl AsRef<Path>,
+        version_name: &str,
+    ) -> DynResult<MrpackIndex>;
+    /// 下载整合包中所有客户端需要的文件到指定版本的版本文件夹中
+    async fn download_mrpack_files(
+        &self,
+        index: &MrpackIndex,
+        version_name: &str,
+    ) -> DynResult<Vec<PathBuf>>;
+}
+
+impl<R: Reporter> MrpackImportExt for Downloader<R> {
+    async fn import_mrpack(
+        &self,
+        mrpack_path: impl AsRef<Path>,
+        version_name: &str,
+    ) -> DynResult<MrpackIndex> {
+        let mrpack_path = mrpack_path.as_ref().to_owned();
+        let index = read_mrpack_index(&mrpack_path).await?;
+        let loader = index.loader()?;
+        let minecraft_version = index.minecraft_version()?.to_owned();
+        if self.version_dir(version_name).exists() {
+            anyhow::bail!("版本 {} 已存在", version_name);
+        }
+
+        let r = self.reporter.fork();
+        r.set_message(format!("正在导入整合包 {}", index.name));
+        let result = async {
+            self.install_modpack_game(version_name, &minecraft_version, loader.as_ref())
+                .await?;
+            self.download_mrpack_files(&index, version_name).await?;
+
+            r.set_message("正在应用整合包覆盖文件".into());
+            let version_dir = self.version_dir(version_name);
+            inner_future::unblock(move || -> DynResult {
+                let mut archive = zip::ZipArchive::new(std::fs::File::open(mrpack_path)?)?;
+                extract_overrides(&mut archive, OVERRIDES_DIR, &version_dir)?;
+                extract_overrides(&mut archive, CLIENT_OVERRIDES_DIR, &version_dir)?;
+                Ok(())
+            })
+            .await?;
+
+            self.enable_game_independent(version_name).await
+        }
+        .await;
+        self.cleanup_failed_import(version_name, result)
+            .await
+            .map(|_| index)
+    }
+
+    async fn download_mrpack_files(
+        &self,
+        index: &MrpackIndex,
+        version_name: &str,
+    ) -> DynResult<Vec<PathBuf>> {
+        let version_dir = self.version_dir(version_name);
+        let files = index
+            .client_files()
+            .map(|file| {
+                safe_join(&version_dir, &file.path)
+                    .map(|dest_path| (file, dest_path))
+                    .ok_or_else(|| anyhow::anyhow!("整合包文件路径 {} 不安全", file.path))
+            })
+            .collect::<DynResult<Vec<_>>>()?;
+        let r = self.reporter.sub();
+        r.add_max_progress(files.len() as f64);
+        r.set_message("正在下载整合包文件".into());
+        futures::future::try_join_all(files.into_iter().map(|(file, dest_path)| {
+            let r = r.clone();
+            async move {
+                let l = self.parallel_lock.acquire().await;
+                if file.downloads.is_empty() {
+                    anyhow::bail!("整合包文件 {} 没有下载链接", file.path);
+                }
+                inner_future::fs::create_dir_all(dest_path.parent().unwrap()).await?;
+                crate::http::download(
+                    &file.downloads,
+                    &dest_path.to_string_lossy(),
+                    file.file_size as _,
+                )
+                .await?;
+                let data = inner_future::fs::read(&dest_path).await?;
+                if let Err(err) = verify_hashes(file, &data) {
+                    let _ = inner_future::fs::remove_file(&dest_path).await;
+                    return Err(err);
+                }
+                r.add_progress(1.);
+                drop(l);
+                Ok(dest_path)
+            }
+        }))
+        .await
+    }
+}
+
+#[test]
+fn mrpack_index_test() {
+    let index: MrpackIndex = serde_json::from_str(
+        r#"{
+            "formatVersion": 1,
+            "game": "minecraft",
+            "versionId": "1.0.0",
+            "name": "Test Pack",
+            "files": [
+                {
+                    "path": "mods/sodium.jar",
+                    "hashes": { "sha1": "a9993e364706816aba3e25717850c26c9cd0d89d" },
+                    "env": { "client": "required", "server": "unsupported" },
+                    "downloads": ["https://cdn.modrinth.com/data/AANobbMI/versions/sodium.jar"],
+                    "fileSize": 3
+                },
+                {
+                    "path": "mods/server-only.jar",
+                    "hashes": { "sha1": "0000000000000000000000000000000000000000" },
+                    "env": { "client": "unsupported", "server": "required" },
+                    "downloads": [],
+                    "fileSize": 0
+                },
+                {
+                    "path": "config/no-env.toml",
+                    "hashes": {},
+                    "downloads": [],
+                    "fileSize": 0
+                }
+            ],
+            "dependencies": { "minecraft": "1.20.1", "fabric-loader": "0.15.7" }
+        }"#,
+    )
+    .unwrap();
+    index.check().unwrap();
+    assert_eq!(index.minecraft_version().unwrap(), "1.20.1");
+    assert_eq!(
+        index.loader().unwrap(),
+        Some(ModpackLoader::Fabric("0.15.7".into()))
+    );
+    let client_files: Vec<_> = index.client_files().map(|x| x.path.as_str()).collect();
+    assert_eq!(client_files, ["mods/sodium.jar", "config/no-env.toml"]);
+
+    verify_hashes(&index.files[0], b"abc").unwrap();
+    assert!(verify_hashes(&index.files[0], b"abd").is_err());
+    assert!(verify_hashes(&index.files[2], b"").is_err());
+
+    let mut conflicting = index.clone();
+    conflicting
+        .dependencies
+        .insert("forge".into(), "47.2.0".into());
+    assert!(conflicting.loader().is_err());
+}
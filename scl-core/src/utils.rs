@@ -97,6 +97,18 @@ extern "C" {
     fn getpagesize() -> libc::c_int;
 }
 
+/// 检查文件或文件夹名称是否合法，避免通过名称访问到其它文件夹
+pub(crate) fn check_file_name(file_name: &str) -> DynResult {
+    if file_name.is_empty()
+        || file_name.contains(['/', '\\'])
+        || file_name == "."
+        || file_name == ".."
+    {
+        anyhow::bail!("文件名 {} 不合法", file_name);
+    }
+    Ok(())
+}
+
 /// 异步计算一个数据的 SHA1 摘要值
 ///
 /// 返回一个十六进制的小写摘要字符串
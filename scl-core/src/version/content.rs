@@ -0,0 +1,723 @@
+//! 模组、资源包、光影包和数据包的统一管理
+//!
+//! 文件形式的内容通过添加或去除 `.disabled` 后缀来禁用或启用，和 [`super::mods::Mod`] 的规则一致；
+//! 文件夹形式的内容无法通过重命名文件夹来禁用，所以会重命名其中的 `pack.mcmeta` 或 `shaders` 文件夹。
+//!
+//! 移除内容时会将其移动到游戏目录的回收站文件夹中，而不是直接删除。
+
+use std::{
+    io::Read,
+    path::{Path, PathBuf},
+};
+
+use image::DynamicImage;
+use inner_future::stream::StreamExt;
+
+use crate::{prelude::*, semver::MinecraftVersion, utils::check_file_name};
+
+/// 回收站文件夹相对于游戏目录的路径
+pub const TRASH_DIR: &str = ".scl-trash";
+
+const DISABLED_SUFFIX: &str = ".disabled";
+
+/// 内容的种类
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ContentKind {
+    /// 模组，位于 `mods` 文件夹
+    Mod,
+    /// 资源包，位于 `resourcepacks` 文件夹，列出时也会包含 1.6 之前的版本使用的 `texturepacks` 文件夹
+    ResourcePack,
+    /// 光影包，位于 `shaderpacks` 文件夹
+    ShaderPack,
+    /// 数据包，位于指定世界存档的 `datapacks` 文件夹，值为世界存档的文件夹名称
+    DataPack(String),
+}
+
+impl ContentKind {
+    /// 该种类内容相对于游戏目录的文件夹路径，新安装的内容会放在这里
+    ///
+    /// 数据包的世界存档名称不合法（例如包含路径分隔符或 `..`）时返回错误
+    pub fn folder(&self) -> DynResult<PathBuf> {
+        Ok(match self {
+            ContentKind::Mod => "mods".into(),
+            ContentKind::ResourcePack => "resourcepacks".into(),
+            ContentKind::ShaderPack => "shaderpacks".into(),
+            ContentKind::DataPack(world) => {
+                check_file_name(world)?;
+                Path::new("saves").join(world).join("datapacks")
+            }
+        })
+    }
+
+    /// 列出内容时需要扫描的所有文件夹，包括旧版本使用的文件夹
+    fn folders(&self) -> DynResult<Vec<PathBuf>> {
+        let mut folders = vec![self.folder()?];
+        if *self == ContentKind::ResourcePack {
+            folders.push("texturepacks".into());
+        }
+        Ok(folders)
+    }
+
+    /// 该种类内容文件的扩展名
+    fn extension(&self) -> &'static str {
+        match self {
+            ContentKind::Mod => "jar",
+            _ => "zip",
+        }
+    }
+
+    /// 文件夹形式的内容中用于标记启用状态的文件或文件夹，模组不支持文件夹形式
+    fn dir_marker(&self) -> Option<&'static str> {
+        match self {
+            ContentKind::Mod => None,
+            ContentKind::ResourcePack | ContentKind::DataPack(_) => Some("pack.mcmeta"),
+            ContentKind::ShaderPack => Some("shaders"),
+        }
+    }
+}
+
+/// 资源包或数据包的兼容情况
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PackCompatibility {
+    /// 和游戏版本兼容
+    Compatible,
+    /// 为更旧的游戏版本制作
+    TooOld,
+    /// 为更新的游戏版本制作
+    TooNew,
+    /// 无法判断，例如游戏版本为快照或者不在已知的版本表中
+    Unknown,
+}
+
+/// 资源包和数据包的 `pack.mcmeta` 信息
+#[derive(Debug, Clone, Default)]
+pub struct PackMeta {
+    /// 包的介绍，如果是 JSON 文本组件则会被转换为纯文本
+    pub description: String,
+    /// 包的格式版本
+    pub pack_format: u32,
+    /// 包声明支持的格式版本范围，包含两端
+    pub supported_formats: Option<(u32, u32)>,
+    /// 包的图标 `pack.png` 的数据
+    pub icon_data: Option<Vec<u8>>,
+}
+
+/// 将 JSON 文本组件转换为纯文本
+fn text_component_to_string(value: &serde_json::Value) -> String {
+    match value {
+        serde_json::Value::String(s) => s.to_owned(),
+        serde_json::Value::Array(parts) => parts.iter().map(text_component_to_string).collect(),
+        serde_json::Value::Object(obj) => {
+            let mut result = obj
+                .get("text")
+                .or_else(|| obj.get("translate"))
+                .and_then(|x| x.as_str())
+                .unwrap_or_default()
+                .to_owned();
+            if let Some(extra) = obj.get("extra") {
+                result.push_str(&text_component_to_string(extra));
+            }
+            result
+        }
+        serde_json::Value::Null => String::new(),
+        other => other.to_string(),
+    }
+}
+
+impl PackMeta {
+    /// 解析 `pack.mcmeta` 文件的内容
+    pub fn parse(data: &str) -> DynResult<Self> {
+        let value: serde_json::Value = serde_json::from_str(data.trim_start_matches('\u{feff}'))?;
+        let pack = value
+            .get("pack")
+            .ok_or_else(|| anyhow::anyhow!("pack.mcmeta 中缺少 pack 字段"))?;
+        let pack_format = pack["pack_format"]
+            .as_u64()
+            .ok_or_else(|| anyhow::anyhow!("pack.mcmeta 中缺少 pack_format 字段"))?
+            as u32;
+        let supported = &pack["supported_formats"];
+        let supported_formats = if let Some(x) = supported.as_u64() {
+            Some((x, x))
+        } else if let Some([min, max]) = supported.as_array().map(Vec::as_slice) {
+            min.as_u64().zip(max.as_u64())
+        } else {
+            supported["min_inclusive"]
+                .as_u64()
+                .zip(supported["max_inclusive"].as_u64())
+        }
+        .map(|(min, max)| (min as u32, max as u32));
+        Ok(Self {
+            description: text_component_to_string(&pack["description"]),
+            pack_format,
+            supported_formats,
+            icon_data: None,
+        })
+    }
+
+    /// 解码包的图标
+    pub fn icon(&self) -> DynResult<DynamicImage> {
+        let data = self
+            .icon_data
+            .as_ref()
+            .ok_or_else(|| anyhow::anyhow!("该包没有图标"))?;
+        Ok(image::load_from_memory(data)?)
+    }
+
+    /// 检查包是否和指定游戏版本兼容，`data_pack` 为是否为数据包，两者的格式版本号不同
+    pub fn compatibility(
+        &self,
+        minecraft_version: &MinecraftVersion,
+        data_pack: bool,
+    ) -> PackCompatibility {
+        let Some(expected) = pack_format_for(minecraft_version, data_pack) else {
+            return PackCompatibility::Unknown;
+        };
+        let (min, max) = self
+            .supported_formats
+            .unwrap_or((self.pack_format, self.pack_format));
+        if expected < min.min(self.pack_format) {
+            PackCompatibility::TooNew
+        } else if expected > max.max(self.pack_format) {
+            PackCompatibility::TooOld
+        } else {
+            PackCompatibility::Compatible
+        }
+    }
+}
+
+/// 各个正式版本开始使用的资源包格式版本，格式为 `(次版本号, 修订版本号, 格式版本)`
+const RESOURCE_PACK_FORMATS: &[(u32, u32, u32)] = &[
+    (6, 1, 1),
+    (9, 0, 2),
+    (11, 0, 3),
+    (13, 0, 4),
+    (15, 0, 5),
+    (16, 2, 6),
+    (17, 0, 7),
+    (18, 0, 8),
+    (19, 0, 9),
+    (19, 3, 12),
+    (19, 4, 13),
+    (20, 0, 15),
+    (20, 2, 18),
+    (20, 3, 22),
+    (20, 5, 32),
+    (21, 0, 34),
+    (21, 2, 42),
+    (21, 4, 46),
+    (21, 5, 55),
+    (21, 6, 63),
+    (21, 7, 64),
+];
+
+/// 各个正式版本开始使用的数据包格式版本，格式同 [`RESOURCE_PACK_FORMATS`]
+const DATA_PACK_FORMATS: &[(u32, u32, u32)] = &[
+    (13, 0, 4),
+    (15, 0, 5),
+    (16, 2, 6),
+    (17, 0, 7),
+    (18, 0, 8),
+    (18, 2, 9),
+    (19, 0, 10),
+    (19, 4, 12),
+    (20, 0, 15),
+    (20, 2, 18),
+    (20, 3, 26),
+    (20, 5, 41),
+    (21, 0, 48),
+    (21, 2, 57),
+    (21, 4, 61),
+    (21, 5, 71),
+    (21, 6, 80),
+    (21, 7, 81),
+];
+
+/// 格式版本表中已知的最新正式版本，格式为 `(次版本号, 修订版本号)`
+const LATEST_KNOWN_VERSION: (u32, u32) = (21, 8);
+
+/// 获取指定游戏版本使用的资源包或数据包格式版本，快照、过旧或者比已知版本更新的版本会返回 `None`
+pub fn pack_format_for(minecraft_version: &MinecraftVersion, data_pack: bool) -> Option<u32> {
+    let MinecraftVersion::Release(1, minor, patch) = *minecraft_version else {
+        return None;
+    };
+    let table = if data_pack {
+        DATA_PACK_FORMATS
+    } else {
+        RESOURCE_PACK_FORMATS
+    };
+    if (minor, patch) > LATEST_KNOWN_VERSION {
+        return None;
+    }
+    table
+        .iter()
+        .rev()
+        .find(|(m, p, _)| (minor, patch) >= (*m, *p))
+        .map(|x| x.2)
+}
+
+/// 一个模组、资源包、光影包或数据包
+///
+/// 可通过 [`ContentManager::list`] 获取
+#[derive(Debug, Clone)]
+pub struct ContentFile {
+    kind: ContentKind,
+    file_name: String,
+    path: PathBuf,
+    enabled: bool,
+}
+
+impl ContentFile {
+    /// 通过内容的路径获取内容信息
+    pub fn from_path(kind: ContentKind, path: impl AsRef<Path>) -> Self {
+        let path = path.as_ref().to_path_buf();
+        let file_name = path
+            .file_name()
+            .map(|x| x.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let enabled = match kind.dir_marker() {
+            Some(marker) if path.is_dir() => path.join(marker).exists(),
+            _ => !file_name.ends_with(DISABLED_SUFFIX),
+        };
+        Self {
+            kind,
+            file_name,
+            path,
+            enabled,
+        }
+    }
+
+    /// 内容的种类
+    pub fn kind(&self) -> &ContentKind {
+        &self.kind
+    }
+
+    /// 内容的文件名或文件夹名
+    pub fn file_name(&self) -> &str {
+        &self.file_name
+    }
+
+    /// 内容所在路径
+    pub fn path(&self) -> &PathBuf {
+        &self.path
+    }
+
+    /// 内容是否已经启用
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// 内容是否为文件夹形式
+    pub fn is_dir(&self) -> bool {
+        self.path.is_dir()
+    }
+
+    /// 启用该内容，已经启用时不做任何操作
+    pub async fn enable(&mut self) -> DynResult {
+        self.set_enabled(true).await
+    }
+
+    /// 禁用该内容，已经禁用时不做任何操作
+    pub async fn disable(&mut self) -> DynResult {
+        self.set_enabled(false).await
+    }
+
+    async fn set_enabled(&mut self, enabled: bool) -> DynResult {
+        if self.enabled == enabled {
+            return Ok(());
+        }
+        match self.kind.dir_marker() {
+            Some(marker) if self.is_dir() => {
+                let marker_path = self.path.join(marker);
+                let disabled_path = self.path.join(format!("{marker}{DISABLED_SUFFIX}"));
+                if enabled {
+                    inner_future::fs::rename(disabled_path, marker_path).await?;
+                } else {
+                    inner_future::fs::rename(marker_path, disabled_path).await?;
+                }
+            }
+            _ => {
+                let file_name = if enabled {
+                    self.file_name.trim_end_matches(DISABLED_SUFFIX).to_owned()
+                } else {
+                    format!("{}{DISABLED_SUFFIX}", self.file_name)
+                };
+                let path = self.path.with_file_name(&file_name);
+                inner_future::fs::rename(&self.path, &path).await?;
+                self.file_name = file_name;
+                self.path = path;
+            }
+        }
+        self.enabled = enabled;
+        Ok(())
+    }
+
+    /// 读取资源包或数据包的 `pack.mcmeta` 和图标，支持压缩包和文件夹形式
+    pub async fn read_pack_meta(&self) -> DynResult<PackMeta> {
+        if !matches!(
+            self.kind,
+            ContentKind::ResourcePack | ContentKind::DataPack(_)
+        ) {
+            anyhow::bail!("只有资源包和数据包才有 pack.mcmeta");
+        }
+        let path = self.path.to_owned();
+        inner_future::unblock(move || -> DynResult<PackMeta> {
+            if path.is_dir() {
+                let meta_path = ["pack.mcmeta", "pack.mcmeta.disabled"]
+                    .iter()
+                    .map(|x| path.join(x))
+                    .find(|x| x.is_file())
+                    .ok_or_else(|| anyhow::anyhow!("找不到 pack.mcmeta"))?;
+                let mut meta = PackMeta::parse(&std::fs::read_to_string(meta_path)?)?;
+                meta.icon_data = std::fs::read(path.join("pack.png")).ok();
+                Ok(meta)
+            } else {
+                let mut z = zip::ZipArchive::new(std::fs::File::open(&path)?)?;
+                let mut data = String::new();
+                z.by_name("pack.mcmeta")?.read_to_string(&mut data)?;
+                let mut meta = PackMeta::parse(&data)?;
+                if let Ok(mut icon) = z.by_name("pack.png") {
+                    let mut buf = Vec::with_capacity(icon.size() as _);
+                    icon.read_to_end(&mut buf)?;
+                    meta.icon_data = Some(buf);
+                }
+                Ok(meta)
+            }
+        })
+        .await
+    }
+
+    /// 检查资源包或数据包是否和指定游戏版本兼容，其余种类总是返回 [`PackCompatibility::Unknown`]
+    pub async fn check_compatibility(
+        &self,
+        minecraft_version: &MinecraftVersion,
+    ) -> DynResult<PackCompatibility> {
+        match self.kind {
+            ContentKind::ResourcePack | ContentKind::DataPack(_) => {
+                Ok(self.read_pack_meta().await?.compatibility(
+                    minecraft_version,
+                    matches!(self.kind, ContentKind::DataPack(_)),
+                ))
+            }
+            _ => Ok(PackCompatibility::Unknown),
+        }
+    }
+}
+
+/// 一个游戏目录中的内容管理器
+///
+/// 可通过 [`crate::version::structs::VersionInfo::content_manager`] 获取版本对应的管理器
+#[derive(Debug, Clone)]
+pub struct ContentManager {
+    game_dir: PathBuf,
+}
+
+impl ContentManager {
+    /// 创建指定游戏目录的内容管理器
+    pub fn new(game_dir: impl Into<PathBuf>) -> Self {
+        Self {
+            game_dir: game_dir.into(),
+        }
+    }
+
+    /// 游戏目录
+    pub fn game_dir(&self) -> &Path {
+        &self.game_dir
+    }
+
+    /// 回收站文件夹的路径
+    pub fn trash_dir(&self) -> PathBuf {
+        self.game_dir.join(TRASH_DIR)
+    }
+
+    /// 列出游戏目录中所有世界存档的文件夹名称，可用于 [`ContentKind::DataPack`]
+    pub async fn worlds(&self) -> DynResult<Vec<String>> {
+        let saves_path = self.game_dir.join("saves");
+        let mut result = vec![];
+        if saves_path.is_dir() {
+            let mut entries = inner_future::fs::read_dir(saves_path).await?;
+            while let Some(entry) = entries.try_next().await? {
+                if entry.path().join("level.dat").is_file() {
+                    result.push(entry.file_name().to_string_lossy().into_owned());
+                }
+            }
+        }
+        result.sort();
+        Ok(result)
+    }
+
+    /// 列出指定种类的所有内容，包括已禁用的内容，按文件名排序
+    pub async fn list(&self, kind: &ContentKind) -> DynResult<Vec<ContentFile>> {
+        let mut result = vec![];
+        let extension = format!(".{}", kind.extension());
+        for folder in kind.folders()? {
+            let folder = self.game_dir.join(folder);
+            if folder.is_dir() {
+                self.list_folder(kind, &folder, &extension, &mut result)
+                    .await?;
+            }
+        }
+        result.sort_by(|a, b| a.file_name.cmp(&b.file_name));
+        Ok(result)
+    }
+
+    async fn list_folder(
+        &self,
+        kind: &ContentKind,
+        folder: &Path,
+        extension: &str,
+        result: &mut Vec<ContentFile>,
+    ) -> DynResult {
+        let mut entries = inner_future::fs::read_dir(folder).await?;
+        while let Some(entry) = entries.try_next().await? {
+            let path = entry.path();
+            let file_name = entry.file_name().to_string_lossy().into_owned();
+            let is_content = if path.is_dir() {
+                kind.dir_marker().is_some_and(|marker| {
+                    path.join(marker).exists()
+                        || path.join(format!("{marker}{DISABLED_SUFFIX}")).exists()
+                })
+            } else {
+                file_name
+                    .trim_end_matches(DISABLED_SUFFIX)
+                    .ends_with(extension)
+            };
+            if is_content {
+                result.push(ContentFile::from_path(kind.to_owned(), path));
+            }
+        }
+        Ok(())
+    }
+
+    fn install_path(&self, kind: &ContentKind, file_name: &str) -> DynResult<PathBuf> {
+        check_file_name(file_name)?;
+        if !file_name.ends_with(&format!(".{}", kind.extension())) {
+            anyhow::bail!("文件 {} 不是 .{} 文件", file_name, kind.extension());
+        }
+        let path = self.game_dir.join(kind.folder()?).join(file_name);
+        let disabled_path = path.with_file_name(format!("{file_name}{DISABLED_SUFFIX}"));
+        if path.exists() || disabled_path.exists() {
+            anyhow::bail!("文件 {} 已存在", file_name);
+        }
+        Ok(path)
+    }
+
+    /// 从本地文件复制安装内容
+    pub async fn install_from_file(
+        &self,
+        kind: &ContentKind,
+        source: impl AsRef<Path>,
+    ) -> DynResult<ContentFile> {
+        let source = source.as_ref();
+        let file_name = source
+            .file_name()
+            .map(|x| x.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let dest = self.install_path(kind, &file_name)?;
+        inner_future::fs::create_dir_all(dest.parent().unwrap()).await?;
+        inner_future::fs::copy(source, &dest).await?;
+        Ok(ContentFile::from_path(kind.to_owned(), dest))
+    }
+
+    /// 从链接下载安装内容，没有提供文件名时会使用链接的最后一段路径作为文件名
+    pub async fn install_from_url(
+        &self,
+        kind: &ContentKind,
+        url: &str,
+        file_name: Option<&str>,
+    ) -> DynResult<ContentFile> {
+        let file_name = match file_name {
+            Some(file_name) => file_name.to_owned(),
+            None => {
+                let last = url
+                    .split(['?', '#'])
+                    .next()
+                    .and_then(|x| x.rsplit('/').next())
+                    .unwrap_or_default();
+                urlencoding::decode(last)?.into_owned()
+            }
+        };
+        let dest = self.install_path(kind, &file_name)?;
+        inner_future::fs::create_dir_all(dest.parent().unwrap()).await?;
+        crate::http::download(&[url], &dest.to_string_lossy(), 0).await?;
+        Ok(ContentFile::from_path(kind.to_owned(), dest))
+    }
+
+    /// 将内容移动到回收站中，返回其在回收站中的路径
+    ///
+    /// 回收站中的内容会按照原来的文件夹结构存放，并在文件名前加上移除时的时间戳以避免重名
+    pub async fn trash(&self, content: ContentFile) -> DynResult<PathBuf> {
+        let timestamp = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|x| x.as_millis())
+            .unwrap_or_default();
+        let folder = match content
+            .path
+            .parent()
+            .and_then(|x| x.strip_prefix(&self.game_dir).ok())
+        {
+            Some(folder) => folder.to_owned(),
+            None => content.kind.folder()?,
+        };
+        let dest = self
+            .trash_dir()
+            .join(folder)
+            .join(format!("{timestamp}-{}", content.file_name));
+        inner_future::fs::create_dir_all(dest.parent().unwrap()).await?;
+        inner_future::fs::rename(&content.path, &dest).await?;
+        Ok(dest)
+    }
+
+    /// 将回收站中的内容还原到原来的位置
+    pub async fn restore(
+        &self,
+        kind: &ContentKind,
+        trashed_path: impl AsRef<Path>,
+    ) -> DynResult<ContentFile> {
+        let trashed_path = trashed_path.as_ref();
+        let trashed_name = trashed_path
+            .file_name()
+            .map(|x| x.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let file_name = trashed_name
+            .split_once('-')
+            .map(|x| x.1)
+            .unwrap_or(&trashed_name);
+        // 还原到移入回收站前所在的文件夹，例如旧版本的 texturepacks 文件夹
+        let folder = match trashed_path
+            .parent()
+            .and_then(|x| x.strip_prefix(self.trash_dir()).ok())
+        {
+            Some(folder) if !folder.as_os_str().is_empty() => folder.to_owned(),
+            _ => kind.folder()?,
+        };
+        let dest = self.game_dir.join(folder).join(file_name);
+        if dest.exists() {
+            anyhow::bail!("文件 {} 已存在", file_name);
+        }
+        inner_future::fs::create_dir_all(dest.parent().unwrap()).await?;
+        inner_future::fs::rename(trashed_path, &dest).await?;
+        Ok(ContentFile::from_path(kind.to_owned(), dest))
+    }
+
+    /// 清空回收站，**此操作不可撤销**
+    pub async fn empty_trash(&self) -> DynResult {
+        let trash_dir = self.trash_dir();
+        if trash_dir.is_dir() {
+            inner_future::fs::remove_dir_all(trash_dir).await?;
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn content_manager_test() {
+    let meta = PackMeta::parse(
+        r#"{"pack":{"pack_format":15,"supported_formats":[15,18],
+            "description":[{"text":"Faithful "},{"text":"32x","extra":[" pack"]}]}}"#,
+    )
+    .unwrap();
+    assert_eq!(meta.description, "Faithful 32x pack");
+    assert_eq!(meta.supported_formats, Some((15, 18)));
+    let v = |minor, patch| MinecraftVersion::Release(1, minor, patch);
+    assert_eq!(
+        meta.compatibility(&v(20, 1), false),
+        PackCompatibility::Compatible
+    );
+    assert_eq!(
+        meta.compatibility(&v(20, 2), false),
+        PackCompatibility::Compatible
+    );
+    assert_eq!(
+        meta.compatibility(&v(20, 4), false),
+        PackCompatibility::TooOld
+    );
+    assert_eq!(
+        meta.compatibility(&v(19, 2), false),
+        PackCompatibility::TooNew
+    );
+    assert_eq!(
+        meta.compatibility(&MinecraftVersion::Snapshot(23, 51, 'a'), false),
+        PackCompatibility::Unknown
+    );
+    assert_eq!(pack_format_for(&v(16, 5), false), Some(6));
+    assert_eq!(pack_format_for(&v(18, 2), true), Some(9));
+    assert_eq!(pack_format_for(&v(12, 2), true), None);
+    assert_eq!(pack_format_for(&v(99, 0), false), None);
+
+    let dir = std::env::temp_dir().join(format!("scl-content-test-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let manager = ContentManager::new(&dir);
+    let source = std::env::temp_dir().join(format!("scl-content-src-{}.zip", std::process::id()));
+    {
+        let mut zip = zip::ZipWriter::new(std::fs::File::create(&source).unwrap());
+        zip.start_file("pack.mcmeta", Default::default()).unwrap();
+        std::io::Write::write_all(
+            &mut zip,
+            br#"{"pack":{"pack_format":6,"description":"Old"}}"#,
+        )
+        .unwrap();
+        zip.finish().unwrap();
+    }
+    let pack_dir = dir.join("resourcepacks").join("Folder Pack");
+    std::fs::create_dir_all(&pack_dir).unwrap();
+    std::fs::write(
+        pack_dir.join("pack.mcmeta"),
+        r#"{"pack":{"pack_format":15,"description":{"text":"Folder"}}}"#,
+    )
+    .unwrap();
+
+    inner_future::block_on(async {
+        let kind = ContentKind::ResourcePack;
+        let mut installed = manager.install_from_file(&kind, &source).await.unwrap();
+        assert!(manager.install_from_file(&kind, &source).await.is_err());
+        assert_eq!(
+            installed.check_compatibility(&v(20, 1)).await.unwrap(),
+            PackCompatibility::TooOld
+        );
+        installed.disable().await.unwrap();
+        assert!(installed.file_name().ends_with(".zip.disabled"));
+
+        let mut packs = manager.list(&kind).await.unwrap();
+        assert_eq!(packs.len(), 2);
+        assert!(packs[0].is_dir() && packs[0].is_enabled());
+        assert!(!packs[1].is_enabled());
+        packs[0].disable().await.unwrap();
+        assert!(!ContentFile::from_path(kind.to_owned(), &pack_dir).is_enabled());
+        assert_eq!(
+            packs[0].read_pack_meta().await.unwrap().description,
+            "Folder"
+        );
+        packs[0].enable().await.unwrap();
+        assert!(pack_dir.join("pack.mcmeta").is_file());
+
+        let trashed = manager.trash(packs.remove(1)).await.unwrap();
+        assert!(trashed.starts_with(manager.trash_dir()));
+        assert_eq!(manager.list(&kind).await.unwrap().len(), 1);
+        let restored = manager.restore(&kind, &trashed).await.unwrap();
+        assert!(!restored.is_enabled());
+        manager.empty_trash().await.unwrap();
+        assert!(!manager.trash_dir().exists());
+
+        let legacy_dir = dir.join("texturepacks");
+        std::fs::create_dir_all(&legacy_dir).unwrap();
+        std::fs::write(legacy_dir.join("Legacy.zip"), b"").unwrap();
+        let packs = manager.list(&kind).await.unwrap();
+        assert_eq!(packs.len(), 3);
+        let legacy = packs
+            .into_iter()
+            .find(|x| x.file_name() == "Legacy.zip")
+            .unwrap();
+        let trashed = manager.trash(legacy).await.unwrap();
+        let restored = manager.restore(&kind, &trashed).await.unwrap();
+        assert_eq!(restored.path(), &legacy_dir.join("Legacy.zip"));
+
+        for world in ["../..", "a/b", "a\\b", ""] {
+            let kind = ContentKind::DataPack(world.into());
+            assert!(manager.list(&kind).await.is_err());
+            assert!(manager.install_from_file(&kind, &source).await.is_err());
+        }
+    });
+
+    std::fs::remove_dir_all(&dir).unwrap();
+    std::fs::remove_file(&source).unwrap();
+}
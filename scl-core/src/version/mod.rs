@@ -2,6 +2,12 @@
 
 use std::path::Path;
 
+pub mod content;
+pub mod mod_check;
+pub mod mod_identify;
+pub mod mod_info;
+pub mod mod_profile;
+pub mod mod_update;
 pub mod mods;
 pub mod structs;
 
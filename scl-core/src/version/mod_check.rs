@@ -0,0 +1,762 @@
+//! 模组依赖及冲突检查
+//!
+//! 根据 [`super::mod_info::ModInfo`] 中声明的依赖关系，检查一个版本的模组文件夹中是否存在以下问题：
+//! - 缺少前置模组
+//! - 模组或游戏版本不符合依赖要求（Forge/NeoForge 为 Maven 版本范围，Fabric/Quilt 为 SemVer 版本谓词）
+//! - 多个文件包含了相同 ID 的模组
+//! - 安装了被声明为不兼容的模组
+//! - 模组不适用于当前版本的模组加载器
+
+use std::{
+    cmp::Ordering,
+    collections::HashMap,
+    fmt::{Display, Formatter},
+};
+
+use super::{
+    mod_info::{ModDependency, ModDependencyKind, ModEnvironment, ModInfo, ModLoaderType},
+    VersionType,
+};
+use crate::semver::MinecraftVersion;
+
+/// 检查出的问题的严重程度
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ModIssueSeverity {
+    /// 可能会导致部分功能异常，但不会阻止游戏启动
+    Warning,
+    /// 会导致游戏无法启动或模组无法加载
+    Error,
+}
+
+/// 检查出的问题的种类
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ModIssueKind {
+    /// 缺少必需的前置模组
+    MissingDependency {
+        /// 缺少的模组 ID
+        dependency: String,
+        /// 要求的版本范围，为空时代表任意版本
+        version_ranges: Vec<String>,
+    },
+    /// 前置模组或游戏的版本不符合要求
+    VersionMismatch {
+        /// 依赖的模组 ID，游戏本身为 `minecraft`
+        dependency: String,
+        /// 要求的版本范围
+        version_ranges: Vec<String>,
+        /// 实际安装的版本
+        found_version: String,
+    },
+    /// 另一个文件中包含了相同 ID 的模组
+    DuplicateMod {
+        /// 另一个文件的文件名
+        other_file_name: String,
+    },
+    /// 安装了被声明为不兼容的模组
+    Breaks {
+        /// 不兼容的模组 ID
+        other_mod_id: String,
+        /// 不兼容的模组所在的文件名
+        other_file_name: String,
+    },
+    /// 安装了被声明为可能存在冲突的模组
+    Conflicts {
+        /// 可能存在冲突的模组 ID
+        other_mod_id: String,
+        /// 可能存在冲突的模组所在的文件名
+        other_file_name: String,
+    },
+    /// 模组不适用于当前版本的模组加载器
+    WrongLoader {
+        /// 模组所需的模组加载器
+        loader: ModLoaderType,
+    },
+}
+
+/// 一个模组检查出的问题
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ModIssue {
+    /// 出现问题的模组文件名
+    pub file_name: String,
+    /// 出现问题的模组 ID
+    pub mod_id: String,
+    /// 问题的严重程度
+    pub severity: ModIssueSeverity,
+    /// 问题的种类
+    pub kind: ModIssueKind,
+}
+
+impl ModIssue {
+    fn new(file: &ModFile, info: &ModInfo, severity: ModIssueSeverity, kind: ModIssueKind) -> Self {
+        Self {
+            file_name: file.file_name.to_owned(),
+            mod_id: info.mod_id.to_owned(),
+            severity,
+            kind,
+        }
+    }
+
+    /// 该问题是否会导致游戏无法启动或模组无法加载
+    pub fn is_error(&self) -> bool {
+        self.severity == ModIssueSeverity::Error
+    }
+}
+
+fn format_ranges(ranges: &[String]) -> String {
+    if ranges.is_empty() {
+        "任意版本".into()
+    } else {
+        ranges.join(" 或 ")
+    }
+}
+
+impl Display for ModIssue {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}（{}）：", self.file_name, self.mod_id)?;
+        match &self.kind {
+            ModIssueKind::MissingDependency {
+                dependency,
+                version_ranges,
+            } => write!(
+                f,
+                "缺少前置模组 {dependency}，需要 {}",
+                format_ranges(version_ranges)
+            ),
+            ModIssueKind::VersionMismatch {
+                dependency,
+                version_ranges,
+                found_version,
+            } => write!(
+                f,
+                "需要 {dependency} {}，但当前为 {found_version}",
+                format_ranges(version_ranges)
+            ),
+            ModIssueKind::DuplicateMod { other_file_name } => {
+                write!(f, "和 {other_file_name} 是同一个模组，请删除其中一个")
+            }
+            ModIssueKind::Breaks {
+                other_mod_id,
+                other_file_name,
+            } => write!(
+                f,
+                "和 {other_file_name}（{other_mod_id}）不兼容，请删除其中一个"
+            ),
+            ModIssueKind::Conflicts {
+                other_mod_id,
+                other_file_name,
+            } => write!(f, "和 {other_file_name}（{other_mod_id}）可能存在冲突"),
+            ModIssueKind::WrongLoader { loader } => {
+                write!(f, "这是一个 {loader:?} 模组，无法在当前版本中加载")
+            }
+        }
+    }
+}
+
+/// 一个需要检查的模组文件
+#[derive(Debug, Clone)]
+pub struct ModFile {
+    /// 模组的文件名，用于在问题中指出是哪个文件
+    pub file_name: String,
+    /// 文件中包含的所有模组，可以通过 [`super::mods::Mod::try_get_mod_infos`] 获取
+    pub mods: Vec<ModInfo>,
+}
+
+/// 检查模组时需要的版本信息
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ModCheckTarget {
+    /// 版本的类型，用于判断模组是否适用于当前的模组加载器
+    pub version_type: VersionType,
+    /// 版本的 Minecraft 版本号，只有正式版本会进行版本范围检查
+    pub minecraft_version: MinecraftVersion,
+}
+
+impl ModCheckTarget {
+    /// 当前版本的模组加载器是否可以加载该模组，无法判断时返回 `true`
+    pub fn can_load(&self, loader: ModLoaderType) -> bool {
+        match self.version_type {
+            VersionType::Fabric => loader == ModLoaderType::Fabric,
+            VersionType::QuiltMC => {
+                matches!(loader, ModLoaderType::Fabric | ModLoaderType::Quilt)
+            }
+            VersionType::Forge => loader == ModLoaderType::Forge,
+            // NeoForge 只有在 1.20.1 时是 Forge 的分支，可以加载 Forge 模组，1.20.2 起不再兼容
+            VersionType::NeoForge => {
+                loader == ModLoaderType::NeoForge
+                    || (loader == ModLoaderType::Forge
+                        && matches!(self.minecraft_version, MinecraftVersion::Release(1, 20, 1)))
+            }
+            VersionType::Vanilla | VersionType::Optifine => false,
+            VersionType::Unknown => true,
+        }
+    }
+
+    /// 由模组加载器本身提供的模组 ID，不需要安装对应的模组文件
+    fn is_builtin(&self, mod_id: &str) -> bool {
+        matches!(mod_id, "minecraft" | "java")
+            || match self.version_type {
+                VersionType::Fabric => mod_id == "fabricloader",
+                VersionType::QuiltMC => {
+                    matches!(mod_id, "fabricloader" | "quilt_loader")
+                }
+                VersionType::Forge => matches!(mod_id, "forge" | "javafml" | "fml"),
+                VersionType::NeoForge => {
+                    matches!(mod_id, "neoforge" | "forge" | "javafml" | "fml")
+                }
+                _ => true,
+            }
+    }
+
+    fn minecraft_version(&self) -> Option<String> {
+        match self.minecraft_version {
+            MinecraftVersion::Release(..) => Some(self.minecraft_version.to_string()),
+            _ => None,
+        }
+    }
+}
+
+/// 检查所有模组的依赖关系及冲突，返回检查出的问题
+///
+/// 传入的模组文件应当只包含已经启用的模组
+pub fn check_mods(files: &[ModFile], target: &ModCheckTarget) -> Vec<ModIssue> {
+    let mut issues = Vec::new();
+    // 所有可以被当前加载器加载的模组，以及提供的模组 ID 对应的模组
+    let mut loaded = Vec::new();
+    let mut providers: HashMap<&str, Vec<(&ModFile, &ModInfo)>> = HashMap::new();
+    for file in files {
+        let (accepted, rejected): (Vec<_>, Vec<_>) =
+            file.mods.iter().partition(|x| target.can_load(x.loader));
+        if accepted.is_empty() {
+            if let Some(info) = rejected.iter().find(|x| !x.is_nested()) {
+                issues.push(ModIssue::new(
+                    file,
+                    info,
+                    ModIssueSeverity::Error,
+                    ModIssueKind::WrongLoader {
+                        loader: info.loader,
+                    },
+                ));
+            }
+            continue;
+        }
+        for info in accepted {
+            loaded.push((file, info));
+            for id in std::iter::once(&info.mod_id).chain(info.provides.iter()) {
+                providers.entry(id.as_str()).or_default().push((file, info));
+            }
+        }
+    }
+
+    // 重复的模组，嵌套模组由加载器自动选择版本，所以只检查顶层模组
+    let mut seen: HashMap<&str, &ModFile> = HashMap::new();
+    for (file, info) in loaded.iter().filter(|x| !x.1.is_nested()) {
+        match seen.get(info.mod_id.as_str()) {
+            Some(other) if other.file_name != file.file_name => issues.push(ModIssue::new(
+                file,
+                info,
+                ModIssueSeverity::Error,
+                ModIssueKind::DuplicateMod {
+                    other_file_name: other.file_name.to_owned(),
+                },
+            )),
+            Some(_) => {}
+            None => {
+                seen.insert(info.mod_id.as_str(), file);
+            }
+        }
+    }
+
+    for (file, info) in loaded.iter() {
+        for dep in info
+            .dependencies
+            .iter()
+            .filter(|x| x.environment != ModEnvironment::Server)
+        {
+            check_dependency(file, info, dep, target, &providers, &mut issues);
+        }
+    }
+    issues
+}
+
+fn check_dependency(
+    file: &ModFile,
+    info: &ModInfo,
+    dep: &ModDependency,
+    target: &ModCheckTarget,
+    providers: &HashMap<&str, Vec<(&ModFile, &ModInfo)>>,
+    issues: &mut Vec<ModIssue>,
+) {
+    let satisfies = |version: &str| {
+        dep.version_ranges.is_empty()
+            || dep
+                .version_ranges
+                .iter()
+                .any(|range| version_matches(info.loader, range, version).unwrap_or(true))
+    };
+    let mut mismatch = |found_version: &str, severity| {
+        issues.push(ModIssue::new(
+            file,
+            info,
+            severity,
+            ModIssueKind::VersionMismatch {
+                dependency: dep.mod_id.to_owned(),
+                version_ranges: dep.version_ranges.to_owned(),
+                found_version: found_version.to_owned(),
+            },
+        ))
+    };
+    // Fabric 的可选依赖版本不符时只会输出警告，Forge 则会无法加载
+    let optional_severity = match info.loader {
+        ModLoaderType::Fabric | ModLoaderType::Quilt => ModIssueSeverity::Warning,
+        ModLoaderType::Forge | ModLoaderType::NeoForge => ModIssueSeverity::Error,
+    };
+
+    if dep.mod_id == "minecraft" {
+        if let Some(version) = target.minecraft_version() {
+            if matches!(
+                dep.kind,
+                ModDependencyKind::Required | ModDependencyKind::Optional
+            ) && !satisfies(&version)
+            {
+                mismatch(&version, ModIssueSeverity::Error);
+            }
+        }
+        return;
+    }
+    if target.is_builtin(&dep.mod_id) {
+        return;
+    }
+    let found = providers
+        .get(dep.mod_id.as_str())
+        .map(|x| x.as_slice())
+        .unwrap_or_default();
+    match dep.kind {
+        ModDependencyKind::Required | ModDependencyKind::Optional => {
+            if found.is_empty() {
+                if dep.kind == ModDependencyKind::Required {
+                    issues.push(ModIssue::new(
+                        file,
+                        info,
+                        ModIssueSeverity::Error,
+                        ModIssueKind::MissingDependency {
+                            dependency: dep.mod_id.to_owned(),
+                            version_ranges: dep.version_ranges.to_owned(),
+                        },
+                    ));
+                }
+            } else if !found.iter().any(|(_, x)| satisfies(&x.version)) {
+                let severity = if dep.kind == ModDependencyKind::Required {
+                    ModIssueSeverity::Error
+                } else {
+                    optional_severity
+                };
+                mismatch(&found[0].1.version, severity);
+            }
+        }
+        ModDependencyKind::Breaks | ModDependencyKind::Conflicts => {
+            if let Some((other_file, other)) = found
+                .iter()
+                .find(|(f, x)| f.file_name != file.file_name && satisfies(&x.version))
+            {
+                let (severity, kind) = if dep.kind == ModDependencyKind::Breaks {
+                    (
+                        ModIssueSeverity::Error,
+                        ModIssueKind::Breaks {
+                            other_mod_id: other.mod_id.to_owned(),
+                            other_file_name: other_file.file_name.to_owned(),
+                        },
+                    )
+                } else {
+                    (
+                        ModIssueSeverity::Warning,
+                        ModIssueKind::Conflicts {
+                            other_mod_id: other.mod_id.to_owned(),
+                            other_file_name: other_file.file_name.to_owned(),
+                        },
+                    )
+                };
+                issues.push(ModIssue::new(file, info, severity, kind));
+            }
+        }
+    }
+}
+
+/// 检查版本号是否满足版本要求，无法解析时返回 `None`
+///
+/// Forge/NeoForge 模组使用 Maven 版本范围，Fabric/Quilt 模组使用 SemVer 版本谓词
+pub fn version_matches(loader: ModLoaderType, range: &str, version: &str) -> Option<bool> {
+    if range.contains("${") || version.contains("${") {
+        return None;
+    }
+    match loader {
+        ModLoaderType::Fabric | ModLoaderType::Quilt => semver_predicate_matches(range, version),
+        ModLoaderType::Forge | ModLoaderType::NeoForge => maven_range_matches(range, version),
+    }
+}
+
+/// 版本号中的一个部分
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum VersionPart {
+    Number(u64),
+    Text(String),
+}
+
+impl VersionPart {
+    fn parse(part: &str) -> Self {
+        part.parse()
+            .map(Self::Number)
+            .unwrap_or_else(|_| Self::Text(part.to_ascii_lowercase()))
+    }
+}
+
+/// 一个 SemVer 版本号，构建信息会被忽略
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct SemVersion {
+    numbers: Vec<u64>,
+    pre_release: Vec<VersionPart>,
+}
+
+impl SemVersion {
+    fn parse(version: &str) -> Option<Self> {
+        let version = version.trim();
+        let version = version.split_once('+').map(|x| x.0).unwrap_or(version);
+        let (core, pre_release) = version.split_once('-').unwrap_or((version, ""));
+        let numbers = core
+            .split('.')
+            .map(|x| x.parse().ok())
+            .collect::<Option<Vec<u64>>>()?;
+        let pre_release = if pre_release.is_empty() {
+            vec![]
+        } else {
+            pre_release.split('.').map(VersionPart::parse).collect()
+        };
+        Some(Self {
+            numbers,
+            pre_release,
+        })
+    }
+}
+
+impl PartialOrd for SemVersion {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for SemVersion {
+    fn cmp(&self, other: &Self) -> Ordering {
+        let len = self.numbers.len().max(other.numbers.len());
+        for i in 0..len {
+            let a = self.numbers.get(i).copied().unwrap_or_default();
+            let b = other.numbers.get(i).copied().unwrap_or_default();
+            match a.cmp(&b) {
+                Ordering::Equal => {}
+                ord => return ord,
+            }
+        }
+        // 没有预发布标识的版本更大
+        match (self.pre_release.is_empty(), other.pre_release.is_empty()) {
+            (true, true) => return Ordering::Equal,
+            (true, false) => return Ordering::Greater,
+            (false, true) => return Ordering::Less,
+            _ => {}
+        }
+        for (a, b) in self.pre_release.iter().zip(other.pre_release.iter()) {
+            let ord = match (a, b) {
+                (VersionPart::Number(a), VersionPart::Number(b)) => a.cmp(b),
+                (VersionPart::Number(_), VersionPart::Text(_)) => Ordering::Less,
+                (VersionPart::Text(_), VersionPart::Number(_)) => Ordering::Greater,
+                (VersionPart::Text(a), VersionPart::Text(b)) => a.cmp(b),
+            };
+            if ord != Ordering::Equal {
+                return ord;
+            }
+        }
+        self.pre_release.len().cmp(&other.pre_release.len())
+    }
+}
+
+/// 检查版本号是否满足 Fabric 的版本谓词，多个以空格分隔的谓词需要同时满足
+///
+/// 参考 <https://fabricmc.net/wiki/documentation:fabric_mod_json_spec#versionrange>
+fn semver_predicate_matches(predicate: &str, version: &str) -> Option<bool> {
+    let predicate = predicate.trim();
+    if predicate.is_empty() || predicate == "*" {
+        return Some(true);
+    }
+    let Some(parsed) = SemVersion::parse(version) else {
+        // 非 SemVer 版本号只支持完全相等的比较
+        return Some(predicate.trim_start_matches('=') == version);
+    };
+    for predicate in predicate.split_whitespace() {
+        let (op, target) = ["<=", ">=", "<", ">", "=", "~", "^"]
+            .iter()
+            .find_map(|op| predicate.strip_prefix(op).map(|x| (*op, x)))
+            .unwrap_or(("", predicate));
+        // 形如 1.20.x 的通配符
+        let wildcard = target.split('.').position(|x| matches!(x, "x" | "X" | "*"));
+        if let Some(wildcard) = wildcard {
+            let prefix = target
+                .split('.')
+                .take(wildcard)
+                .map(|x| x.parse().ok())
+                .collect::<Option<Vec<u64>>>()?;
+            if !prefix
+                .iter()
+                .enumerate()
+                .all(|(i, x)| parsed.numbers.get(i).copied().unwrap_or_default() == *x)
+            {
+                return Some(false);
+            }
+            continue;
+        }
+        let target = SemVersion::parse(target)?;
+        let matched = match op {
+            "<=" => parsed <= target,
+            ">=" => parsed >= target,
+            "<" => parsed < target,
+            ">" => parsed > target,
+            "~" | "^" => {
+                // ~ 要求主次版本相同，^ 要求主版本相同
+                let same = if op == "~" { 2 } else { 1 };
+                parsed >= target
+                    && (0..same).all(|i| {
+                        parsed.numbers.get(i).copied().unwrap_or_default()
+                            == target.numbers.get(i).copied().unwrap_or_default()
+                    })
+            }
+            _ => parsed == target,
+        };
+        if !matched {
+            return Some(false);
+        }
+    }
+    Some(true)
+}
+
+/// 将 Maven 版本号拆分为可以比较的部分
+fn maven_version_parts(version: &str) -> Vec<VersionPart> {
+    version
+        .trim()
+        .split(['.', '-', '_'])
+        .filter(|x| !x.is_empty())
+        .map(VersionPart::parse)
+        .collect()
+}
+
+/// 近似 Maven `ComparableVersion` 的比较方式：数字按数值比较，缺少的部分视为 `0`，
+/// 而 `alpha`、`beta`、`rc`、`snapshot` 等限定词视为预发布版本，比对应的正式版本小
+fn maven_compare(a: &str, b: &str) -> Ordering {
+    fn qualifier_rank(text: &str) -> (u8, &str) {
+        match text {
+            "alpha" | "a" => (0, ""),
+            "beta" | "b" => (1, ""),
+            "milestone" | "m" => (2, ""),
+            "rc" | "cr" => (3, ""),
+            "snapshot" => (4, ""),
+            "" | "ga" | "final" | "release" => (5, ""),
+            "sp" => (6, ""),
+            other => (7, other),
+        }
+    }
+    let a = maven_version_parts(a);
+    let b = maven_version_parts(b);
+    let len = a.len().max(b.len());
+    for i in 0..len {
+        let ord = match (a.get(i), b.get(i)) {
+            (Some(VersionPart::Number(x)), Some(VersionPart::Number(y))) => x.cmp(y),
+            (Some(VersionPart::Number(x)), None) => x.cmp(&0),
+            (None, Some(VersionPart::Number(y))) => 0.cmp(y),
+            (Some(VersionPart::Text(_)), Some(VersionPart::Number(_))) => Ordering::Less,
+            (Some(VersionPart::Number(_)), Some(VersionPart::Text(_))) => Ordering::Greater,
+            (Some(VersionPart::Text(x)), Some(VersionPart::Text(y))) => {
+                qualifier_rank(x).cmp(&qualifier_rank(y))
+            }
+            (Some(VersionPart::Text(x)), None) => qualifier_rank(x).cmp(&qualifier_rank("")),
+            (None, Some(VersionPart::Text(y))) => qualifier_rank("").cmp(&qualifier_rank(y)),
+            (None, None) => Ordering::Equal,
+        };
+        if ord != Ordering::Equal {
+            return ord;
+        }
+    }
+    Ordering::Equal
+}
+
+/// 检查版本号是否满足 Maven 版本范围，多个范围之间以逗号分隔，满足其一即可
+///
+/// 参考 <https://maven.apache.org/enforcer/enforcer-rules/versionRanges.html>
+fn maven_range_matches(range: &str, version: &str) -> Option<bool> {
+    let range = range.trim();
+    if range.is_empty() || range == "*" {
+        return Some(true);
+    }
+    // 不带括号的版本号在 Maven 中只是推荐版本，不作限制
+    if !range.starts_with(['[', '(']) {
+        return Some(true);
+    }
+    let mut rest = range;
+    while !rest.is_empty() {
+        let rest_trimmed = rest.trim_start_matches([',', ' ']);
+        let lower_inclusive = match rest_trimmed.chars().next()? {
+            '[' => true,
+            '(' => false,
+            _ => return None,
+        };
+        let end = rest_trimmed.find([']', ')'])?;
+        let upper_inclusive = rest_trimmed[end..].starts_with(']');
+        let body = &rest_trimmed[1..end];
+        rest = &rest_trimmed[end + 1..];
+        let matched = if let Some((lower, upper)) = body.split_once(',') {
+            let lower = lower.trim();
+            let upper = upper.trim();
+            let lower_ok = lower.is_empty()
+                || match maven_compare(version, lower) {
+                    Ordering::Greater => true,
+                    Ordering::Equal => lower_inclusive,
+                    Ordering::Less => false,
+                };
+            let upper_ok = upper.is_empty()
+                || match maven_compare(version, upper) {
+                    Ordering::Less => true,
+                    Ordering::Equal => upper_inclusive,
+                    Ordering::Greater => false,
+                };
+            lower_ok && upper_ok
+        } else {
+            // [1.0] 代表精确版本
+            maven_compare(version, body.trim()) == Ordering::Equal
+        };
+        if matched {
+            return Some(true);
+        }
+    }
+    Some(false)
+}
+
+#[test]
+fn check_mods_test() {
+    assert_eq!(semver_predicate_matches(">=0.5.4", "0.5.3"), Some(false));
+    assert_eq!(semver_predicate_matches("1.20.x", "1.20.1"), Some(true));
+    assert_eq!(semver_predicate_matches("~1.20", "1.20.4"), Some(true));
+    assert_eq!(semver_predicate_matches("^0.14.0", "1.0.0"), Some(false));
+    assert_eq!(
+        semver_predicate_matches(">=1.0.0-beta.2 <2", "1.0.0-beta.10"),
+        Some(true)
+    );
+    assert_eq!(
+        semver_predicate_matches(">=1.0.0", "1.0.0-rc.1"),
+        Some(false)
+    );
+    assert_eq!(maven_range_matches("[47.1.3,)", "47.2.0"), Some(true));
+    assert_eq!(maven_range_matches("[1.18,1.19)", "1.20.1"), Some(false));
+    assert_eq!(maven_range_matches("[1.20.1]", "1.20.1"), Some(true));
+    assert_eq!(maven_range_matches("(,1.0],[1.2,)", "1.1"), Some(false));
+    assert_eq!(maven_range_matches("(,1.0],[1.2,)", "1.2.0"), Some(true));
+    assert_eq!(maven_range_matches("[1.0,)", "1.0-SNAPSHOT"), Some(false));
+
+    let fixtures = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/mods");
+    let mut files: Vec<_> = std::fs::read_dir(&fixtures)
+        .unwrap()
+        .map(|x| x.unwrap().path())
+        .filter(|x| x.extension().map(|x| x == "jar").unwrap_or_default())
+        .map(|path| {
+            let mut z = zip::ZipArchive::new(std::fs::File::open(&path).unwrap()).unwrap();
+            ModFile {
+                file_name: path.file_name().unwrap().to_string_lossy().to_string(),
+                mods: super::mod_info::read_mod_infos(&mut z),
+            }
+        })
+        .collect();
+    files.sort_by(|a, b| a.file_name.cmp(&b.file_name));
+    let jei = files
+        .iter()
+        .find(|x| x.file_name == "jei-forge-15.2.0.jar")
+        .unwrap();
+    assert_eq!(jei.mods[0].version, "15.2.0");
+    let bundle = files
+        .iter()
+        .find(|x| x.file_name == "library-bundle-1.0.0.jar")
+        .unwrap();
+    assert_eq!(bundle.mods.len(), 2);
+    assert!(bundle.mods[1].is_nested());
+
+    let describe = |issues: &[ModIssue]| {
+        let mut result: Vec<_> = issues
+            .iter()
+            .map(|x| {
+                let kind = match &x.kind {
+                    ModIssueKind::MissingDependency { dependency, .. } => {
+                        format!("missing {dependency}")
+                    }
+                    ModIssueKind::VersionMismatch { dependency, .. } => {
+                        format!("version {dependency}")
+                    }
+                    ModIssueKind::DuplicateMod { .. } => "duplicate".into(),
+                    ModIssueKind::Breaks { other_mod_id, .. } => format!("breaks {other_mod_id}"),
+                    ModIssueKind::Conflicts { other_mod_id, .. } => {
+                        format!("conflicts {other_mod_id}")
+                    }
+                    ModIssueKind::WrongLoader { .. } => "loader".into(),
+                };
+                format!("{} {}", x.file_name, kind)
+            })
+            .collect();
+        result.sort();
+        result
+    };
+
+    let fabric = ModCheckTarget {
+        version_type: VersionType::Fabric,
+        minecraft_version: MinecraftVersion::Release(1, 20, 1),
+    };
+    assert_eq!(
+        describe(&check_mods(&files, &fabric)),
+        [
+            "iris-fabric-1.6.4.jar breaks optifabric",
+            "iris-fabric-1.6.4.jar version sodium",
+            "jei-forge-15.2.0.jar loader",
+            "old-forge-mod-1.0.jar loader",
+            "sodium-fabric-0.5.3.jar duplicate",
+            "sodium-fabric-0.5.3.jar missing fabric-api",
+        ]
+    );
+
+    let forge = ModCheckTarget {
+        version_type: VersionType::Forge,
+        minecraft_version: MinecraftVersion::Release(1, 20, 1),
+    };
+    let issues = check_mods(&files, &forge);
+    assert_eq!(
+        describe(&issues),
+        [
+            "iris-fabric-1.6.4.jar loader",
+            "library-bundle-1.0.0.jar loader",
+            "old-forge-mod-1.0.jar version jei",
+            "old-forge-mod-1.0.jar version minecraft",
+            "optifabric-1.14.3.jar loader",
+            "sodium-fabric-0.5.3-copy.jar loader",
+            "sodium-fabric-0.5.3.jar loader",
+        ]
+    );
+    assert!(issues.iter().all(|x| x.is_error()));
+
+    let neoforge = |minor, patch| ModCheckTarget {
+        version_type: VersionType::NeoForge,
+        minecraft_version: MinecraftVersion::Release(1, minor, patch),
+    };
+    assert!(neoforge(20, 1).can_load(ModLoaderType::Forge));
+    assert!(!neoforge(20, 4).can_load(ModLoaderType::Forge));
+    assert!(neoforge(20, 4).can_load(ModLoaderType::NeoForge));
+    assert_eq!(
+        describe(&check_mods(&files, &neoforge(20, 4))),
+        [
+            "iris-fabric-1.6.4.jar loader",
+            "jei-forge-15.2.0.jar loader",
+            "library-bundle-1.0.0.jar loader",
+            "old-forge-mod-1.0.jar loader",
+            "optifabric-1.14.3.jar loader",
+            "sodium-fabric-0.5.3-copy.jar loader",
+            "sodium-fabric-0.5.3.jar loader",
+        ]
+    );
+}
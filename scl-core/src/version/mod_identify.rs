@@ -0,0 +1,180 @@
+//! 通过文件摘要在 Modrinth 和 CurseForge 上识别本地模组文件
+//!
+//! Modrinth 使用文件的 SHA-1 或 SHA-512 摘要查询，CurseForge 则使用去除空白字符后计算的 MurmurHash2 指纹查询，
+//! 这样即使是用户手动放入的模组，也可以获取到它在平台上的名称、图标、更新和依赖信息。
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use sha2::{Digest, Sha512};
+
+use super::mods::Mod;
+use crate::{
+    download::{curseforge, modrinth},
+    prelude::*,
+};
+
+/// 一个文件在各个平台上用于识别的摘要
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ModHashes {
+    /// 文件的 SHA-1 摘要，十六进制小写
+    pub sha1: String,
+    /// 文件的 SHA-512 摘要，十六进制小写
+    pub sha512: String,
+    /// 文件的 CurseForge 指纹，参考 [`curseforge_fingerprint`]
+    pub curseforge_fingerprint: u32,
+}
+
+impl ModHashes {
+    /// 计算文件数据的所有摘要
+    pub fn from_data(data: &[u8]) -> Self {
+        Self {
+            sha1: sha1_smol::Sha1::from(data).hexdigest(),
+            sha512: format!("{:x}", Sha512::digest(data)),
+            curseforge_fingerprint: curseforge_fingerprint(data),
+        }
+    }
+
+    /// 读取并计算文件的所有摘要
+    pub async fn from_path(path: impl AsRef<Path>) -> DynResult<Self> {
+        let data = inner_future::fs::read(path).await?;
+        Ok(inner_future::unblock(move || Self::from_data(&data)).await)
+    }
+}
+
+/// 计算 CurseForge 使用的文件指纹
+///
+/// 即去除所有空白字符（`\t`、`\n`、`\r` 和空格）后，以 `1` 为种子计算的 32 位 MurmurHash2 值
+pub fn curseforge_fingerprint(data: &[u8]) -> u32 {
+    const M: u32 = 0x5bd1_e995;
+    const R: u32 = 24;
+    let data: Vec<u8> = data
+        .iter()
+        .copied()
+        .filter(|b| !matches!(b, 9 | 10 | 13 | 32))
+        .collect();
+    let mut h = 1 ^ data.len() as u32;
+    let chunks = data.chunks_exact(4);
+    let rest = chunks.remainder();
+    for chunk in chunks {
+        let mut k = u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+        k = k.wrapping_mul(M);
+        k ^= k >> R;
+        k = k.wrapping_mul(M);
+        h = h.wrapping_mul(M);
+        h ^= k;
+    }
+    if !rest.is_empty() {
+        for (i, b) in rest.iter().enumerate() {
+            h ^= (*b as u32) << (8 * i);
+        }
+        h = h.wrapping_mul(M);
+    }
+    h ^= h >> 13;
+    h = h.wrapping_mul(M);
+    h ^= h >> 15;
+    h
+}
+
+/// 一个本地模组文件的识别结果
+#[derive(Debug, Clone)]
+pub struct ModIdentity {
+    /// 模组的文件名
+    pub file_name: String,
+    /// 模组的文件所在路径
+    pub path: PathBuf,
+    /// 模组文件的摘要
+    pub hashes: ModHashes,
+    /// 模组文件在 Modrinth 上对应的版本，可以通过 [`modrinth::ModVersion::project_id`] 获取所属的模组
+    pub modrinth: Option<modrinth::ModVersion>,
+    /// 模组文件在 CurseForge 上对应的文件，可以通过 [`curseforge::ModFile::mod_id`] 获取所属的模组
+    pub curseforge: Option<curseforge::ModFile>,
+}
+
+impl ModIdentity {
+    /// 是否在任意平台上识别到了该模组
+    pub fn is_identified(&self) -> bool {
+        self.modrinth.is_some() || self.curseforge.is_some()
+    }
+}
+
+/// 批量识别本地模组文件，返回的结果和传入的模组顺序一致
+///
+/// 两个平台会分别查询，如果其中一个平台查询失败，则该平台的结果均为 `None`，两个平台都失败时才会返回错误
+pub async fn identify_mods(mods: &[Mod]) -> DynResult<Vec<ModIdentity>> {
+    let mut identities = Vec::with_capacity(mods.len());
+    for m in mods {
+        identities.push(ModIdentity {
+            file_name: m.file_name().to_owned(),
+            path: m.path().to_owned(),
+            hashes: ModHashes::from_path(m.path()).await?,
+            modrinth: None,
+            curseforge: None,
+        });
+    }
+    if identities.is_empty() {
+        return Ok(identities);
+    }
+
+    let sha512: Vec<_> = identities.iter().map(|x| x.hashes.sha512.clone()).collect();
+    let fingerprints: Vec<_> = identities
+        .iter()
+        .map(|x| x.hashes.curseforge_fingerprint)
+        .collect();
+    let (modrinth_result, curseforge_result) = futures::join!(
+        modrinth::get_versions_by_hashes(&sha512, modrinth::HashAlgorithm::Sha512),
+        curseforge::get_fingerprint_matches(&fingerprints)
+    );
+    if let (Err(modrinth_err), Err(curseforge_err)) = (&modrinth_result, &curseforge_result) {
+        anyhow::bail!(
+            "无法识别模组文件，Modrinth：{:?}，CurseForge：{:?}",
+            modrinth_err,
+            curseforge_err
+        );
+    }
+
+    match modrinth_result {
+        Ok(versions) => {
+            for identity in identities.iter_mut() {
+                identity.modrinth = versions.get(&identity.hashes.sha512).cloned();
+            }
+        }
+        Err(err) => tracing::warn!("无法从 Modrinth 识别模组文件：{:?}", err),
+    }
+    match curseforge_result {
+        Ok(matches) => {
+            let files: HashMap<u32, curseforge::ModFile> = matches
+                .into_iter()
+                .map(|x| (x.file.file_fingerprint, x.file))
+                .collect();
+            for identity in identities.iter_mut() {
+                identity.curseforge = files.get(&identity.hashes.curseforge_fingerprint).cloned();
+            }
+        }
+        Err(err) => tracing::warn!("无法从 CurseForge 识别模组文件：{:?}", err),
+    }
+    Ok(identities)
+}
+
+#[test]
+fn mod_hashes_test() {
+    assert_eq!(curseforge_fingerprint(b""), 0x5bd1_5e36);
+    assert_eq!(
+        curseforge_fingerprint(b"hello world\r\n"),
+        curseforge_fingerprint(b"helloworld")
+    );
+    assert_ne!(
+        curseforge_fingerprint(b"abcd"),
+        curseforge_fingerprint(b"abce")
+    );
+
+    let hashes = ModHashes::from_data(b"abc");
+    assert_eq!(hashes.sha1, "a9993e364706816aba3e25717850c26c9cd0d89d");
+    assert_eq!(
+        hashes.sha512,
+        "ddaf35a193617abacc417349ae20413112e6fa4e89a97ea20a9eeee64b55d39a\
+         2192992a274fc1a836ba3c23a3feebbd454d4423643ce80e2a9ac94fa54ca49f"
+    );
+}
@@ -0,0 +1,774 @@
+//! 模组元数据的完整解析
+//!
+//! 支持以下格式的元数据，并会递归读取嵌套在模组文件内的模组（Jar-in-Jar）：
+//! - Fabric 的 `fabric.mod.json`
+//! - Quilt 的 `quilt.mod.json`
+//! - Forge 的 `META-INF/mods.toml` 及 1.12.2 以前的 `mcmod.info`
+//! - NeoForge 的 `META-INF/neoforge.mods.toml`
+//! - Forge 及 NeoForge 的嵌套模组列表 `META-INF/jarjar/metadata.json`
+
+use std::io::{Cursor, Read, Seek};
+
+use serde_json::Value;
+use zip::ZipArchive;
+
+use crate::prelude::*;
+
+/// 嵌套模组的最大读取深度
+const MAX_NESTED_DEPTH: usize = 4;
+
+/// 模组所对应的模组加载器
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ModLoaderType {
+    /// Fabric 模组，使用 `fabric.mod.json`
+    Fabric,
+    /// Quilt 模组，使用 `quilt.mod.json`
+    Quilt,
+    /// Forge 模组，使用 `META-INF/mods.toml` 或 `mcmod.info`
+    Forge,
+    /// NeoForge 模组，使用 `META-INF/neoforge.mods.toml`
+    NeoForge,
+}
+
+/// 模组声明的运行环境
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum ModEnvironment {
+    /// 客户端和服务端均可运行
+    #[default]
+    Both,
+    /// 仅客户端
+    Client,
+    /// 仅服务端
+    Server,
+}
+
+impl ModEnvironment {
+    fn parse(value: &str) -> Self {
+        match value.to_ascii_lowercase().as_str() {
+            "client" => Self::Client,
+            "server" | "dedicated_server" => Self::Server,
+            _ => Self::Both,
+        }
+    }
+}
+
+/// 模组依赖关系的种类
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ModDependencyKind {
+    /// 必须安装的前置模组
+    ///
+    /// 对应 Fabric/Quilt 的 `depends` 和 Forge/NeoForge 的必需依赖
+    Required,
+    /// 可选的模组，如果安装了则需要满足版本要求
+    ///
+    /// 对应 Fabric 的 `recommends` 及 `suggests`、Quilt 的可选依赖和 Forge/NeoForge 的可选依赖
+    Optional,
+    /// 不兼容的模组，同时安装会导致游戏无法启动
+    ///
+    /// 对应 Fabric/Quilt 的 `breaks` 和 NeoForge 的 `incompatible`
+    Breaks,
+    /// 可能存在冲突的模组，同时安装时游戏依然可以启动，但可能会出现问题
+    ///
+    /// 对应 Fabric 的 `conflicts` 和 NeoForge 的 `discouraged`
+    Conflicts,
+}
+
+/// 模组声明的一个依赖关系
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ModDependency {
+    /// 依赖的模组 ID
+    pub mod_id: String,
+    /// 依赖关系的种类
+    pub kind: ModDependencyKind,
+    /// 版本要求，满足其中任意一个即可，为空时代表任意版本
+    ///
+    /// Fabric/Quilt 模组为 SemVer 版本谓词，例如 `>=0.14.0`；
+    /// Forge/NeoForge 模组为 Maven 版本范围，例如 `[1.20,1.21)`
+    pub version_ranges: Vec<String>,
+    /// 该依赖关系生效的运行环境
+    pub environment: ModEnvironment,
+}
+
+/// 一个模组的完整元数据
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ModInfo {
+    /// 元数据所属的模组加载器
+    pub loader: ModLoaderType,
+    /// 模组 ID
+    pub mod_id: String,
+    /// 模组的名称，元数据没有提供时为模组 ID
+    pub name: String,
+    /// 模组的版本号
+    pub version: String,
+    /// 模组的介绍
+    pub description: String,
+    /// 模组的作者
+    pub authors: Vec<String>,
+    /// 模组的许可证
+    pub license: String,
+    /// 模组的主页
+    pub homepage: String,
+    /// 模组声明的运行环境
+    pub environment: ModEnvironment,
+    /// 模组的图标文件在模组文件内的路径
+    pub icon: Option<String>,
+    /// 模组声明的依赖关系
+    pub dependencies: Vec<ModDependency>,
+    /// 模组额外提供的模组 ID，其它模组可以通过这些 ID 依赖此模组
+    pub provides: Vec<String>,
+    /// 如果是嵌套在其它模组文件内的模组，则为嵌套文件在外层文件中的路径，多层嵌套时使用 `!/` 连接
+    pub nested_path: Option<String>,
+}
+
+impl ModInfo {
+    fn new(loader: ModLoaderType, mod_id: String) -> Self {
+        Self {
+            loader,
+            name: mod_id.to_owned(),
+            mod_id,
+            version: String::new(),
+            description: String::new(),
+            authors: Vec::new(),
+            license: String::new(),
+            homepage: String::new(),
+            environment: ModEnvironment::Both,
+            icon: None,
+            dependencies: Vec::new(),
+            provides: Vec::new(),
+            nested_path: None,
+        }
+    }
+
+    /// 该模组是否是嵌套在其它模组文件内的模组
+    pub fn is_nested(&self) -> bool {
+        self.nested_path.is_some()
+    }
+}
+
+fn read_entry<R: Read + Seek>(z: &mut ZipArchive<R>, name: &str) -> Option<Vec<u8>> {
+    let mut r = z.by_name(name).ok()?;
+    let mut buf = Vec::with_capacity(r.size() as _);
+    r.read_to_end(&mut buf).ok()?;
+    Some(buf)
+}
+
+fn as_string(value: &Value) -> String {
+    value.as_str().unwrap_or_default().to_owned()
+}
+
+/// 将一个字符串或者字符串数组转换成字符串数组
+fn string_or_array(value: &Value) -> Vec<String> {
+    match value {
+        Value::String(s) => vec![s.to_owned()],
+        Value::Array(a) => a
+            .iter()
+            .filter_map(|x| x.as_str())
+            .map(|x| x.to_owned())
+            .collect(),
+        _ => vec![],
+    }
+}
+
+/// 将版本要求统一为数组，`*` 代表任意版本，转换为空数组
+fn version_ranges(value: &Value) -> Vec<String> {
+    let ranges = string_or_array(value);
+    if ranges.iter().any(|x| x.trim() == "*") {
+        vec![]
+    } else {
+        ranges
+    }
+}
+
+/// 从 `MANIFEST.MF` 中读取 `Implementation-Version`，用于替换 Forge 模组版本中的 `${file.jarVersion}`
+fn manifest_version<R: Read + Seek>(z: &mut ZipArchive<R>) -> Option<String> {
+    let manifest = read_entry(z, "META-INF/MANIFEST.MF")?;
+    String::from_utf8_lossy(&manifest)
+        .lines()
+        .find_map(|line| line.strip_prefix("Implementation-Version:"))
+        .map(|x| x.trim().to_owned())
+}
+
+fn parse_fabric(data: &[u8]) -> DynResult<(ModInfo, Vec<String>)> {
+    // https://fabricmc.net/wiki/documentation:fabric_mod_json_spec
+    let v: Value = serde_json::from_slice(data)?;
+    let mod_id = v["id"]
+        .as_str()
+        .ok_or_else(|| anyhow::anyhow!("fabric.mod.json 中缺少模组 ID"))?;
+    let mut info = ModInfo::new(ModLoaderType::Fabric, mod_id.to_owned());
+    if let Some(name) = v["name"].as_str() {
+        info.name = name.to_owned();
+    }
+    info.version = as_string(&v["version"]);
+    info.description = as_string(&v["description"]);
+    info.authors = v["authors"]
+        .as_array()
+        .map(|a| {
+            a.iter()
+                .filter_map(|x| x.as_str().or_else(|| x["name"].as_str()))
+                .map(|x| x.to_owned())
+                .collect()
+        })
+        .unwrap_or_default();
+    info.license = string_or_array(&v["license"]).join(", ");
+    info.homepage = as_string(&v["contact"]["homepage"]);
+    info.environment = ModEnvironment::parse(v["environment"].as_str().unwrap_or("*"));
+    info.icon = match &v["icon"] {
+        Value::String(icon) => Some(icon.to_owned()),
+        // 多个图标时选择尺寸最大的
+        Value::Object(icons) => icons
+            .iter()
+            .max_by_key(|(size, _)| size.parse::<u32>().unwrap_or_default())
+            .and_then(|(_, icon)| icon.as_str())
+            .map(|x| x.to_owned()),
+        _ => None,
+    };
+    for (key, kind) in [
+        ("depends", ModDependencyKind::Required),
+        ("recommends", ModDependencyKind::Optional),
+        ("suggests", ModDependencyKind::Optional),
+        ("breaks", ModDependencyKind::Breaks),
+        ("conflicts", ModDependencyKind::Conflicts),
+    ] {
+        if let Some(deps) = v[key].as_object() {
+            info.dependencies
+                .extend(deps.iter().map(|(mod_id, ranges)| ModDependency {
+                    mod_id: mod_id.to_owned(),
+                    kind,
+                    version_ranges: version_ranges(ranges),
+                    environment: ModEnvironment::Both,
+                }));
+        }
+    }
+    info.provides = string_or_array(&v["provides"]);
+    let jars = v["jars"]
+        .as_array()
+        .map(|a| a.iter().map(|x| as_string(&x["file"])).collect())
+        .unwrap_or_default();
+    Ok((info, jars))
+}
+
+fn parse_quilt_dependency(value: &Value, kind: ModDependencyKind) -> Option<ModDependency> {
+    match value {
+        Value::String(id) => Some(ModDependency {
+            mod_id: id.rsplit(':').next().unwrap_or_default().to_owned(),
+            kind,
+            version_ranges: vec![],
+            environment: ModEnvironment::Both,
+        }),
+        Value::Object(_) => Some(ModDependency {
+            mod_id: value["id"].as_str()?.rsplit(':').next()?.to_owned(),
+            kind: if kind == ModDependencyKind::Required
+                && value["optional"].as_bool().unwrap_or_default()
+            {
+                ModDependencyKind::Optional
+            } else {
+                kind
+            },
+            version_ranges: version_ranges(&value["versions"]),
+            environment: ModEnvironment::Both,
+        }),
+        _ => None,
+    }
+}
+
+fn parse_quilt(data: &[u8]) -> DynResult<(ModInfo, Vec<String>)> {
+    // https://github.com/QuiltMC/rfcs/blob/main/specification/0002-quilt.mod.json.md
+    let v: Value = serde_json::from_slice(data)?;
+    let loader = &v["quilt_loader"];
+    let mod_id = loader["id"]
+        .as_str()
+        .ok_or_else(|| anyhow::anyhow!("quilt.mod.json 中缺少模组 ID"))?;
+    let mut info = ModInfo::new(ModLoaderType::Quilt, mod_id.to_owned());
+    let metadata = &loader["metadata"];
+    if let Some(name) = metadata["name"].as_str() {
+        info.name = name.to_owned();
+    }
+    info.version = as_string(&loader["version"]);
+    info.description = as_string(&metadata["description"]);
+    info.authors = metadata["contributors"]
+        .as_object()
+        .map(|x| x.keys().cloned().collect())
+        .unwrap_or_default();
+    info.license = match &metadata["license"] {
+        Value::Object(license) => as_string(&license["id"]),
+        Value::Array(licenses) => licenses
+            .iter()
+            .map(|x| {
+                x.as_str()
+                    .unwrap_or_else(|| x["id"].as_str().unwrap_or_default())
+            })
+            .collect::<Vec<_>>()
+            .join(", "),
+        license => as_string(license),
+    };
+    info.homepage = as_string(&metadata["contact"]["homepage"]);
+    info.environment = ModEnvironment::parse(v["minecraft"]["environment"].as_str().unwrap_or("*"));
+    info.icon = match &metadata["icon"] {
+        Value::String(icon) => Some(icon.to_owned()),
+        Value::Object(icons) => icons
+            .iter()
+            .max_by_key(|(size, _)| size.parse::<u32>().unwrap_or_default())
+            .and_then(|(_, icon)| icon.as_str())
+            .map(|x| x.to_owned()),
+        _ => None,
+    };
+    for (key, kind) in [
+        ("depends", ModDependencyKind::Required),
+        ("breaks", ModDependencyKind::Breaks),
+    ] {
+        if let Some(deps) = loader[key].as_array() {
+            info.dependencies
+                .extend(deps.iter().filter_map(|x| parse_quilt_dependency(x, kind)));
+        }
+    }
+    info.provides = loader["provides"]
+        .as_array()
+        .map(|a| {
+            a.iter()
+                .filter_map(|x| x.as_str().or_else(|| x["id"].as_str()))
+                .map(|x| x.rsplit(':').next().unwrap_or_default().to_owned())
+                .collect()
+        })
+        .unwrap_or_default();
+    let jars = string_or_array(&loader["jars"]);
+    Ok((info, jars))
+}
+
+fn parse_forge_toml(
+    data: &[u8],
+    loader: ModLoaderType,
+    jar_version: Option<&str>,
+) -> DynResult<Vec<ModInfo>> {
+    // https://docs.minecraftforge.net/en/latest/gettingstarted/modfiles/
+    // https://docs.neoforged.net/docs/gettingstarted/modfiles
+    let v: toml::Value = toml::from_str(&String::from_utf8_lossy(data))?;
+    let get_str = |v: &toml::Value, key: &str| -> String {
+        v.get(key)
+            .and_then(|x| x.as_str())
+            .unwrap_or_default()
+            .to_owned()
+    };
+    let license = get_str(&v, "license");
+    let logo_file = get_str(&v, "logoFile");
+    let environment = if v
+        .get("clientSideOnly")
+        .and_then(|x| x.as_bool())
+        .unwrap_or_default()
+    {
+        ModEnvironment::Client
+    } else {
+        ModEnvironment::Both
+    };
+    let mods = v
+        .get("mods")
+        .and_then(|x| x.as_array())
+        .ok_or_else(|| anyhow::anyhow!("模组元数据中没有模组信息"))?;
+    let mut result = Vec::with_capacity(mods.len());
+    for m in mods {
+        let mod_id = get_str(m, "modId");
+        if mod_id.is_empty() {
+            continue;
+        }
+        let mut info = ModInfo::new(loader, mod_id.to_owned());
+        let name = get_str(m, "displayName");
+        if !name.is_empty() {
+            info.name = name;
+        }
+        info.version = get_str(m, "version");
+        if info.version == "${file.jarVersion}" {
+            info.version = jar_version.unwrap_or_default().to_owned();
+        }
+        info.description = get_str(m, "description").trim().to_owned();
+        info.authors = get_str(m, "authors")
+            .split(',')
+            .map(|x| x.trim())
+            .filter(|x| !x.is_empty())
+            .map(|x| x.to_owned())
+            .collect();
+        info.license = license.to_owned();
+        info.homepage = get_str(m, "displayURL");
+        info.environment = environment;
+        let logo = get_str(m, "logoFile");
+        info.icon = if !logo.is_empty() {
+            Some(logo)
+        } else if !logo_file.is_empty() {
+            Some(logo_file.to_owned())
+        } else {
+            None
+        };
+        if let Some(deps) = v
+            .get("dependencies")
+            .and_then(|x| x.get(&mod_id))
+            .and_then(|x| x.as_array())
+        {
+            for dep in deps {
+                let dep_id = get_str(dep, "modId");
+                if dep_id.is_empty() {
+                    continue;
+                }
+                let kind = match dep.get("type").and_then(|x| x.as_str()) {
+                    Some(t) => match t.to_ascii_lowercase().as_str() {
+                        "required" => ModDependencyKind::Required,
+                        "incompatible" => ModDependencyKind::Breaks,
+                        "discouraged" => ModDependencyKind::Conflicts,
+                        _ => ModDependencyKind::Optional,
+                    },
+                    None => {
+                        if dep
+                            .get("mandatory")
+                            .and_then(|x| x.as_bool())
+                            .unwrap_or(true)
+                        {
+                            ModDependencyKind::Required
+                        } else {
+                            ModDependencyKind::Optional
+                        }
+                    }
+                };
+                let range = get_str(dep, "versionRange");
+                info.dependencies.push(ModDependency {
+                    mod_id: dep_id,
+                    kind,
+                    version_ranges: if range.is_empty() || range == "*" {
+                        vec![]
+                    } else {
+                        vec![range]
+                    },
+                    environment: ModEnvironment::parse(&get_str(dep, "side")),
+                });
+            }
+        }
+        result.push(info);
+    }
+    Ok(result)
+}
+
+fn parse_mcmod_info(data: &[u8]) -> DynResult<Vec<ModInfo>> {
+    // https://docs.minecraftforge.net/en/1.12.x/gettingstarted/structuring/
+    let v: Value = serde_json::from_slice(data)?;
+    let mods = match &v {
+        Value::Array(mods) => mods,
+        // modListVersion 2 的格式
+        _ => v["modList"]
+            .as_array()
+            .ok_or_else(|| anyhow::anyhow!("mcmod.info 中没有模组信息"))?,
+    };
+    let mut result = Vec::with_capacity(mods.len());
+    for m in mods {
+        let Some(mod_id) = m["modid"].as_str() else {
+            continue;
+        };
+        let mut info = ModInfo::new(ModLoaderType::Forge, mod_id.to_owned());
+        if let Some(name) = m["name"].as_str() {
+            info.name = name.to_owned();
+        }
+        info.version = as_string(&m["version"]);
+        info.description = as_string(&m["description"]).trim().to_owned();
+        info.authors = if m["authorList"].is_array() {
+            string_or_array(&m["authorList"])
+        } else {
+            string_or_array(&m["authors"])
+        };
+        info.homepage = as_string(&m["url"]);
+        info.icon = m["logoFile"]
+            .as_str()
+            .filter(|x| !x.is_empty())
+            .map(|x| x.to_owned());
+        let mc_version = as_string(&m["mcversion"]);
+        if !mc_version.is_empty() {
+            info.dependencies.push(ModDependency {
+                mod_id: "minecraft".into(),
+                kind: ModDependencyKind::Required,
+                version_ranges: vec![format!("[{mc_version}]")],
+                environment: ModEnvironment::Both,
+            });
+        }
+        // 依赖格式为 `模组ID@版本范围`
+        info.dependencies
+            .extend(string_or_array(&m["requiredMods"]).iter().map(|x| {
+                let (mod_id, range) = x.split_once('@').unwrap_or((x, ""));
+                ModDependency {
+                    mod_id: mod_id.to_owned(),
+                    kind: ModDependencyKind::Required,
+                    version_ranges: if range.is_empty() {
+                        vec![]
+                    } else {
+                        vec![range.to_owned()]
+                    },
+                    environment: ModEnvironment::Both,
+                }
+            }));
+        result.push(info);
+    }
+    Ok(result)
+}
+
+fn parse_jarjar_metadata(data: &[u8]) -> Vec<String> {
+    serde_json::from_slice::<Value>(data)
+        .ok()
+        .and_then(|v| {
+            v["jars"]
+                .as_array()
+                .map(|a| a.iter().map(|x| as_string(&x["path"])).collect())
+        })
+        .unwrap_or_default()
+}
+
+fn read_mod_infos_inner<R: Read + Seek>(
+    z: &mut ZipArchive<R>,
+    nested_path: Option<&str>,
+    depth: usize,
+) -> Vec<ModInfo> {
+    let mut result = Vec::new();
+    let mut jars = Vec::new();
+    // 同时带有 fabric.mod.json 和 quilt.mod.json 的模组以前者为准，Quilt 也可以加载 Fabric 模组
+    if let Some(data) = read_entry(z, "fabric.mod.json") {
+        match parse_fabric(&data) {
+            Ok((info, nested)) => {
+                result.push(info);
+                jars.extend(nested);
+            }
+            Err(err) => tracing::warn!("无法解析 fabric.mod.json：{:?}", err),
+        }
+    } else if let Some(data) = read_entry(z, "quilt.mod.json") {
+        match parse_quilt(&data) {
+            Ok((info, nested)) => {
+                result.push(info);
+                jars.extend(nested);
+            }
+            Err(err) => tracing::warn!("无法解析 quilt.mod.json：{:?}", err),
+        }
+    }
+    let jar_version = manifest_version(z);
+    let mut has_mods_toml = false;
+    // 同时兼容 Forge 和 NeoForge 的模组会同时带有两个文件，相同 ID 的模组以 neoforge.mods.toml 为准
+    let toml_start = result.len();
+    for (file, loader) in [
+        ("META-INF/neoforge.mods.toml", ModLoaderType::NeoForge),
+        ("META-INF/mods.toml", ModLoaderType::Forge),
+    ] {
+        if let Some(data) = read_entry(z, file) {
+            has_mods_toml = true;
+            match parse_forge_toml(&data, loader, jar_version.as_deref()) {
+                Ok(infos) => {
+                    let declared: Vec<_> = result[toml_start..]
+                        .iter()
+                        .map(|x| x.mod_id.to_owned())
+                        .collect();
+                    result.extend(infos.into_iter().filter(|x| !declared.contains(&x.mod_id)));
+                }
+                Err(err) => tracing::warn!("无法解析 {}：{:?}", file, err),
+            }
+        }
+    }
+    if !has_mods_toml {
+        if let Some(data) = read_entry(z, "mcmod.info") {
+            match parse_mcmod_info(&data) {
+                Ok(infos) => result.extend(infos),
+                Err(err) => tracing::warn!("无法解析 mcmod.info：{:?}", err),
+            }
+        }
+    }
+    if let Some(data) = read_entry(z, "META-INF/jarjar/metadata.json") {
+        jars.extend(parse_jarjar_metadata(&data));
+    }
+    for info in result.iter_mut() {
+        info.nested_path = nested_path.map(|x| x.to_owned());
+    }
+    if depth < MAX_NESTED_DEPTH {
+        for jar in jars {
+            let Some(data) = read_entry(z, jar.trim_start_matches('/')) else {
+                continue;
+            };
+            let Ok(mut nested) = ZipArchive::new(Cursor::new(data)) else {
+                continue;
+            };
+            let path = match nested_path {
+                Some(parent) => format!("{parent}!/{jar}"),
+                None => jar,
+            };
+            result.extend(read_mod_infos_inner(&mut nested, Some(&path), depth + 1));
+        }
+    }
+    result
+}
+
+/// 读取一个模组文件压缩包内的所有模组元数据，包括嵌套在其中的模组
+///
+/// 顶层模组总是排在嵌套模组之前，无法解析的元数据会被跳过
+pub fn read_mod_infos<R: Read + Seek>(z: &mut ZipArchive<R>) -> Vec<ModInfo> {
+    read_mod_infos_inner(z, None, 0)
+}
+
+#[test]
+fn parse_mod_meta_test() {
+    let (fabric, jars) = parse_fabric(
+        br#"{
+            "schemaVersion": 1,
+            "id": "sodium",
+            "version": "0.5.8",
+            "name": "Sodium",
+            "authors": ["JellySquid", { "name": "IMS" }],
+            "contact": { "homepage": "https://modrinth.com/mod/sodium" },
+            "license": "LGPL-3.0-only",
+            "icon": { "32": "icon-32.png", "128": "icon-128.png" },
+            "environment": "client",
+            "depends": { "fabricloader": ">=0.12.0", "minecraft": ["1.20.1", "1.20.2"] },
+            "breaks": { "optifabric": "*" },
+            "jars": [{ "file": "META-INF/jars/fabric-api-base.jar" }]
+        }"#,
+    )
+    .unwrap();
+    assert_eq!(fabric.authors, ["JellySquid", "IMS"]);
+    assert_eq!(fabric.icon.as_deref(), Some("icon-128.png"));
+    assert_eq!(fabric.environment, ModEnvironment::Client);
+    assert_eq!(fabric.dependencies.len(), 3);
+    let minecraft = fabric
+        .dependencies
+        .iter()
+        .find(|x| x.mod_id == "minecraft")
+        .unwrap();
+    assert_eq!(minecraft.version_ranges, ["1.20.1", "1.20.2"]);
+    let optifabric = fabric
+        .dependencies
+        .iter()
+        .find(|x| x.mod_id == "optifabric")
+        .unwrap();
+    assert_eq!(optifabric.kind, ModDependencyKind::Breaks);
+    assert!(optifabric.version_ranges.is_empty());
+    assert_eq!(jars, ["META-INF/jars/fabric-api-base.jar"]);
+
+    let (quilt, _) = parse_quilt(
+        br#"{
+            "schema_version": 1,
+            "quilt_loader": {
+                "group": "org.quiltmc",
+                "id": "qsl",
+                "version": "6.1.2",
+                "metadata": { "name": "QSL", "contributors": { "QuiltMC": "Owner" } },
+                "depends": [
+                    { "id": "quilt_loader", "versions": ">=0.19.0" },
+                    { "id": "modmenu", "optional": true },
+                    "minecraft"
+                ]
+            },
+            "minecraft": { "environment": "dedicated_server" }
+        }"#,
+    )
+    .unwrap();
+    assert_eq!(quilt.authors, ["QuiltMC"]);
+    assert_eq!(quilt.environment, ModEnvironment::Server);
+    assert_eq!(
+        quilt
+            .dependencies
+            .iter()
+            .map(|x| x.kind)
+            .collect::<Vec<_>>(),
+        [
+            ModDependencyKind::Required,
+            ModDependencyKind::Optional,
+            ModDependencyKind::Required
+        ]
+    );
+
+    let forge = parse_forge_toml(
+        br#"
+modLoader = "javafml"
+loaderVersion = "[47,)"
+license = "MIT"
+
+[[mods]]
+modId = "examplemod"
+version = "${file.jarVersion}"
+displayName = "Example Mod"
+authors = "Alice, Bob"
+logoFile = "logo.png"
+
+[[mods]]
+modId = "examplelib"
+version = "1.0.0"
+
+[[dependencies.examplemod]]
+modId = "forge"
+mandatory = true
+versionRange = "[47,)"
+side = "BOTH"
+
+[[dependencies.examplemod]]
+modId = "jei"
+mandatory = false
+versionRange = "[15,)"
+side = "CLIENT"
+"#,
+        ModLoaderType::Forge,
+        Some("1.2.3"),
+    )
+    .unwrap();
+    assert_eq!(forge.len(), 2);
+    assert_eq!(forge[0].version, "1.2.3");
+    assert_eq!(forge[0].authors, ["Alice", "Bob"]);
+    assert_eq!(forge[0].license, "MIT");
+    assert_eq!(forge[0].dependencies[1].kind, ModDependencyKind::Optional);
+    assert_eq!(forge[0].dependencies[1].environment, ModEnvironment::Client);
+    assert_eq!(forge[1].name, "examplelib");
+
+    let neoforge = parse_forge_toml(
+        br#"
+license = "MIT"
+[[mods]]
+modId = "neomod"
+[[dependencies.neomod]]
+modId = "oldmod"
+type = "incompatible"
+"#,
+        ModLoaderType::NeoForge,
+        None,
+    )
+    .unwrap();
+    assert_eq!(neoforge[0].dependencies[0].kind, ModDependencyKind::Breaks);
+
+    let legacy = parse_mcmod_info(
+        br#"{
+            "modListVersion": 2,
+            "modList": [{
+                "modid": "jei",
+                "name": "Just Enough Items",
+                "version": "4.16.1",
+                "mcversion": "1.12.2",
+                "authorList": ["mezz"],
+                "requiredMods": ["forge@[14.23.5.2816,)"]
+            }]
+        }"#,
+    )
+    .unwrap();
+    assert_eq!(legacy[0].dependencies.len(), 2);
+    assert_eq!(legacy[0].dependencies[0].version_ranges, ["[1.12.2]"]);
+    assert_eq!(legacy[0].dependencies[1].mod_id, "forge");
+
+    let mut jar = zip::ZipWriter::new(Cursor::new(Vec::new()));
+    for (file, mods) in [
+        (
+            "META-INF/neoforge.mods.toml",
+            "[[mods]]\nmodId = \"both\"\n",
+        ),
+        (
+            "META-INF/mods.toml",
+            "[[mods]]\nmodId = \"both\"\n[[mods]]\nmodId = \"forgeonly\"\n",
+        ),
+    ] {
+        jar.start_file(file, Default::default()).unwrap();
+        std::io::Write::write_all(&mut jar, mods.as_bytes()).unwrap();
+    }
+    let mut jar = ZipArchive::new(jar.finish().unwrap()).unwrap();
+    let infos = read_mod_infos(&mut jar);
+    assert_eq!(
+        infos
+            .iter()
+            .map(|x| (x.mod_id.as_str(), x.loader))
+            .collect::<Vec<_>>(),
+        [
+            ("both", ModLoaderType::NeoForge),
+            ("forgeonly", ModLoaderType::Forge)
+        ]
+    );
+}
@@ -0,0 +1,256 @@
+//! 模组配置方案，即按名称保存的一组启用的模组
+//!
+//! 配置方案保存在版本文件夹中的 `.scl-mod-profiles.json` 里，模组以去掉 `.disabled` 后缀的文件名记录，
+//! 所以模组被启用或禁用后仍然能对应到同一个成员。应用配置方案时会启用其中的所有模组并禁用其余模组。
+
+use std::{collections::BTreeSet, path::PathBuf};
+
+use super::{mods::Mod, structs::VersionInfo};
+use crate::prelude::*;
+
+/// 配置方案文件的文件名，位于版本文件夹中
+pub const PROFILES_FILE_NAME: &str = ".scl-mod-profiles.json";
+
+/// 获取模组在配置方案中使用的名称，即去掉 `.disabled` 后缀的文件名
+pub fn profile_key(m: &Mod) -> &str {
+    m.file_name().trim_end_matches(".disabled")
+}
+
+/// 一个模组配置方案
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ModProfile {
+    /// 配置方案的名称
+    pub name: String,
+    /// 配置方案中启用的模组，参考 [`profile_key`]
+    pub mods: BTreeSet<String>,
+}
+
+/// 应用配置方案时需要进行的修改
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ModProfileDiff {
+    /// 需要启用的模组
+    pub enable: Vec<String>,
+    /// 需要禁用的模组
+    pub disable: Vec<String>,
+    /// 配置方案中存在但模组文件夹中已经找不到的模组
+    pub missing: Vec<String>,
+}
+
+impl ModProfileDiff {
+    /// 是否不需要进行任何修改
+    pub fn is_empty(&self) -> bool {
+        self.enable.is_empty() && self.disable.is_empty()
+    }
+}
+
+impl ModProfile {
+    /// 以当前已启用的模组创建一个配置方案
+    pub fn from_enabled(name: impl Into<String>, mods: &[Mod]) -> Self {
+        Self {
+            name: name.into(),
+            mods: mods
+                .iter()
+                .filter(|x| x.is_enabled())
+                .map(|x| profile_key(x).to_owned())
+                .collect(),
+        }
+    }
+
+    /// 计算应用该配置方案时需要进行的修改，不会修改任何文件
+    pub fn diff(&self, mods: &[Mod]) -> ModProfileDiff {
+        let mut diff = ModProfileDiff::default();
+        for m in mods {
+            let key = profile_key(m);
+            match (self.mods.contains(key), m.is_enabled()) {
+                (true, false) => diff.enable.push(key.to_owned()),
+                (false, true) => diff.disable.push(key.to_owned()),
+                _ => {}
+            }
+        }
+        let existing: BTreeSet<&str> = mods.iter().map(profile_key).collect();
+        diff.missing = self
+            .mods
+            .iter()
+            .filter(|x| !existing.contains(x.as_str()))
+            .cloned()
+            .collect();
+        diff
+    }
+
+    /// 应用该配置方案，启用其中的所有模组并禁用其余模组，返回进行的修改
+    pub async fn apply(&self, mods: &mut [Mod]) -> DynResult<ModProfileDiff> {
+        let diff = self.diff(mods);
+        for m in mods.iter_mut() {
+            if self.mods.contains(profile_key(m)) {
+                m.enable().await?;
+            } else {
+                m.disable().await?;
+            }
+        }
+        Ok(diff)
+    }
+}
+
+/// 一个版本的所有模组配置方案
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ModProfiles {
+    /// 所有配置方案
+    pub profiles: Vec<ModProfile>,
+    /// 上一次应用的配置方案名称
+    pub active: Option<String>,
+}
+
+impl ModProfiles {
+    fn file_path(version: &VersionInfo) -> PathBuf {
+        PathBuf::from(&version.version_base)
+            .join(&version.version)
+            .join(PROFILES_FILE_NAME)
+    }
+
+    /// 读取版本的模组配置方案，文件不存在时返回空的配置方案列表
+    pub async fn load(version: &VersionInfo) -> DynResult<Self> {
+        let path = Self::file_path(version);
+        if !path.is_file() {
+            return Ok(Self::default());
+        }
+        let data = inner_future::fs::read_to_string(path).await?;
+        Ok(serde_json::from_str(data.trim_start_matches('\u{feff}'))?)
+    }
+
+    /// 保存版本的模组配置方案
+    pub async fn save(&self, version: &VersionInfo) -> DynResult {
+        let data = serde_json::to_string_pretty(self)?;
+        inner_future::fs::write(Self::file_path(version), data).await?;
+        Ok(())
+    }
+
+    /// 获取指定名称的配置方案
+    pub fn get(&self, name: &str) -> Option<&ModProfile> {
+        self.profiles.iter().find(|x| x.name == name)
+    }
+
+    /// 获取指定名称的配置方案的可变引用
+    pub fn get_mut(&mut self, name: &str) -> Option<&mut ModProfile> {
+        self.profiles.iter_mut().find(|x| x.name == name)
+    }
+
+    /// 添加或替换同名的配置方案
+    pub fn set(&mut self, profile: ModProfile) {
+        match self.get_mut(&profile.name) {
+            Some(existing) => *existing = profile,
+            None => self.profiles.push(profile),
+        }
+    }
+
+    /// 删除指定名称的配置方案，返回被删除的配置方案
+    pub fn remove(&mut self, name: &str) -> Option<ModProfile> {
+        let index = self.profiles.iter().position(|x| x.name == name)?;
+        if self.active.as_deref() == Some(name) {
+            self.active = None;
+        }
+        Some(self.profiles.remove(index))
+    }
+
+    /// 获取不属于任何配置方案的模组，通常是新放入的模组
+    pub fn unassigned<'a>(&self, mods: &'a [Mod]) -> Vec<&'a Mod> {
+        mods.iter()
+            .filter(|m| {
+                let key = profile_key(m);
+                !self.profiles.iter().any(|p| p.mods.contains(key))
+            })
+            .collect()
+    }
+}
+
+impl VersionInfo {
+    /// 应用指定名称的模组配置方案，并将其记录为当前使用的配置方案
+    ///
+    /// 如果 `dry_run` 为 `true` 则只计算需要进行的修改，不会修改任何文件
+    pub async fn apply_mod_profile(&self, name: &str, dry_run: bool) -> DynResult<ModProfileDiff> {
+        let mut profiles = ModProfiles::load(self).await?;
+        let profile = profiles
+            .get(name)
+            .ok_or_else(|| anyhow::anyhow!("找不到模组配置方案 {}", name))?
+            .to_owned();
+        let mut mods = self.get_mods().await?;
+        if dry_run {
+            return Ok(profile.diff(&mods));
+        }
+        let diff = profile.apply(&mut mods).await?;
+        profiles.active = Some(name.to_owned());
+        profiles.save(self).await?;
+        Ok(diff)
+    }
+}
+
+#[test]
+fn mod_profile_test() {
+    let dir = std::env::temp_dir().join(format!("scl-mod-profile-test-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let mods_dir = dir.join("versions").join("pack").join("mods");
+    std::fs::create_dir_all(&mods_dir).unwrap();
+    for file in ["sodium.jar", "worldedit.jar", "litematica.jar.disabled"] {
+        std::fs::write(mods_dir.join(file), b"").unwrap();
+    }
+    let version = VersionInfo {
+        version_base: dir.join("versions").to_string_lossy().into_owned(),
+        version: "pack".into(),
+        scl_launch_config: Some(super::structs::SCLLaunchConfig {
+            game_independent: true,
+            ..Default::default()
+        }),
+        ..Default::default()
+    };
+
+    inner_future::block_on(async {
+        let mut mods = version.get_mods().await.unwrap();
+        mods.sort_by(|a, b| a.file_name().cmp(b.file_name()));
+        let mut profiles = ModProfiles::load(&version).await.unwrap();
+        assert!(profiles.profiles.is_empty());
+        profiles.set(ModProfile::from_enabled("performance", &mods));
+        profiles.set(ModProfile {
+            name: "building".into(),
+            mods: ["worldedit.jar", "litematica.jar", "axiom.jar"]
+                .map(String::from)
+                .into(),
+        });
+        profiles.save(&version).await.unwrap();
+        assert_eq!(
+            profiles.get("performance").unwrap().mods,
+            ["sodium.jar", "worldedit.jar"].map(String::from).into()
+        );
+
+        let dry = version.apply_mod_profile("building", true).await.unwrap();
+        assert_eq!(
+            dry,
+            ModProfileDiff {
+                enable: vec!["litematica.jar".into()],
+                disable: vec!["sodium.jar".into()],
+                missing: vec!["axiom.jar".into()],
+            }
+        );
+        assert!(mods_dir.join("sodium.jar").is_file());
+
+        assert_eq!(
+            version.apply_mod_profile("building", false).await.unwrap(),
+            dry
+        );
+        assert!(mods_dir.join("sodium.jar.disabled").is_file());
+        assert!(mods_dir.join("litematica.jar").is_file());
+        let profiles = ModProfiles::load(&version).await.unwrap();
+        assert_eq!(profiles.active.as_deref(), Some("building"));
+        assert!(version.apply_mod_profile("full", true).await.is_err());
+
+        std::fs::write(mods_dir.join("iris.jar"), b"").unwrap();
+        let mods = version.get_mods().await.unwrap();
+        let unassigned: Vec<_> = profiles
+            .unassigned(&mods)
+            .iter()
+            .map(|x| x.file_name())
+            .collect();
+        assert_eq!(unassigned, ["iris.jar"]);
+    });
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,500 @@
+//! 模组的更新检查及更新
+//!
+//! 基于 [`super::mod_identify`] 的识别结果，从 Modrinth 或 CurseForge 查询适用于当前版本的模组加载器和游戏版本的新版本，
+//! 更新时会先下载并校验新文件，再将旧文件禁用或移动到备份文件夹，以便在新版本出现问题时回滚。
+
+use std::path::{Path, PathBuf};
+
+use super::{mod_check::ModCheckTarget, mod_identify::ModIdentity, VersionType};
+use crate::{
+    download::{curseforge, modrinth},
+    prelude::*,
+    semver::MinecraftVersion,
+};
+
+/// 更新日志摘要的默认最大长度，单位为字符
+pub const CHANGELOG_SUMMARY_LEN: usize = 300;
+
+/// 同时向 CurseForge 查询模组文件列表的最大请求数，避免触发频率限制
+const CURSEFORGE_CONCURRENCY: usize = 8;
+
+/// 模组更新的来源
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ModUpdateSource {
+    /// 来自 Modrinth
+    Modrinth {
+        /// 模组的 ID
+        project_id: String,
+        /// 新版本的 ID
+        version_id: String,
+    },
+    /// 来自 CurseForge
+    CurseForge {
+        /// 模组的 ID
+        mod_id: u64,
+        /// 新文件的 ID
+        file_id: u64,
+    },
+}
+
+/// 一个可用的模组更新
+#[derive(Debug, Clone)]
+pub struct ModUpdate {
+    /// 当前模组的文件名
+    pub file_name: String,
+    /// 当前模组的文件所在路径
+    pub path: PathBuf,
+    /// 更新的来源
+    pub source: ModUpdateSource,
+    /// 当前的版本名称
+    pub current_version: String,
+    /// 新版本的名称
+    pub new_version: String,
+    /// 新版本的文件名
+    pub new_file_name: String,
+    /// 新版本的下载链接
+    pub download_url: String,
+    /// 新版本文件的 SHA-1 摘要，为空时代表未知
+    pub sha1: String,
+    /// 新版本文件的 MD5 摘要，为空时代表未知，仅在没有 SHA-1 摘要时使用
+    pub md5: String,
+    /// 新版本文件的大小，为 `0` 时代表未知，仅在没有任何摘要时使用
+    pub size: u64,
+    /// 新版本的发布时间
+    pub date_published: String,
+    /// 新版本的更新日志摘要，参考 [`summarize_changelog`]
+    pub changelog: String,
+}
+
+/// 更新模组时旧文件的保留方式
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum ModBackup {
+    /// 将旧文件重命名为 `.jar.disabled` 并留在模组文件夹中
+    #[default]
+    Disable,
+    /// 将旧文件移动到指定的备份文件夹中
+    Folder(PathBuf),
+}
+
+/// 一个已经完成的模组更新，可用于回滚
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AppliedModUpdate {
+    /// 旧文件原本所在的路径
+    pub original_path: PathBuf,
+    /// 旧文件现在所在的路径
+    pub backup_path: PathBuf,
+    /// 新文件所在的路径
+    pub new_path: PathBuf,
+}
+
+impl AppliedModUpdate {
+    /// 删除新文件并恢复旧文件
+    pub async fn rollback(self) -> DynResult {
+        if self.new_path != self.original_path && self.original_path.exists() {
+            anyhow::bail!(
+                "无法回滚模组更新，文件 {} 已存在",
+                self.original_path.to_string_lossy()
+            );
+        }
+        if self.new_path.is_file() {
+            inner_future::fs::remove_file(&self.new_path).await?;
+        }
+        inner_future::fs::rename(&self.backup_path, &self.original_path).await?;
+        Ok(())
+    }
+}
+
+fn modrinth_loaders(version_type: VersionType) -> Vec<String> {
+    match version_type {
+        VersionType::Fabric => vec!["fabric".into()],
+        VersionType::QuiltMC => vec!["quilt".into(), "fabric".into()],
+        VersionType::Forge => vec!["forge".into()],
+        VersionType::NeoForge => vec!["neoforge".into()],
+        _ => vec![],
+    }
+}
+
+fn curseforge_loader(version_type: VersionType) -> curseforge::ModLoaderType {
+    match version_type {
+        VersionType::Fabric => curseforge::ModLoaderType::Fabric,
+        // CurseForge 上的模组大多只标记了 Fabric，所以 Quilt 版本也按照 Fabric 筛选
+        VersionType::QuiltMC => curseforge::ModLoaderType::Fabric,
+        VersionType::Forge => curseforge::ModLoaderType::Forge,
+        VersionType::NeoForge => curseforge::ModLoaderType::NeoForge,
+        _ => curseforge::ModLoaderType::Any,
+    }
+}
+
+fn decode_html_entities(text: &str) -> String {
+    text.replace("&nbsp;", " ")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&amp;", "&")
+}
+
+/// 将 Markdown 或 HTML 格式的更新日志转换为适合直接显示的纯文本摘要
+///
+/// 会去除 HTML 标签、标题符号和空行，超过最大长度时截断并以 `…` 结尾
+pub fn summarize_changelog(changelog: &str, max_len: usize) -> String {
+    let mut text = String::with_capacity(changelog.len());
+    let mut in_tag = false;
+    for c in changelog.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' if in_tag => {
+                in_tag = false;
+                // 块级标签通常代表换行
+                text.push('\n');
+            }
+            _ if !in_tag => text.push(c),
+            _ => {}
+        }
+    }
+    let text = decode_html_entities(&text);
+    let lines: Vec<_> = text
+        .lines()
+        .map(|x| x.trim().trim_start_matches('#').trim())
+        .filter(|x| !x.is_empty())
+        .collect();
+    let summary = lines.join("\n");
+    if summary.chars().count() > max_len {
+        let mut result: String = summary.chars().take(max_len).collect();
+        result.push('…');
+        result
+    } else {
+        summary
+    }
+}
+
+/// 检查模组是否有适用于当前版本的更新，返回所有可用的更新
+///
+/// 同时在两个平台上识别到的模组会优先使用 Modrinth 的结果。
+/// 某个平台或某个模组查询失败时只会记录警告并跳过，不会影响其它模组的结果。
+pub async fn check_mod_updates(
+    identities: &[ModIdentity],
+    target: &ModCheckTarget,
+) -> DynResult<Vec<ModUpdate>> {
+    let game_versions = match target.minecraft_version {
+        MinecraftVersion::Release(..) => vec![target.minecraft_version.to_string()],
+        _ => vec![],
+    };
+    let mut updates = vec![];
+
+    let modrinth_identities: Vec<_> = identities.iter().filter(|x| x.modrinth.is_some()).collect();
+    if !modrinth_identities.is_empty() {
+        let hashes: Vec<_> = modrinth_identities
+            .iter()
+            .map(|x| x.hashes.sha512.to_owned())
+            .collect();
+        let latest = match modrinth::get_latest_versions_by_hashes(
+            &hashes,
+            modrinth::HashAlgorithm::Sha512,
+            &modrinth_loaders(target.version_type),
+            &game_versions,
+        )
+        .await
+        {
+            Ok(latest) => latest,
+            Err(err) => {
+                tracing::warn!("无法从 Modrinth 检查模组更新：{:?}", err);
+                Default::default()
+            }
+        };
+        for identity in modrinth_identities {
+            let current = identity.modrinth.as_ref().unwrap();
+            let Some(version) = latest.get(&identity.hashes.sha512) else {
+                continue;
+            };
+            if version.id == current.id || version.date_published <= current.date_published {
+                continue;
+            }
+            let Some(file) = version
+                .files
+                .iter()
+                .find(|x| x.primary)
+                .or_else(|| version.files.first())
+            else {
+                continue;
+            };
+            updates.push(ModUpdate {
+                file_name: identity.file_name.to_owned(),
+                path: identity.path.to_owned(),
+                source: ModUpdateSource::Modrinth {
+                    project_id: version.project_id.to_owned(),
+                    version_id: version.id.to_owned(),
+                },
+                current_version: current.version_number.to_owned(),
+                new_version: version.version_number.to_owned(),
+                new_file_name: file.filename.to_owned(),
+                download_url: file.url.to_owned(),
+                sha1: file.hashes.get("sha1").cloned().unwrap_or_default(),
+                md5: String::new(),
+                size: file.size,
+                date_published: version.date_published.to_owned(),
+                changelog: summarize_changelog(&version.changelog, CHANGELOG_SUMMARY_LEN),
+            });
+        }
+    }
+
+    let game_version = game_versions
+        .first()
+        .map(|x| x.as_str())
+        .unwrap_or_default();
+    let curseforge_identities: Vec<_> = identities
+        .iter()
+        .filter(|x| x.modrinth.is_none())
+        .filter_map(|x| Some((x, x.curseforge.as_ref()?)))
+        .collect();
+    let mut curseforge_results = Vec::with_capacity(curseforge_identities.len());
+    for chunk in curseforge_identities.chunks(CURSEFORGE_CONCURRENCY) {
+        curseforge_results.extend(
+            futures::future::join_all(chunk.iter().map(|(identity, current)| async move {
+                let files = curseforge::get_mod_files_filtered(
+                    current.mod_id,
+                    game_version,
+                    curseforge_loader(target.version_type),
+                )
+                .await;
+                (*identity, *current, files)
+            }))
+            .await,
+        );
+    }
+    for (identity, current, files) in curseforge_results {
+        let files = match files {
+            Ok(files) => files,
+            Err(err) => {
+                tracing::warn!(
+                    "无法从 CurseForge 检查模组 {} 的更新：{:?}",
+                    identity.file_name,
+                    err
+                );
+                continue;
+            }
+        };
+        let Some(file) = files
+            .into_iter()
+            .filter(|x| x.id != current.id && x.file_date > current.file_date)
+            .filter(|x| !x.download_url.is_empty())
+            .max_by(|a, b| a.file_date.cmp(&b.file_date))
+        else {
+            continue;
+        };
+        let changelog = curseforge::get_mod_file_changelog(file.mod_id, file.id)
+            .await
+            .unwrap_or_default();
+        updates.push(ModUpdate {
+            file_name: identity.file_name.to_owned(),
+            path: identity.path.to_owned(),
+            source: ModUpdateSource::CurseForge {
+                mod_id: file.mod_id,
+                file_id: file.id,
+            },
+            current_version: current.display_name.to_owned(),
+            new_version: file.display_name.to_owned(),
+            sha1: file
+                .hash(curseforge::HashAlgo::Sha1)
+                .unwrap_or_default()
+                .to_owned(),
+            md5: file
+                .hash(curseforge::HashAlgo::Md5)
+                .unwrap_or_default()
+                .to_owned(),
+            size: file.file_length,
+            new_file_name: file.file_name,
+            download_url: file.download_url,
+            date_published: file.file_date,
+            changelog: summarize_changelog(&changelog, CHANGELOG_SUMMARY_LEN),
+        });
+    }
+    Ok(updates)
+}
+
+/// 获取不会覆盖已有文件的备份路径，文件名冲突时会在扩展名前加上序号，例如 `sodium (1).jar`
+fn unique_backup_path(dir: &Path, file_name: &str, suffix: &str) -> PathBuf {
+    let (stem, ext) = match file_name.rsplit_once('.') {
+        Some((stem, ext)) if !stem.is_empty() => (stem, format!(".{ext}")),
+        _ => (file_name, String::new()),
+    };
+    (0..)
+        .map(|i| match i {
+            0 => dir.join(format!("{file_name}{suffix}")),
+            i => dir.join(format!("{stem} ({i}){ext}{suffix}")),
+        })
+        .find(|x| !x.exists())
+        .expect("序号不会耗尽")
+}
+
+/// 将下载好的新文件替换到模组文件夹中，旧文件会被移动到备份路径，失败时会恢复旧文件
+async fn replace_mod_file(
+    original_path: &Path,
+    downloaded_path: &Path,
+    new_path: &Path,
+    backup_path: &Path,
+) -> DynResult<AppliedModUpdate> {
+    if new_path != original_path && new_path.exists() {
+        anyhow::bail!("模组文件 {} 已存在", new_path.to_string_lossy());
+    }
+    if let Some(parent) = backup_path.parent() {
+        inner_future::fs::create_dir_all(parent).await?;
+    }
+    if backup_path.exists() {
+        anyhow::bail!("备份文件 {} 已存在", backup_path.to_string_lossy());
+    }
+    inner_future::fs::rename(original_path, backup_path).await?;
+    if let Err(err) = inner_future::fs::rename(downloaded_path, new_path).await {
+        inner_future::fs::rename(backup_path, original_path).await?;
+        let _ = inner_future::fs::remove_file(downloaded_path).await;
+        return Err(err.into());
+    }
+    Ok(AppliedModUpdate {
+        original_path: original_path.to_owned(),
+        backup_path: backup_path.to_owned(),
+        new_path: new_path.to_owned(),
+    })
+}
+
+impl ModUpdate {
+    /// 校验下载好的新文件，优先使用 SHA-1 摘要，其次是 MD5 摘要和文件大小
+    async fn verify(&self, path: &Path) -> DynResult {
+        let data = inner_future::fs::read(path).await?;
+        let (algo, expected, current) = if !self.sha1.is_empty() {
+            (
+                "SHA-1",
+                self.sha1.to_owned(),
+                sha1_smol::Sha1::from(&data).hexdigest(),
+            )
+        } else if !self.md5.is_empty() {
+            (
+                "MD5",
+                self.md5.to_owned(),
+                format!("{:x}", md5::compute(&data)),
+            )
+        } else if self.size != 0 {
+            tracing::warn!(
+                "模组文件 {} 没有提供 SHA-1 或 MD5 摘要，只能校验文件大小",
+                self.new_file_name
+            );
+            ("文件大小", self.size.to_string(), data.len().to_string())
+        } else {
+            anyhow::bail!(
+                "模组文件 {} 没有提供摘要和文件大小，无法校验文件",
+                self.new_file_name
+            );
+        };
+        if !expected.eq_ignore_ascii_case(&current) {
+            anyhow::bail!(
+                "模组文件 {} 校验失败，期望 {} 为 {}，实际为 {}",
+                self.new_file_name,
+                algo,
+                expected,
+                current
+            );
+        }
+        Ok(())
+    }
+
+    /// 下载新版本并替换当前模组，旧文件按照指定的方式保留，可以通过返回值回滚更新
+    ///
+    /// 新文件会先下载到临时文件并依次按 SHA-1、MD5 或文件大小校验，全部完成后才会替换，
+    /// 所以下载失败时不会影响当前模组，没有任何校验信息的更新会被拒绝；
+    /// 备份路径已有同名文件时会自动改用带序号的文件名，不会覆盖已有文件；
+    /// 如果当前模组已被禁用，则新文件同样会被禁用，此时只能使用 [`ModBackup::Folder`] 保留旧文件
+    pub async fn apply(&self, backup: &ModBackup) -> DynResult<AppliedModUpdate> {
+        let mods_dir = self
+            .path
+            .parent()
+            .ok_or_else(|| anyhow::anyhow!("模组文件路径不正确"))?;
+        crate::utils::check_file_name(&self.new_file_name)?;
+        let disabled = self.file_name.ends_with(".disabled");
+        let new_file_name = if disabled {
+            format!("{}.disabled", self.new_file_name)
+        } else {
+            self.new_file_name.to_owned()
+        };
+        let backup_path = match backup {
+            ModBackup::Disable if disabled => {
+                anyhow::bail!(
+                    "模组 {} 已被禁用，请使用备份文件夹保留旧文件",
+                    self.file_name
+                )
+            }
+            ModBackup::Disable => unique_backup_path(mods_dir, &self.file_name, ".disabled"),
+            ModBackup::Folder(dir) => unique_backup_path(dir, &self.file_name, ""),
+        };
+
+        let downloaded_path = mods_dir.join(format!("{new_file_name}.download"));
+        let downloaded_path_str = downloaded_path.to_string_lossy().to_string();
+        crate::http::download(&[&self.download_url], &downloaded_path_str, 0).await?;
+        if let Err(err) = self.verify(&downloaded_path).await {
+            let _ = inner_future::fs::remove_file(&downloaded_path).await;
+            return Err(err);
+        }
+        replace_mod_file(
+            &self.path,
+            &downloaded_path,
+            &mods_dir.join(new_file_name),
+            &backup_path,
+        )
+        .await
+    }
+}
+
+#[test]
+fn mod_update_test() {
+    assert_eq!(
+        summarize_changelog(
+            "## Changes\n\n- Fixed crash &amp; lag\n\n\n- Updated to 1.20.1",
+            CHANGELOG_SUMMARY_LEN
+        ),
+        "Changes\n- Fixed crash & lag\n- Updated to 1.20.1"
+    );
+    assert_eq!(
+        summarize_changelog("<p>Fixed <b>rendering</b></p><ul><li>Item</li></ul>", 100),
+        "Fixed\nrendering\nItem"
+    );
+    assert_eq!(summarize_changelog("abcdef", 3), "abc…");
+
+    let dir = std::env::temp_dir().join(format!("scl-mod-update-test-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    let original = dir.join("sodium-0.5.3.jar");
+    let downloaded = dir.join("sodium-0.5.4.jar.download");
+    std::fs::write(&original, b"old").unwrap();
+    std::fs::write(&downloaded, b"new").unwrap();
+    let applied = inner_future::block_on(replace_mod_file(
+        &original,
+        &downloaded,
+        &dir.join("sodium-0.5.4.jar"),
+        &dir.join("sodium-0.5.3.jar.disabled"),
+    ))
+    .unwrap();
+    assert!(!original.exists());
+    assert_eq!(std::fs::read(&applied.new_path).unwrap(), b"new");
+    assert_eq!(std::fs::read(&applied.backup_path).unwrap(), b"old");
+    inner_future::block_on(applied.rollback()).unwrap();
+    assert_eq!(std::fs::read(&original).unwrap(), b"old");
+    assert!(!dir.join("sodium-0.5.4.jar").exists());
+    assert!(!dir.join("sodium-0.5.3.jar.disabled").exists());
+
+    // 已有同名的禁用模组时不会覆盖它
+    std::fs::write(dir.join("sodium-0.5.3.jar.disabled"), b"user").unwrap();
+    assert_eq!(
+        unique_backup_path(&dir, "sodium-0.5.3.jar", ".disabled"),
+        dir.join("sodium-0.5.3 (1).jar.disabled")
+    );
+    assert!(inner_future::block_on(replace_mod_file(
+        &original,
+        &downloaded,
+        &dir.join("sodium-0.5.4.jar"),
+        &dir.join("sodium-0.5.3.jar.disabled"),
+    ))
+    .is_err());
+    assert_eq!(
+        std::fs::read(dir.join("sodium-0.5.3.jar.disabled")).unwrap(),
+        b"user"
+    );
+    std::fs::remove_dir_all(&dir).unwrap();
+}
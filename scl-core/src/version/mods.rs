@@ -8,6 +8,7 @@ use std::{
 use anyhow::Context;
 use image::DynamicImage;
 
+use super::mod_info::{read_mod_infos, ModInfo};
 use crate::prelude::*;
 
 /// 模组文件数据
@@ -140,7 +141,26 @@ impl Mod {
         &self.path
     }
 
+    /// 尝试获取模组文件中所有模组的完整元数据，包括嵌套在其中的模组（Jar-in-Jar）
+    ///
+    /// 支持 Fabric、Quilt、Forge 和 NeoForge 模组，顶层模组总是排在嵌套模组之前
+    pub async fn try_get_mod_infos(&self) -> DynResult<Vec<ModInfo>> {
+        let path = self.path.to_owned();
+        inner_future::unblock(move || -> DynResult<Vec<ModInfo>> {
+            let mut z = zip::ZipArchive::new(
+                std::fs::OpenOptions::new()
+                    .read(true)
+                    .open(&path)
+                    .with_context(|| format!("无法打开模组文件 {}", path.to_string_lossy()))?,
+            )?;
+            Ok(read_mod_infos(&mut z))
+        })
+        .await
+    }
+
     /// 尝试获取模组的元数据信息
+    ///
+    /// 此处只会返回 Fabric 或 Forge 格式的第一个模组，如需获取完整的元数据请使用 [`Mod::try_get_mod_infos`]
     pub async fn try_get_mod_meta(&self) -> DynResult<ModMeta> {
         let path = self.path.to_owned();
         inner_future::unblock(move || -> DynResult<ModMeta> {
@@ -151,13 +171,18 @@ impl Mod {
                     .with_context(|| format!("无法打开模组文件 {}", path.to_string_lossy()))
                     .with_context(|| format!("无法读取模组文件 {}", path.to_string_lossy()))?,
             )?;
+            let mods_toml = if z.by_name("META-INF/neoforge.mods.toml").is_ok() {
+                "META-INF/neoforge.mods.toml"
+            } else {
+                "META-INF/mods.toml"
+            };
             if let Ok(meta) = z.by_name("fabric.mod.json").and_then(|r| {
                 serde_json::from_reader::<_, FabricModMeta>(r)
                     .map_err(|_| zip::result::ZipError::InvalidArchive("fabric.mod.json"))
             }) {
                 // fabric.mod.json https://github.com/FabricMC/fabric-example-mod/blob/1.19/src/main/resources/fabric.mod.json
                 Ok(ModMeta::Fabric(meta))
-            } else if let Ok(meta) = z.by_name("mods.toml").and_then(|mut r| {
+            } else if let Ok(meta) = z.by_name(mods_toml).and_then(|mut r| {
                 // mods.toml https://docs.minecraftforge.net/en/1.19.x/gettingstarted/structuring/
                 let mut buf = String::with_capacity(r.size() as _);
                 r.read_to_string(&mut buf).unwrap_or_default();
@@ -182,25 +207,25 @@ impl Mod {
         .await
     }
 
-    /// [`Mod::try_get_mod_meta`] 的语法糖，尝试根据模组元数据获取模组名称
+    /// [`Mod::try_get_mod_infos`] 的语法糖，尝试根据模组元数据获取第一个模组的名称
     pub async fn try_get_mod_name(&self) -> DynResult<String> {
-        Ok(self.try_get_mod_meta().await?.name().to_owned())
+        self.try_get_mod_infos()
+            .await?
+            .into_iter()
+            .next()
+            .map(|x| x.name)
+            .ok_or_else(|| anyhow::anyhow!("Mod name not found"))
     }
 
-    /// [`Mod::try_get_mod_meta`] 的语法糖，尝试根据模组元数据获取模组的其中一个图标
+    /// [`Mod::try_get_mod_infos`] 的语法糖，尝试根据模组元数据获取第一个模组的图标
     pub async fn try_get_mod_icon(&self) -> DynResult<DynamicImage> {
-        let icon_path = match self.try_get_mod_meta().await? {
-            ModMeta::Fabric(meta) => match meta.icon {
-                Some(FabricModIcon::Multiply(icon)) => {
-                    icon.iter().next().map(|x| x.0.to_owned()).ok_or_else(|| {
-                        anyhow::anyhow!("Can't find icon from such multiply fabric mod icons")
-                    })?
-                }
-                Some(FabricModIcon::Single(icon)) => icon,
-                None => anyhow::bail!("Can't find icon from such multiply fabric mod icons"),
-            },
-            ModMeta::Forge(meta) => meta.logo_file,
-        };
+        let icon_path = self
+            .try_get_mod_infos()
+            .await?
+            .into_iter()
+            .find(|x| !x.is_nested())
+            .and_then(|x| x.icon)
+            .ok_or_else(|| anyhow::anyhow!("Can't find icon from mod metadata"))?;
         let path = self.path.to_owned();
         inner_future::unblock(move || -> DynResult<DynamicImage> {
             let mut z = zip::ZipArchive::new(std::fs::OpenOptions::new().read(true).open(path)?)?;
@@ -253,6 +253,8 @@ pub struct SCLLaunchConfig {
     pub window_title: String,
     /// 额外的 JVM 参数，将会附加到 Class Path 前面
     pub jvm_args: String,
+    /// 使用的 JVM 参数预设，如果提供则会覆盖 [`crate::client::ClientConfig::jvm_args_preset`]
+    pub jvm_args_preset: Option<crate::jvm_args::JvmArgsPreset>,
     /// 额外的游戏参数，将会附加到参数末尾
     pub game_args: String,
     /// 包装器执行文件路径，对于某些 Linux 用户有用，用于指定 Java 前的执行文件
@@ -657,6 +659,25 @@ impl VersionInfo {
         Ok(results)
     }
 
+    /// 检查该版本下所有已启用模组的依赖关系及冲突，无法读取元数据的模组会被忽略
+    pub async fn check_mods(&self) -> DynResult<Vec<super::mod_check::ModIssue>> {
+        let mut files = vec![];
+        for m in self.get_mods().await? {
+            if !m.is_enabled() {
+                continue;
+            }
+            files.push(super::mod_check::ModFile {
+                file_name: m.file_name().to_owned(),
+                mods: m.try_get_mod_infos().await.unwrap_or_default(),
+            });
+        }
+        let target = super::mod_check::ModCheckTarget {
+            version_type: self.guess_version_type(),
+            minecraft_version: self.minecraft_version.to_owned(),
+        };
+        Ok(super::mod_check::check_mods(&files, &target))
+    }
+
     /// 根据版本实际情况获取最佳的最大内存用量，单位为 MB
     ///
     /// 代码参考自 <https://github.com/Hex-Dragon/PCL2/blob/f1310f18fda13b79b7a6189f02df15cd8300b28d/Plain%20Craft%20Launcher%202/Pages/PageSetup/PageSetupLaunch.xaml.vb#L327>
@@ -725,28 +746,16 @@ impl VersionInfo {
         Ok(result)
     }
 
-    /// 读取该版本下的所有资源包信息
-    ///
-    /// 目前只是简单的扫一遍文件夹而已
-    ///
-    /// TODO: 做准确的解析过滤
-    pub async fn get_resources_packs(&self) -> DynResult<Vec<ResourcesPack>> {
-        let resourcepacks_path = self.version_path().join("resourcepacks");
-        let texturepacks_path = self.version_path().join("texturepacks");
-        let mut result = Vec::new();
-        if resourcepacks_path.is_dir() {
-            let mut files = inner_future::fs::read_dir(&resourcepacks_path).await?;
-            while let Some(_file) = files.try_next().await? {
-                result.push(ResourcesPack {})
-            }
-        }
-        if texturepacks_path.is_dir() {
-            let mut files = inner_future::fs::read_dir(&texturepacks_path).await?;
-            while let Some(_file) = files.try_next().await? {
-                result.push(ResourcesPack {})
-            }
-        }
-        Ok(result)
+    /// 获取该版本游戏目录的内容管理器，可用于管理模组、资源包、光影包和数据包
+    pub fn content_manager(&self) -> super::content::ContentManager {
+        super::content::ContentManager::new(self.version_path())
+    }
+
+    /// 读取该版本下的所有资源包信息，包括已禁用的资源包和旧版本 `texturepacks` 文件夹中的材质包
+    pub async fn get_resources_packs(&self) -> DynResult<Vec<super::content::ContentFile>> {
+        self.content_manager()
+            .list(&super::content::ContentKind::ResourcePack)
+            .await
     }
 }
 
@@ -756,12 +765,6 @@ pub struct WorldSave {
     // TODO
 }
 
-/// 一个资源包的信息结构
-#[derive(Debug)]
-pub struct ResourcesPack {
-    // TODO
-}
-
 fn string_or_seq<'de, D>(deserializer: D) -> Result<Vec<String>, D::Error>
 where
     D: Deserializer<'de>,
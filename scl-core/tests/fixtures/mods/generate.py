@@ -0,0 +1,124 @@
+# 生成模组依赖检查测试所使用的模组文件，修改后需要重新运行此脚本
+# python3 generate.py
+
+import io
+import json
+import os
+import zipfile
+
+HERE = os.path.dirname(os.path.abspath(__file__))
+
+
+def jar(entries):
+    buf = io.BytesIO()
+    with zipfile.ZipFile(buf, "w", zipfile.ZIP_DEFLATED) as z:
+        for name, data in entries.items():
+            if isinstance(data, (dict, list)):
+                data = json.dumps(data, indent=2)
+            if isinstance(data, str):
+                data = data.encode("utf-8")
+            info = zipfile.ZipInfo(name, date_time=(2024, 1, 1, 0, 0, 0))
+            info.compress_type = zipfile.ZIP_DEFLATED
+            z.writestr(info, data)
+    return buf.getvalue()
+
+
+def fabric(mod_id, version, **extra):
+    meta = {"schemaVersion": 1, "id": mod_id, "version": version}
+    meta.update(extra)
+    return meta
+
+
+FIXTURES = {
+    "sodium-fabric-0.5.3.jar": {
+        "fabric.mod.json": fabric(
+            "sodium",
+            "0.5.3",
+            name="Sodium",
+            depends={
+                "fabricloader": ">=0.12.0",
+                "minecraft": "1.20.x",
+                "fabric-api": ">=0.80.0",
+            },
+        ),
+    },
+    "sodium-fabric-0.5.3-copy.jar": {
+        "fabric.mod.json": fabric("sodium", "0.5.3", name="Sodium"),
+    },
+    "iris-fabric-1.6.4.jar": {
+        "fabric.mod.json": fabric(
+            "iris",
+            "1.6.4",
+            name="Iris",
+            depends={
+                "minecraft": ["1.20", "1.20.1"],
+                "sodium": ">=0.5.4",
+                "fabric-api-base": "*",
+            },
+            breaks={"optifabric": "*"},
+        ),
+    },
+    "optifabric-1.14.3.jar": {
+        "fabric.mod.json": fabric("optifabric", "1.14.3", name="OptiFabric"),
+    },
+    "library-bundle-1.0.0.jar": {
+        "fabric.mod.json": fabric(
+            "library_bundle",
+            "1.0.0",
+            jars=[{"file": "META-INF/jars/fabric-api-base-0.4.31.jar"}],
+        ),
+        "META-INF/jars/fabric-api-base-0.4.31.jar": jar(
+            {"fabric.mod.json": fabric("fabric-api-base", "0.4.31")}
+        ),
+    },
+    "jei-forge-15.2.0.jar": {
+        "META-INF/MANIFEST.MF": "Manifest-Version: 1.0\nImplementation-Version: 15.2.0\n",
+        "META-INF/mods.toml": """modLoader = "javafml"
+loaderVersion = "[47,)"
+license = "MIT"
+
+[[mods]]
+modId = "jei"
+version = "${file.jarVersion}"
+displayName = "Just Enough Items"
+
+[[dependencies.jei]]
+modId = "forge"
+mandatory = true
+versionRange = "[47.1.3,)"
+side = "BOTH"
+
+[[dependencies.jei]]
+modId = "minecraft"
+mandatory = true
+versionRange = "[1.20.1,1.20.2)"
+side = "BOTH"
+""",
+    },
+    "old-forge-mod-1.0.jar": {
+        "META-INF/mods.toml": """modLoader = "javafml"
+loaderVersion = "[40,)"
+license = "All rights reserved"
+
+[[mods]]
+modId = "oldmod"
+version = "1.0"
+
+[[dependencies.oldmod]]
+modId = "minecraft"
+mandatory = true
+versionRange = "[1.18,1.19)"
+side = "BOTH"
+
+[[dependencies.oldmod]]
+modId = "jei"
+mandatory = false
+versionRange = "[16,)"
+side = "CLIENT"
+""",
+    },
+}
+
+for name, entries in FIXTURES.items():
+    with open(os.path.join(HERE, name), "wb") as f:
+        f.write(jar(entries))